@@ -0,0 +1,440 @@
+use regex::Regex;
+use crate::utils;
+
+/// The class of the helper applying the sections of a cascade
+/// which cannot be put before its statement
+const HELPERS : &str = "DartCascades";
+
+/// Lower the cascades `x..a = 1..b()` and the null-aware cascades
+/// `x?..a = 1..b()`, which C# does not have.
+///
+/// - If the target is a newly constructed object, and every section is
+///   a plain assignment, the cascade becomes an object initializer
+///   `X() { a = 1, b = 2 }`
+/// - As a statement, the cascade becomes a statement for each section,
+///   `x.a = 1; x.b();`, guarded by `if (x != null)` if null-aware
+/// - As an arrow body, the body becomes a block body, which runs the
+///   sections and returns the target
+/// - Elsewhere, the statements are put before the statement containing
+///   the cascade, which then uses the target, unless the cascade is
+///   evaluated conditionally or repeatedly, like in the condition of an
+///   `if` or after `&&`. Then it becomes a call of a generated helper,
+///   `DartCascades.Apply(x, _cascade0 => { _cascade0.a = 1; })`
+///
+/// A target which is not a simple variable or member is evaluated only
+/// once, into a temporary variable. Cascades nested in the sections are
+/// lowered after the outer ones.
+pub fn transpile_cascades(input: &str) -> String {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"\?\.\.|\.\.\.?").unwrap();
+	}
+
+	let mut text = input.to_string();
+	let mut temps = 0;
+	let mut from = 0;
+	let mut helpers = false;
+	while let Some(mat) = RE.find_at(&text, from) {
+		if mat.as_str() == "..." {
+			from = mat.end();
+			continue;
+		}
+		match lower_cascade(&text, mat.start(), mat.end(), &mut temps, &mut helpers) {
+			Some((start, lowered)) => {
+				text = lowered;
+				from = start;
+			},
+			None => from = mat.end(),
+		}
+	}
+	if !helpers {
+		return text;
+	}
+	format!("{}\n\npublic static class {}\n{{\n\tpublic static T Apply<T>(T target, Action<T> sections)\n\t{{\n\t\tsections(target);\n\t\treturn target;\n\t}}\n}}\n",
+		text.trim_end(), HELPERS)
+}
+
+/// Lower the cascade whose first operator is at `op..op_end`, returning
+/// the position from which the text is changed and the whole new text
+fn lower_cascade(text: &str, op: usize, op_end: usize, temps: &mut usize, helpers: &mut bool) -> Option<(usize, String)> {
+	lazy_static! {
+		static ref JUMP : Regex = Regex::new(r"^(?:return|throw|yield)\s+").unwrap();
+		static ref VOID : Regex = Regex::new(r"^(?:[\w@]+\s+)*void\s").unwrap();
+	}
+
+	let mut start = target_start(text, op);
+	start += text[start..op].len() - text[start..op].trim_start().len();
+	if let Some(mat) = JUMP.find(&text[start..op]) {
+		start += mat.end();
+	}
+	let target = text[start..op].trim_end();
+	if target.is_empty() {
+		return None;
+	}
+	let null_aware = text[op..op_end].starts_with('?');
+	let end = utils::expression_end(text, op_end);
+	let sections = split_sections(&text[op_end..end]);
+	let before = text[..start].trim_end();
+	let indent = utils::line_indent(text, start);
+
+	if !null_aware && is_constructor(target) && sections.iter().all(|section| is_assignment(section)) {
+		let initializer = if text[start..end].contains('\n') {
+			let items = sections.iter()
+				.map(|section| format!("{}  {}", indent, section))
+				.collect::<Vec<String>>()
+				.join(",\n");
+			format!("{}\n{}{{\n{}\n{}}}", target, indent, items, indent)
+		} else {
+			format!("{} {{ {} }}", target, sections.join(", "))
+		};
+		return Some((start, format!("{}{}{}", &text[..start], initializer, &text[end..])));
+	}
+
+	let is_statement_end = text[end..].starts_with(';');
+	if (before.is_empty() || before.ends_with([';', '{', '}'])) && is_statement_end {
+		let (receiver, declaration) = evaluate(target, temps);
+		let lowered = format!("{}{}",
+			declaration.map(|declaration| format!("{}\n{}", declaration, indent)).unwrap_or_default(),
+			statements(&receiver, &sections, null_aware, indent));
+		return Some((start, format!("{}{}{}", &text[..start], lowered, &text[end + 1..])));
+	}
+
+	if before.ends_with("=>") {
+		let arrow = before.len() - 2;
+		let (receiver, declaration) = evaluate(target, temps);
+		let mut lines = vec![];
+		lines.extend(declaration);
+		lines.push(statements(&receiver, &sections, null_aware, ""));
+		let header_start = utils::skip_spaces(text, statement_start(text, arrow));
+		if is_statement_end && !is_lambda(&text[header_start..arrow]) {
+			let indent = utils::line_indent(text, header_start);
+			if !VOID.is_match(&text[header_start..arrow]) {
+				lines.push(format!("return {};", receiver));
+			}
+			let body = lines.join("\n").split('\n')
+				.map(|line| format!("{}  {}", indent, line))
+				.collect::<Vec<String>>()
+				.join("\n");
+			return Some((arrow, format!("{}{{\n{}\n{}}}{}",
+				&text[..arrow], body, indent, &text[end + 1..])));
+		}
+		lines.push(format!("return {};", receiver));
+		let body = lines.join(" ").split('\n').map(str::trim).collect::<Vec<&str>>().join(" ");
+		return Some((arrow, format!("{}=> {{ {} }}{}", &text[..arrow], body, &text[end..])));
+	}
+
+	let statement = utils::skip_spaces(text, statement_start(text, start));
+	let indent = utils::line_indent(text, statement);
+	let temp = format!("_cascade{}", temps);
+	*temps += 1;
+	if is_conditional(text, statement, start) {
+		*helpers = true;
+		let body = statements(&temp, &sections, null_aware, "").split('\n').map(str::trim).collect::<Vec<&str>>().join(" ");
+		let applied = format!("{}.Apply({}, {} => {{ {} }})", HELPERS, target, temp, body);
+		return Some((start, format!("{}{}{}", &text[..start], applied, &text[end..])));
+	}
+	let hoisted = format!("var {} = {};\n{}{}\n{}",
+		temp, target, indent, statements(&temp, &sections, null_aware, indent), indent);
+	Some((statement, format!("{}{}{}{}{}",
+		&text[..statement], hoisted, &text[statement..start], temp, &text[end..])))
+}
+
+/// Find the start of the target of the cascade whose operator is at `op`,
+/// which is right after the previous `;`, `,`, `=` or `=>`, or the
+/// enclosing bracket. The cascade has the lowest precedence, so the
+/// target may contain conditional and binary operators.
+fn target_start(text: &str, op: usize) -> usize {
+	let bytes = text.as_bytes();
+	let mut depth = 0;
+	let mut i = op;
+	while i > 0 {
+		i -= 1;
+		match bytes[i] {
+			b')' | b']' => depth += 1,
+			b'}' if depth > 0 => depth += 1,
+			b'(' | b'[' | b'{' if depth == 0 => return i + 1,
+			b'(' | b'[' | b'{' => depth -= 1,
+			b';' | b',' | b'}' if depth == 0 => return i + 1,
+			b'=' if depth == 0 => {
+				let next = bytes.get(i + 1).cloned().unwrap_or(0);
+				let prev = if i > 0 { bytes[i - 1] } else { 0 };
+				if next == b'>' {
+					return i + 2;
+				} else if next != b'=' && !b"=!<>".contains(&prev) {
+					return i + 1;
+				}
+			},
+			_ => {}
+		}
+	}
+	0
+}
+
+/// The start of the statement containing the position,
+/// right after the previous `;`, `{` or `}`
+fn statement_start(text: &str, pos: usize) -> usize {
+	text[..pos].rfind([';', '{', '}']).map(|i| i + 1).unwrap_or(0)
+}
+
+/// Whether the cascade at `start` is evaluated conditionally or repeatedly
+/// in the statement starting at `statement`, so that it cannot be put before
+/// the statement: in the header of a control statement, or after a
+/// short-circuit or conditional operator
+fn is_conditional(text: &str, statement: usize, start: usize) -> bool {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"^(?:else\s+)?(?:if|while|for|foreach|switch)\s*\(|&&|\|\||\?\?|\?\s").unwrap();
+	}
+
+	let bytes = text.as_bytes();
+	let mut depth = 0;
+	for i in (0..start).rev() {
+		match bytes[i] {
+			b')' | b']' | b'}' => depth += 1,
+			b'(' | b'[' | b'{' if depth > 0 => depth -= 1,
+			// In the header of a `for` whose parts are separated by `;`
+			b'(' if i < statement => return true,
+			b'(' | b'[' | b'{' => break,
+			_ => {}
+		}
+	}
+	RE.is_match(&text[statement..start])
+}
+
+/// Whether the arrow after the header starts a function literal instead of
+/// the body of a declaration, i.e. the header is in an expression after `=`
+fn is_lambda(header: &str) -> bool {
+	let mut depth = 0;
+	let bytes = header.as_bytes();
+	for (i, c) in bytes.iter().enumerate() {
+		match c {
+			b'(' | b'[' | b'{' => depth += 1,
+			b')' | b']' | b'}' => depth -= 1,
+			b'=' if depth == 0 => {
+				let next = bytes.get(i + 1).cloned().unwrap_or(0);
+				let prev = if i > 0 { bytes[i - 1] } else { 0 };
+				if next != b'=' && !b"=!<>".contains(&prev) {
+					return true;
+				}
+			},
+			_ => {}
+		}
+	}
+	false
+}
+
+/// Whether the target is a constructor call like `X(a)` or `new X<T>()`
+fn is_constructor(target: &str) -> bool {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"^(?:new\s+)?[A-Z][\w.]*(?:<[\w\s,<>?.@]*>)?\s*\(").unwrap();
+	}
+
+	match RE.find(target) {
+		Some(mat) => utils::matching_bracket(target, mat.end() - 1) == Some(target.len() - 1),
+		None => false,
+	}
+}
+
+/// Whether the section is a plain assignment `a = v`
+fn is_assignment(section: &str) -> bool {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"^[\w@]+\s*=(?:[^=]|$)").unwrap();
+	}
+
+	RE.is_match(section)
+}
+
+/// Decide the receiver of the sections. A target which is not a simple
+/// variable or member is put into a temporary variable, whose
+/// declaration is returned too.
+fn evaluate(target: &str, temps: &mut usize) -> (String, Option<String>) {
+	lazy_static! {
+		static ref SIMPLE : Regex = Regex::new(r"^[\w.@]+$").unwrap();
+	}
+
+	if SIMPLE.is_match(target) {
+		(target.to_string(), None)
+	} else {
+		let temp = format!("_cascade{}", temps);
+		*temps += 1;
+		let declaration = format!("var {} = {};", temp, target);
+		(temp, Some(declaration))
+	}
+}
+
+/// The statements applying the sections to the receiver, with lines
+/// after the first one indented
+fn statements(receiver: &str, sections: &[&str], null_aware: bool, indent: &str) -> String {
+	if null_aware {
+		let body = sections.iter()
+			.map(|section| format!("{}  {};\n", indent, apply_section(receiver, section)))
+			.collect::<String>();
+		format!("if ({} != null) {{\n{}{}}}", receiver, body, indent)
+	} else {
+		sections.iter()
+			.map(|section| format!("{};", apply_section(receiver, section)))
+			.collect::<Vec<String>>()
+			.join(&format!("\n{}", indent))
+	}
+}
+
+/// Split the sections of a cascade, which are separated by `..`
+/// out of brackets, excluding the first `..`
+fn split_sections(text: &str) -> Vec<&str> {
+	let bytes = text.as_bytes();
+	let mut result = vec![];
+	let mut depth = 0;
+	let mut start = 0;
+	let mut i = 0;
+	while i < bytes.len() {
+		match bytes[i] {
+			b'(' | b'[' | b'{' => depth += 1,
+			b')' | b']' | b'}' => depth -= 1,
+			b'.' if depth == 0 && bytes[i..].starts_with(b"..")
+				&& !bytes[i..].starts_with(b"...") && (i == 0 || bytes[i - 1] != b'.') => {
+				result.push(text[start..i].trim());
+				i += 2;
+				start = i;
+				continue;
+			},
+			_ => {}
+		}
+		i += 1;
+	}
+	result.push(text[start..].trim());
+	result
+}
+
+/// Apply a cascade section to the receiver, e.g. `a = 1` to `x.a = 1`
+fn apply_section(receiver: &str, section: &str) -> String {
+	if section.starts_with('[') {
+		format!("{}{}", receiver, section)
+	} else {
+		format!("{}.{}", receiver, section)
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn sections_splitted() {
+		assert_eq!(split_sections("a = 1..b(x..y)..[0] = [...c]"), vec!["a = 1", "b(x..y)", "[0] = [...c]"]);
+	}
+
+	#[test]
+	fn null_aware_cascade_lowered() {
+		assert_eq!(
+			transpile_cascades(r"
+void f() {
+  meta?..styles = []..add(a);
+  build(meta)?..[0] = 1;
+  final x = meta?..add(b);
+}"),
+			r"
+void f() {
+  if (meta != null) {
+    meta.styles = [];
+    meta.add(a);
+  }
+  var _cascade0 = build(meta);
+  if (_cascade0 != null) {
+    _cascade0[0] = 1;
+  }
+  var _cascade1 = meta;
+  if (_cascade1 != null) {
+    _cascade1.add(b);
+  }
+  final x = _cascade1;
+}"
+		);
+	}
+
+	#[test]
+	fn object_initializer() {
+		assert_eq!(
+			transpile_cascades(r"
+CssMargin copyWith({CssLength bottom, CssLength left}) =>
+      CssMargin()
+        ..bottom = bottom ?? this.bottom
+        ..left = left ?? this.left;
+final a = A(1)..x = 1..y = f(b, c);"),
+			r"
+CssMargin copyWith({CssLength bottom, CssLength left}) =>
+      CssMargin()
+      {
+        bottom = bottom ?? this.bottom,
+        left = left ?? this.left
+      };
+final a = A(1) { x = 1, y = f(b, c) };"
+		);
+	}
+
+	#[test]
+	fn cascade_statements() {
+		assert_eq!(
+			transpile_cascades(r"
+  buffer..write(a)..write(b);
+  list.where(f).toList()
+    ..sort()
+    ..add(c);"),
+			r"
+  buffer.write(a);
+  buffer.write(b);
+  var _cascade0 = list.where(f).toList();
+  _cascade0.sort();
+  _cascade0.add(c);"
+		);
+	}
+
+	#[test]
+	fn cascade_expressions() {
+		assert_eq!(
+			transpile_cascades(r"
+  List<int> sorted() => items.toList()..sort();
+  void clear() => items..clear()..add(0);
+  final f = (x) => x..add(1);
+  return Foo()..add(bar..x = 1);"),
+			r"
+  List<int> sorted() {
+    var _cascade0 = items.toList();
+    _cascade0.sort();
+    return _cascade0;
+  }
+  void clear() {
+    items.clear();
+    items.add(0);
+  }
+  final f = (x) => { x.add(1); return x; };
+  var _cascade1 = Foo();
+  var _cascade2 = bar;
+  _cascade2.x = 1;
+  _cascade1.add(_cascade2);
+  return _cascade1;"
+		);
+	}
+
+	#[test]
+	fn conditional_cascades() {
+		assert_eq!(
+			transpile_cascades(r"
+  if (ready && (queue..add(a)..flush()).isEmpty) f();
+  final b = c ? (Builder()..add(d)) : null;
+  while ((buffer..clear()).isEmpty) {}"),
+			r"
+  if (ready && (DartCascades.Apply(queue, _cascade0 => { _cascade0.add(a); _cascade0.flush(); })).isEmpty) f();
+  final b = c ? (DartCascades.Apply(Builder(), _cascade1 => { _cascade1.add(d); })) : null;
+  while ((DartCascades.Apply(buffer, _cascade2 => { _cascade2.clear(); })).isEmpty) {}
+
+public static class DartCascades
+{
+	public static T Apply<T>(T target, Action<T> sections)
+	{
+		sections(target);
+		return target;
+	}
+}
+"
+		);
+	}
+}
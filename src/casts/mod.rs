@@ -0,0 +1,504 @@
+use regex::Captures;
+use regex::Regex;
+use crate::config::Config;
+use crate::keywords;
+use crate::mask;
+use crate::utils;
+
+/// Transpile the type tests and the casts, which must be run after the
+/// types are mapped.
+///
+/// - `x as T` becomes `(T)x`, since the C# `as` returns null on failure
+///   while the dart `as` throws, like a C# cast
+/// - `x is! T` becomes `x is not T`, or `!(x is T)` before C# 9
+/// - the type promotion of a variable, after `x is T` in the condition of
+///   an `if` or a conditional expression, in the rest of a conjunction
+///   `x is T && ...`, or after `x is! T` in an `if` leaving the block,
+///   becomes a pattern variable `x is T t` which
+///   replaces the variable where it is promoted. Only variables used with
+///   members are promoted, and not those assigned in the scope. The
+///   interpolated expressions are not masked, so the uses in them are
+///   replaced too.
+pub fn transpile_casts(input: &str, config: &Config) -> String {
+	let result = if config.csharp_version >= 7 {
+		promote_types(input, config)
+	} else {
+		input.to_string()
+	};
+	let result = transpile_operator(&result, "as", |operand, ty| format!("({}){}", ty, operand));
+	transpile_operator(&result, "is!", |operand, ty| if config.csharp_version >= 9 {
+		format!("{} is not {}", operand, ty)
+	} else {
+		format!("!({} is {})", operand, ty)
+	})
+}
+
+/// The regex of a type after `as` or `is`, which is completed by
+/// `type_end` for the type arguments
+const TYPE : &str = r"[\w.@]+";
+
+/// Replace `operand op Type` by the result of `f(operand, type)`
+fn transpile_operator<F>(input: &str, op: &str, f: F) -> String
+	where F: Fn(&str, &str) -> String {
+	let re = Regex::new(&format!(r"\s+{}\s+(?P<type>{}|\()", regex::escape(op), TYPE)).unwrap();
+	let mut text = input.to_string();
+	let mut pos = 0;
+	while let Some(cap) = re.captures(&text[pos..]) {
+		let whole = cap.get(0).unwrap();
+		let op_start = pos + whole.start();
+		let type_start = pos + cap.name("type").unwrap().start();
+		pos += whole.end();
+		if is_keyword_before(&text[..op_start]) {
+			continue;
+		}
+		let end = type_end(&text, type_start);
+		let start = utils::skip_spaces(&text, operand_start(&text, op_start));
+		let operand = text[start..op_start].trim();
+		if operand.is_empty() {
+			continue;
+		}
+		let operand = if is_postfix(operand) { operand.to_string() } else { format!("({})", operand) };
+		let replacement = f(&operand, &text[type_start..end]);
+		text.replace_range(start..end, &replacement);
+		pos = start + replacement.len();
+	}
+	text
+}
+
+/// Whether the word before is a keyword, like `import 'a' as b`
+fn is_keyword_before(before: &str) -> bool {
+	lazy_static! {
+		static ref WORD : Regex = Regex::new(r"(\w+)$").unwrap();
+	}
+
+	WORD.captures(before).map(|cap| matches!(&cap[1], "import" | "export")).unwrap_or(false)
+}
+
+/// The end of the type starting at `start`, including the type
+/// arguments and the `?`, or of the tuple type `(int, string)`
+fn type_end(text: &str, start: usize) -> usize {
+	let mut end = start + text[start..].find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.' || c == '@')).unwrap_or(text.len() - start);
+	if end == start && text[start..].starts_with('(') {
+		end = utils::matching_bracket(text, start).map(|close| close + 1).unwrap_or(start);
+	} else if text[end..].starts_with('<') {
+		if let Some(close) = utils::matching_bracket(text, end) {
+			end = close + 1;
+		}
+	}
+	if text[end..].starts_with('?') && !text[end..].starts_with("?.") && !text[end..].starts_with("??")
+		&& !is_conditional(&text[end..]) {
+		end += 1;
+	}
+	end
+}
+
+/// Whether the `?` at the start of the text is that of a conditional
+/// expression, rather than of a nullable type
+fn is_conditional(text: &str) -> bool {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"^\?\s*[\w@(\[!-]").unwrap();
+		static ref DECLARED : Regex = Regex::new(r"^\?\s+[\w@]+\s*[=;,)]").unwrap();
+	}
+
+	RE.is_match(text) && !DECLARED.is_match(text)
+}
+
+/// The start of the operand of `as` or `is` ending at `end`. The type test
+/// operators have a lower precedence than the arithmetic operators, so the
+/// operand extends to the operators of lower precedence, like `==` or
+/// `&&`, or to the enclosing bracket.
+fn operand_start(text: &str, end: usize) -> usize {
+	let bytes = text.as_bytes();
+	let mut i = end;
+	while i > 0 {
+		let c = bytes[i - 1];
+		match c {
+			b')' | b']' | b'}' => match utils::matching_open(text, i - 1) {
+				Some(open) => i = open,
+				None => return i,
+			},
+			b'(' | b'[' | b'{' | b',' | b';' | b'=' | b'<' | b'>' | b':' => return i,
+			b'&' if i >= 2 && bytes[i - 2] == b'&' => return i,
+			b'|' if i >= 2 && bytes[i - 2] == b'|' => return i,
+			b'?' if !text[i..].starts_with('.') && !text[i..].starts_with('[') => return i,
+			_ if c.is_ascii_alphabetic() || c == b'_' => {
+				let word_start = text[..i].rfind(|c: char| !(c.is_alphanumeric() || c == '_')).map(|j| j + 1).unwrap_or(0);
+				let word = &text[word_start..i];
+				let is_member = text[..word_start].ends_with('.');
+				if !is_member && matches!(word, "return" | "yield" | "throw" | "in" | "case" | "else" | "when" | "is" | "as" | "if" | "while") {
+					return i;
+				}
+				i = word_start;
+			},
+			_ => i -= 1,
+		}
+	}
+	i
+}
+
+/// Whether the expression is a postfix expression, like `a.b(c)[d]!`,
+/// which needs no parentheses when it is the operand of a cast
+fn is_postfix(expression: &str) -> bool {
+	let mut depth = 0;
+	for c in expression.chars() {
+		match c {
+			'(' | '[' | '{' => depth += 1,
+			')' | ']' | '}' => depth -= 1,
+			_ if depth > 0 => {},
+			_ if c.is_alphanumeric() || matches!(c, '_' | '.' | '?' | '!' | '@') => {},
+			_ if mask::span_index(c).is_some() => {},
+			_ => return false,
+		}
+	}
+	!expression.starts_with('!')
+}
+
+fn promote_types(input: &str, config: &Config) -> String {
+	lazy_static! {
+		static ref IF : Regex = Regex::new(r"\bif\s*\(").unwrap();
+		static ref TEST : Regex = Regex::new(&format!(r"^\s*(?P<name>[\w@]+)\s+(?P<op>is!?)\s+(?P<type>{})", TYPE)).unwrap();
+		static ref CONDITIONAL : Regex = Regex::new(&format!(r"(?P<pre>[=(,:;{{}}]|=>|\breturn)\s*(?P<name>[\w@]+)\s+is\s+(?P<type>{})", TYPE)).unwrap();
+		static ref CONJUNCTION : Regex = Regex::new(&format!(r"(?P<pre>[=(,:;{{}}?]|=>|&&|\|\||\breturn)\s*(?P<name>[\w@]+)\s+is\s+(?P<type>{})", TYPE)).unwrap();
+	}
+
+	let mut text = input.to_string();
+	let mut pos = 0;
+	while let Some(mat) = IF.find_at(&text, pos) {
+		pos = mat.end();
+		let open = mat.end() - 1;
+		let close = match utils::matching_bracket(&text, open) {
+			Some(close) => close,
+			None => continue,
+		};
+		let condition = text[open + 1..close].to_string();
+		let body_start = utils::skip_spaces(&text, close + 1);
+		let body_end = match utils::statement_end(&text, body_start) {
+			Some(end) => end,
+			None => continue,
+		};
+		let mut offset = 0;
+		for conjunct in split_conjunction(&condition) {
+			let cap = match TEST.captures(&condition[offset..]) {
+				Some(cap) if offset + cap.get(0).unwrap().end() <= offset + conjunct.len() => cap,
+				_ => {
+					offset += conjunct.len() + 2;
+					continue;
+				},
+			};
+			let name = cap.name("name").unwrap().as_str().to_string();
+			let type_start = open + 1 + offset + cap.name("type").unwrap().start();
+			let type_end = type_end(&text, type_start);
+			let is_whole = text[type_end..open + 1 + offset + conjunct.len()].trim().is_empty();
+			let negated = cap.name("op").unwrap().as_str() == "is!";
+			offset += conjunct.len() + 2;
+			if !is_whole || name == "this" {
+				continue;
+			}
+			let ty = text[type_start..type_end].to_string();
+			let (scope_start, scope_end) = if negated {
+				if condition.contains("||") || condition.contains("&&") || !leaves_block(&text[body_start..body_end]) {
+					continue;
+				}
+				match block_end(&text, body_end) {
+					Some(end) => (body_end, end),
+					None => continue,
+				}
+			} else {
+				(type_end, body_end)
+			};
+			let promoted = match promoted_name(&text, &name, &ty) {
+				Some(promoted) => promoted,
+				None => continue,
+			};
+			let scope = &text[scope_start..scope_end];
+			if !is_used_promoted(scope, &name) {
+				continue;
+			}
+			let scope = rename(scope, &name, &promoted);
+			text.replace_range(scope_start..scope_end, &scope);
+			let test = match (negated, config.csharp_version >= 9) {
+				(false, _) => format!("{} is {} {}", name, ty, promoted),
+				(true, true) => format!("{} is not {} {}", name, ty, promoted),
+				(true, false) => format!("!({} is {} {})", name, ty, promoted),
+			};
+			let test_start = open + 1 + offset - conjunct.len() - 2 + conjunct.find(|c: char| !c.is_whitespace()).unwrap_or(0);
+			text.replace_range(test_start..type_end, &test);
+			break;
+		}
+	}
+
+	let mut pos = 0;
+	while let Some(cap) = CONDITIONAL.captures(&text[pos..]) {
+		let name = cap.name("name").unwrap();
+		let name_start = pos + name.start();
+		let name = name.as_str().to_string();
+		let type_start = pos + cap.name("type").unwrap().start();
+		pos = type_start;
+		let type_end = type_end(&text, type_start);
+		let question = utils::skip_spaces(&text, type_end);
+		if !text[question..].starts_with('?') || !is_conditional(&text[question..]) {
+			continue;
+		}
+		let colon = match conditional_colon(&text, question + 1) {
+			Some(colon) => colon,
+			None => continue,
+		};
+		let ty = text[type_start..type_end].to_string();
+		let promoted = match promoted_name(&text, &name, &ty) {
+			Some(promoted) => promoted,
+			None => continue,
+		};
+		if name == "this" || !is_used_promoted(&text[question..colon], &name) {
+			continue;
+		}
+		let scope = rename(&text[question..colon], &name, &promoted);
+		text.replace_range(question..colon, &scope);
+		text.replace_range(name_start..type_end, &format!("{} is {} {}", name, ty, promoted));
+	}
+
+	let mut pos = 0;
+	while let Some(cap) = CONJUNCTION.captures(&text[pos..]) {
+		let name = cap.name("name").unwrap();
+		let name_start = pos + name.start();
+		let name = name.as_str().to_string();
+		let type_start = pos + cap.name("type").unwrap().start();
+		pos = type_start;
+		let type_end = type_end(&text, type_start);
+		if !text[type_end..].trim_start().starts_with("&&") {
+			continue;
+		}
+		let end = conjunction_end(&text, type_end);
+		let ty = text[type_start..type_end].to_string();
+		let promoted = match promoted_name(&text, &name, &ty) {
+			Some(promoted) => promoted,
+			None => continue,
+		};
+		if name == "this" || !is_used_promoted(&text[type_end..end], &name) {
+			continue;
+		}
+		let scope = rename(&text[type_end..end], &name, &promoted);
+		text.replace_range(type_end..end, &scope);
+		text.replace_range(name_start..type_end, &format!("{} is {} {}", name, ty, promoted));
+	}
+	text
+}
+
+/// The end of the conjunction continuing at `start`, at the first operator
+/// of lower precedence than `&&` out of brackets, or at the end of the
+/// expression
+fn conjunction_end(text: &str, start: usize) -> usize {
+	let mut depth = 0;
+	for (i, c) in text[start..].char_indices() {
+		match c {
+			'(' | '[' | '{' => depth += 1,
+			')' | ']' | '}' if depth == 0 => return start + i,
+			')' | ']' | '}' => depth -= 1,
+			';' | ',' | ':' if depth == 0 => return start + i,
+			'|' | '?' if depth == 0 && (text[start + i + 1..].starts_with(c) || c == '?' && is_conditional(&text[start + i..])) => return start + i,
+			_ => {}
+		}
+	}
+	text.len()
+}
+
+/// Split the condition by the top level `&&`, unless there is a top level
+/// `||`, since then no conjunct is sure to hold
+fn split_conjunction(condition: &str) -> Vec<&str> {
+	let mut result = vec![];
+	let mut depth = 0;
+	let mut start = 0;
+	let bytes = condition.as_bytes();
+	for i in 0..bytes.len() {
+		match bytes[i] {
+			b'(' | b'[' | b'{' => depth += 1,
+			b')' | b']' | b'}' => depth -= 1,
+			b'|' if depth == 0 && bytes.get(i + 1) == Some(&b'|') => return vec![],
+			b'?' if depth == 0 && is_conditional(&condition[i..]) => return vec![],
+			b'&' if depth == 0 && bytes.get(i + 1) == Some(&b'&') && i >= start => {
+				result.push(&condition[start..i]);
+				start = i + 2;
+			},
+			_ => {}
+		}
+	}
+	result.push(&condition[start..]);
+	result
+}
+
+/// Whether the statement always leaves the enclosing block
+fn leaves_block(statement: &str) -> bool {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"(?:^\{?|;)\s*(?:return|throw|continue|break)\b[^;{}]*;\s*\}?$").unwrap();
+	}
+
+	RE.is_match(statement.trim())
+}
+
+/// The end of the block enclosing the position, before its `}`
+fn block_end(text: &str, pos: usize) -> Option<usize> {
+	let mut depth = 0;
+	for (i, c) in text[pos..].char_indices() {
+		match c {
+			'{' => depth += 1,
+			'}' if depth == 0 => return Some(pos + i),
+			'}' => depth -= 1,
+			_ => {}
+		}
+	}
+	None
+}
+
+/// The position of the `:` of the conditional expression whose
+/// then-expression starts at `start`
+fn conditional_colon(text: &str, start: usize) -> Option<usize> {
+	let mut depth = 0;
+	let mut nested = 0;
+	for (i, c) in text[start..].char_indices() {
+		match c {
+			'(' | '[' | '{' => depth += 1,
+			')' | ']' | '}' if depth == 0 => return None,
+			')' | ']' | '}' => depth -= 1,
+			';' if depth == 0 => return None,
+			'?' if depth == 0 && is_conditional(&text[start + i..]) => nested += 1,
+			':' if depth == 0 && nested == 0 => return Some(start + i),
+			':' if depth == 0 => nested -= 1,
+			_ => {}
+		}
+	}
+	None
+}
+
+/// The name of the pattern variable for the promoted variable, the type
+/// name in lower camel case, or `xAsT` if that is taken
+fn promoted_name(text: &str, name: &str, ty: &str) -> Option<String> {
+	let base = ty.trim_end_matches('?');
+	let base = base.split('<').next().unwrap_or(base);
+	let base = base.rsplit('.').next().unwrap_or(base).trim_start_matches('@');
+	let mut chars = base.chars();
+	let first = chars.next()?;
+	let rest = chars.as_str();
+	let camel = format!("{}{}", first.to_lowercase(), rest);
+	let pascal = format!("{}{}", first.to_uppercase(), rest);
+	[camel, format!("{}As{}", name.trim_start_matches('@'), pascal)].iter()
+		.find(|candidate| {
+			let re = Regex::new(&format!(r"\b{}\b", regex::escape(candidate))).unwrap();
+			!keywords::is_csharp_keyword(candidate) && !keywords::is_dart_keyword(candidate)
+				&& !re.is_match(text)
+		})
+		.cloned()
+}
+
+/// Whether the variable is used with its members in the scope, and
+/// is not assigned there
+fn is_used_promoted(scope: &str, name: &str) -> bool {
+	let name = regex::escape(name);
+	let member = Regex::new(&format!(r"(?:^|[^.\w@]){}\s*\??\.", name)).unwrap();
+	let assigned = Regex::new(&format!(r"(?:^|[^.\w@]){}\s*(?:[-+*/%&|^]|\?\?|~/|<<|>>)?=[^=]|(?:\+\+|--){}\b|\b{}\s*(?:\+\+|--)", name, name, name)).unwrap();
+	member.is_match(scope) && !assigned.is_match(scope)
+}
+
+/// Rename the uses of the variable, but not the members or the
+/// named arguments of the same name
+fn rename(scope: &str, name: &str, promoted: &str) -> String {
+	let re = Regex::new(&format!(r"(?P<pre>^|[^.\w@]){}(?P<post>\b\s*:?)", regex::escape(name))).unwrap();
+	re.replace_all(scope, |cap: &Captures| -> String {
+		let pre = cap.name("pre").unwrap().as_str();
+		let post = cap.name("post").unwrap().as_str();
+		if post.ends_with(':') && (pre == "(" || pre == "," || pre.trim().is_empty()) {
+			cap.get(0).unwrap().as_str().to_string()
+		} else {
+			format!("{}{}{}", pre, promoted, post)
+		}
+	}).to_string()
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn casts_transpiled() {
+		assert_eq!(
+			transpile_casts(r"
+  var ops = meta._buildOps as List<BuildOp>;
+  var n = (a + b as int?) * 2;
+  if (x is! string && f(y as Foo).z) {}
+  return (p.x, a.name) as (int x, string);", &Config::default()),
+			r"
+  var ops = (List<BuildOp>)meta._buildOps;
+  var n = ((int?)(a + b)) * 2;
+  if (x is not string && f((Foo)y).z) {}
+  return ((int x, string))(p.x, a.name);"
+		);
+	}
+
+	#[test]
+	fn is_not_before_csharp_9() {
+		assert_eq!(
+			transpile_casts(r"if (a.b is! Foo<int>) return;", &Config { csharp_version: 8, ..Config::default() }),
+			r"if (!(a.b is Foo<int>)) return;"
+		);
+	}
+
+	#[test]
+	fn types_promoted() {
+		assert_eq!(
+			transpile_casts(r"
+  void f(TextBit prev, TextBit child) {
+    if (prev is SpaceBit && prev.data != null) {
+      prev.data = g(prev, prev: 1);
+    }
+    var c = child is TextBlock
+        ? child.forEachBit(f, reversed: reversed)
+        : f(child, i);
+    if (child is! TextBlock) return;
+    child.rebuildBits(f);
+  }", &Config::default()),
+			r"
+  void f(TextBit prev, TextBit child) {
+    if (prev is SpaceBit spaceBit && spaceBit.data != null) {
+      spaceBit.data = g(spaceBit, prev: 1);
+    }
+    var c = child is TextBlock childAsTextBlock
+        ? childAsTextBlock.forEachBit(f, reversed: reversed)
+        : f(child, i);
+    if (child is not TextBlock textBlock) return;
+    textBlock.rebuildBits(f);
+  }"
+		);
+	}
+
+	#[test]
+	fn promoted_in_conjunctions() {
+		assert_eq!(
+			transpile_casts(r"
+  bool f(TextBit child) {
+    while (child is TextBlock && child.bits.isNotEmpty) {}
+    var b = (child is TextBlock && child.isEmpty) || child.isEmpty;
+    return child is TextSpan && child.isEmpty ? child.isEmpty : false;
+  }", &Config::default()),
+			r"
+  bool f(TextBit child) {
+    while (child is TextBlock textBlock && textBlock.bits.isNotEmpty) {}
+    var b = (child is TextBlock childAsTextBlock && childAsTextBlock.isEmpty) || child.isEmpty;
+    return child is TextSpan textSpan && textSpan.isEmpty ? child.isEmpty : false;
+  }"
+		);
+	}
+
+	#[test]
+	fn promoted_in_interpolations() {
+		let masked = mask::mask(r"
+    if (prev is SpaceBit) {
+      prev._data = '${prev._data ?? ''}$data';
+    }");
+		assert_eq!(
+			masked.unmask(&transpile_casts(&masked.text, &Config::default())),
+			r"
+    if (prev is SpaceBit spaceBit) {
+      spaceBit._data = '${spaceBit._data ?? ''}$data';
+    }"
+		);
+	}
+}
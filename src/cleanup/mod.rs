@@ -6,7 +6,7 @@ pub fn remove_import<'a>(input: &'a str) -> Cow<'a, str> {
 		static ref RE : Regex = Regex::new(r"(^import\s+.*\n)*(import\s+.*)(\s*\n)*").unwrap();
 	}
 
-	return RE.replace_all(input, "");
+	RE.replace_all(input, "")
 }
 
 
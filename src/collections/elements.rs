@@ -0,0 +1,366 @@
+use regex::Regex;
+use crate::config::CollectionLowering;
+use crate::config::Config;
+use super::Kind;
+use super::top_level_colon;
+
+/// An element of a collection literal
+#[derive(Debug, PartialEq)]
+pub enum Element<'a> {
+	/// An expression, or an entry `k: v` of a map
+	Plain(&'a str),
+	/// `...x`, or `...?x` if null-aware
+	Spread { expr: &'a str, null_aware: bool },
+	/// `if (c) x` or `if (c) x else y`
+	If { condition: &'a str, then: Box<Element<'a>>, otherwise: Option<Box<Element<'a>>> },
+	/// `for (var e in xs) x`
+	ForIn { variable: &'a str, iterable: &'a str, body: Box<Element<'a>> },
+	/// `for (var i = 0; i < n; i++) x`
+	For { header: &'a str, body: Box<Element<'a>> },
+}
+
+impl<'a> Element<'a> {
+	/// The plain elements inside the element
+	pub fn leaves(&self) -> Vec<&'a str> {
+		match self {
+			Element::Plain(text) => vec![text],
+			Element::Spread { .. } => vec![],
+			Element::If { then, otherwise, .. } => {
+				let mut leaves = then.leaves();
+				if let Some(otherwise) = otherwise {
+					leaves.extend(otherwise.leaves());
+				}
+				leaves
+			},
+			Element::ForIn { body, .. } | Element::For { body, .. } => body.leaves(),
+		}
+	}
+
+	/// The expressions spread inside the element
+	pub fn spreads(&self) -> Vec<&'a str> {
+		match self {
+			Element::Plain(_) => vec![],
+			Element::Spread { expr, .. } => vec![expr],
+			Element::If { then, otherwise, .. } => {
+				let mut spreads = then.spreads();
+				if let Some(otherwise) = otherwise {
+					spreads.extend(otherwise.spreads());
+				}
+				spreads
+			},
+			Element::ForIn { body, .. } | Element::For { body, .. } => body.spreads(),
+		}
+	}
+}
+
+/// Parse an element of a collection literal. The null-aware spreads are
+/// already lowered to `...(x ?? [])`, with the empty literal transpiled.
+pub fn parse(item: &str) -> Element<'_> {
+	lazy_static! {
+		static ref NULL_AWARE : Regex = Regex::new(r"(?s)^\((?P<expr>.*)\s\?\?\s(?:new [\w.]+<[^()]*>\(\)|\[\]|\{\})\)$").unwrap();
+		static ref CONTROL : Regex = Regex::new(r"^(?P<keyword>if|for)\s*\(").unwrap();
+		static ref FOR_IN : Regex = Regex::new(r"(?s)^(?:[\w.@<>?,\s]+\s)?(?P<variable>[\w@]+)\s+in\s+(?P<iterable>.*)$").unwrap();
+	}
+
+	let item = item.trim();
+	if let Some(expr) = item.strip_prefix("...?") {
+		return Element::Spread { expr: expr.trim(), null_aware: true };
+	}
+	if let Some(expr) = item.strip_prefix("...") {
+		let expr = expr.trim();
+		return match NULL_AWARE.captures(expr) {
+			Some(cap) => Element::Spread { expr: cap.name("expr").unwrap().as_str().trim(), null_aware: true },
+			None => Element::Spread { expr, null_aware: false },
+		};
+	}
+	let cap = match CONTROL.captures(item) {
+		Some(cap) => cap,
+		None => return Element::Plain(item),
+	};
+	let open = cap.get(0).unwrap().end() - 1;
+	let close = match crate::utils::matching_bracket(item, open) {
+		Some(close) => close,
+		None => return Element::Plain(item),
+	};
+	let header = item[open + 1..close].trim();
+	let rest = item[close + 1..].trim();
+	if cap.name("keyword").unwrap().as_str() == "for" {
+		let body = Box::new(parse(rest));
+		return match FOR_IN.captures(header) {
+			Some(cap) => Element::ForIn {
+				variable: cap.name("variable").unwrap().as_str(),
+				iterable: cap.name("iterable").unwrap().as_str().trim(),
+				body,
+			},
+			None => Element::For { header, body },
+		};
+	}
+	let (then, otherwise) = if rest.starts_with("if") {
+		(rest, None)
+	} else {
+		match else_position(rest) {
+			Some(position) => (&rest[..position], Some(Box::new(parse(&rest[position + 4..])))),
+			None => (rest, None),
+		}
+	};
+	Element::If { condition: header, then: Box::new(parse(then)), otherwise }
+}
+
+/// The position of the first `else` out of brackets
+fn else_position(text: &str) -> Option<usize> {
+	let bytes = text.as_bytes();
+	let is_word = |i: usize| i < bytes.len() && (bytes[i] == b'_' || bytes[i].is_ascii_alphanumeric());
+	let mut depth = 0;
+	for (i, c) in bytes.iter().enumerate() {
+		match c {
+			b'(' | b'[' | b'{' => depth += 1,
+			b')' | b']' | b'}' => depth -= 1,
+			b'e' if depth == 0 && text[i..].starts_with("else")
+				&& (i == 0 || !is_word(i - 1)) && !is_word(i + 4) => return Some(i),
+			_ => {}
+		}
+	}
+	None
+}
+
+/// Lower a collection literal containing `if`, `for` or spread elements.
+/// `ty` is the type of the collection, and `element` the type of its
+/// elements, which is `KeyValuePair<K, V>` for maps. The statements of
+/// the builder are added to `hoisted`, and a temporary variable holding
+/// the built collection is returned.
+pub fn lower(elements: &[Element], kind: Kind, ty: &str, element: &str, config: &Config, hoisted: &mut Vec<String>) -> String {
+	if config.collection_lowering == CollectionLowering::Linq {
+		if let Some(linq) = linq_chain(elements, kind, ty, element) {
+			return linq;
+		}
+	}
+	let temp = format!("_collection{}", hoisted.len());
+	let mut statements = vec![format!("var {} = new {}();", temp, ty)];
+	statements.extend(elements.iter().map(|element| builder(element, kind, &temp)));
+	hoisted.push(statements.join("\n"));
+	temp
+}
+
+fn linq_chain(elements: &[Element], kind: Kind, ty: &str, element: &str) -> Option<String> {
+	let mut segments = vec![];
+	let mut plains = vec![];
+	for item in elements {
+		if let Element::Plain(text) = item {
+			plains.push(plain(text, kind, element));
+			continue;
+		}
+		if !plains.is_empty() {
+			segments.push(format!("new {}[] {{ {} }}", element, plains.join(", ")));
+			plains.clear();
+		}
+		segments.push(linq(item, kind, element)?);
+	}
+	if !plains.is_empty() {
+		segments.push(format!("new {}[] {{ {} }}", element, plains.join(", ")));
+	}
+	let mut chain = segments[0].clone();
+	for segment in segments.iter().skip(1) {
+		chain.push_str(&format!(".Concat({})", segment));
+	}
+	let conversion = match kind {
+		Kind::List => String::from(".ToList()"),
+		Kind::Set => String::from(".ToHashSet()"),
+		Kind::Map => format!(".ToDictionary(_entry => _entry.Key, _entry => _entry.Value) as {}", ty),
+	};
+	if kind == Kind::Map {
+		Some(format!("({}{})", chain, conversion))
+	} else {
+		Some(format!("{}{}", chain, conversion))
+	}
+}
+
+/// A plain element as an element of a sequence
+fn plain(text: &str, kind: Kind, element: &str) -> String {
+	match (kind, top_level_colon(text)) {
+		(Kind::Map, Some(colon)) => format!("new {}({}, {})", element, text[..colon].trim(), text[colon + 1..].trim()),
+		_ => text.to_string(),
+	}
+}
+
+/// The element as a sequence, which is None for the classic `for`
+fn linq(item: &Element, kind: Kind, element: &str) -> Option<String> {
+	let empty = format!("Enumerable.Empty<{}>()", element);
+	Some(match item {
+		Element::Plain(text) => format!("new {}[] {{ {} }}", element, plain(text, kind, element)),
+		Element::Spread { expr, null_aware: false } => cast(parenthesize(expr), element),
+		Element::Spread { expr, null_aware: true } if is_boxed(element) => format!("({}?.Cast<{}>() ?? {})", parenthesize(expr), element, empty),
+		Element::Spread { expr, null_aware: true } => format!("({} ?? {})", expr, empty),
+		Element::If { condition, then, otherwise } => format!("({} ? {} : {})",
+			condition, linq(then, kind, element)?,
+			match otherwise {
+				Some(otherwise) => linq(otherwise, kind, element)?,
+				None => empty,
+			}),
+		Element::ForIn { variable, iterable, body } => match body.as_ref() {
+			Element::Plain(text) => cast(format!("{}.Select({} => {})",
+				parenthesize(iterable), variable, plain(text, kind, element)), element),
+			Element::If { condition, then, otherwise: None } if matches!(then.as_ref(), Element::Plain(_)) => {
+				let text = match then.as_ref() {
+					Element::Plain(text) => text,
+					_ => unreachable!(),
+				};
+				cast(format!("{}.Where({} => {}).Select({} => {})",
+					parenthesize(iterable), variable, condition, variable, plain(text, kind, element)), element)
+			},
+			body => format!("{}.SelectMany({} => {})",
+				parenthesize(iterable), variable, linq(body, kind, element)?),
+		},
+		Element::For { .. } => return None,
+	})
+}
+
+/// Whether the elements are boxed, so that the sequences of value types
+/// must be cast, since the variance of `IEnumerable<T>` only holds for
+/// the reference types
+fn is_boxed(element: &str) -> bool {
+	element == "object" || element == "dynamic"
+}
+
+/// The sequence cast to the element type if it is boxed
+fn cast(sequence: String, element: &str) -> String {
+	if is_boxed(element) {
+		format!("{}.Cast<{}>()", sequence, element)
+	} else {
+		sequence
+	}
+}
+
+fn parenthesize(expr: &str) -> String {
+	if expr.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '.' || c == '@') {
+		expr.to_string()
+	} else {
+		format!("({})", expr)
+	}
+}
+
+/// The statement adding the element to the collection in `temp`
+fn builder(item: &Element, kind: Kind, temp: &str) -> String {
+	match item {
+		Element::Plain(text) => match (kind, top_level_colon(text)) {
+			(Kind::Map, Some(colon)) => format!("{}[{}] = {};", temp, text[..colon].trim(), text[colon + 1..].trim()),
+			_ => format!("{}.Add({});", temp, text),
+		},
+		Element::Spread { expr, null_aware } => {
+			let statement = match kind {
+				Kind::List => format!("{}.AddRange({});", temp, expr),
+				Kind::Set => format!("{}.UnionWith({});", temp, expr),
+				Kind::Map => format!("foreach (var _entry in {}) {}[_entry.Key] = _entry.Value;", expr, temp),
+			};
+			if *null_aware {
+				format!("if ({} != null) {}", expr, statement)
+			} else {
+				statement
+			}
+		},
+		Element::If { condition, then, otherwise } => match otherwise {
+			Some(otherwise) => format!("if ({}) {} else {}",
+				condition, builder(then, kind, temp), builder(otherwise, kind, temp)),
+			None => format!("if ({}) {}", condition, builder(then, kind, temp)),
+		},
+		Element::ForIn { variable, iterable, body } => format!("foreach (var {} in {}) {}",
+			variable, iterable, builder(body, kind, temp)),
+		Element::For { header, body } => format!("for ({}) {}", header, builder(body, kind, temp)),
+	}
+}
+
+/// Put the statements building the collections before the statements
+/// using them, in reverse order, so that the collections nested in
+/// others are built first
+pub fn insert_hoisted(text: &str, hoisted: &[String]) -> String {
+	let mut text = text.to_string();
+	for (index, statements) in hoisted.iter().enumerate().rev() {
+		let re = Regex::new(&format!(r"\b_collection{}\b", index)).unwrap();
+		let position = match re.find(&text) {
+			Some(mat) => statement_start(&text, mat.start()),
+			None => continue,
+		};
+		let line_start = text[..position].rfind('\n').map(|i| i + 1).unwrap_or(0);
+		let indent = text[line_start..position].to_string();
+		let statements = statements.split('\n')
+			.map(|statement| format!("{}\n{}", statement, indent))
+			.collect::<String>();
+		text.insert_str(position, &statements);
+	}
+	text
+}
+
+/// The start of the statement containing the position. The braces of
+/// collection and object initializers do not end statements.
+fn statement_start(text: &str, pos: usize) -> usize {
+	lazy_static! {
+		static ref INITIALIZER : Regex = Regex::new(r"(?:\bnew\s+[\w.@]+(?:<[\w\s,<>?.@]*>)?(?:\s*\[\])?\s*(?:\([^;{}]*\))?|=)\s*$").unwrap();
+	}
+
+	let bytes = text.as_bytes();
+	let mut i = pos;
+	while i > 0 {
+		i -= 1;
+		match bytes[i] {
+			b';' => break,
+			b'{' if !INITIALIZER.is_match(&text[..i]) => break,
+			b'}' => {
+				match matching_open_brace(text, i) {
+					Some(open) if INITIALIZER.is_match(&text[..open]) => i = open,
+					_ => break,
+				}
+			},
+			_ => {}
+		}
+	}
+	let start = if i == 0 && !matches!(bytes.first(), Some(b';') | Some(b'{') | Some(b'}')) { 0 } else { i + 1 };
+	crate::utils::skip_spaces(text, start)
+}
+
+fn matching_open_brace(text: &str, close: usize) -> Option<usize> {
+	let bytes = text.as_bytes();
+	let mut depth = 0;
+	for i in (0..=close).rev() {
+		match bytes[i] {
+			b'}' => depth += 1,
+			b'{' => {
+				depth -= 1;
+				if depth == 0 {
+					return Some(i);
+				}
+			},
+			_ => {}
+		}
+	}
+	None
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn elements_parsed() {
+		assert_eq!(parse(" ...a.b"), Element::Spread { expr: "a.b", null_aware: false });
+		assert_eq!(parse("...(a ?? new List<dynamic>())"), Element::Spread { expr: "a", null_aware: true });
+		assert_eq!(parse("...?a"), Element::Spread { expr: "a", null_aware: true });
+		assert_eq!(parse("if (a) 1 else if (b) 2"), Element::If {
+			condition: "a",
+			then: Box::new(Element::Plain("1")),
+			otherwise: Some(Box::new(Element::If {
+				condition: "b",
+				then: Box::new(Element::Plain("2")),
+				otherwise: None,
+			})),
+		});
+		assert_eq!(parse("for (var e in f(x)) e * 2"), Element::ForIn {
+			variable: "e",
+			iterable: "f(x)",
+			body: Box::new(Element::Plain("e * 2")),
+		});
+		assert_eq!(parse("for (var i = 0; i < n; i++) i"), Element::For {
+			header: "var i = 0; i < n; i++",
+			body: Box::new(Element::Plain("i")),
+		});
+	}
+}
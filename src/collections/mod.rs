@@ -0,0 +1,456 @@
+use regex::Regex;
+use std::collections::HashMap;
+use crate::config::Config;
+use crate::decls;
+use crate::patterns;
+use crate::utils;
+
+mod elements;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Kind {
+	List,
+	Map,
+	Set,
+}
+
+/// Transpile the list, map and set literals into collection initializers,
+/// e.g. `<int>[1, 2]` into `new List<int> { 1, 2 }`, `{'a': 1}` into
+/// `new Dictionary<string, int> { ['a'] = 1 }` and `{1}` into
+/// `new HashSet<int> { 1 }`.
+///
+/// The element types are taken from the type arguments of the literal,
+/// or the declared type of the variable it is assigned to, or inferred
+/// from the elements. If enabled, the list and set literals assigned to
+/// variables of known types become the collection expressions of C# 12.
+///
+/// The literals with `if`, `for` and spread elements are lowered into
+/// LINQ chains, or statements building the collections, as configured.
+pub fn transpile_collections(input: &str, config: &Config) -> String {
+	let variables = declared_variables(input);
+	let mut hoisted = vec![];
+	let result = transpile_literals(input, &variables, config, &mut hoisted);
+	elements::insert_hoisted(&result, &hoisted)
+}
+
+fn transpile_literals(text: &str, variables: &HashMap<String, (String, String)>, config: &Config, hoisted: &mut Vec<String>) -> String {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"(?:<(?P<args>[\w\s,<>?.@]*)>\s*)?(?P<open>[\[{])").unwrap();
+	}
+
+	let mut result = String::new();
+	let mut last = 0;
+	for cap in RE.captures_iter(text) {
+		let start = cap.get(0).unwrap().start();
+		let open = cap.name("open").unwrap().start();
+		if start < last {
+			continue;
+		}
+		let args = cap.name("args").map(|args| args.as_str().trim());
+		if args.is_some() && is_generic_type(text[..start].trim_end()) {
+			continue;
+		}
+		let close = match utils::matching_bracket(text, open) {
+			Some(close) => close,
+			None => continue,
+		};
+		let before = text[..start].trim_end();
+		if args.is_none() && !is_literal(before, &text[open..=close]) {
+			continue;
+		}
+		if patterns::in_pattern(text, start) {
+			result.push_str(&text[last..=close]);
+			last = close + 1;
+			continue;
+		}
+
+		let inner = transpile_literals(&text[open + 1..close], variables, config, hoisted);
+		let target = target_type(before, variables);
+		let literal = build_literal(&inner, text.as_bytes()[open] == b'[', args, target, variables, config, hoisted);
+		result.push_str(&text[last..start]);
+		result.push_str(&literal);
+		last = close + 1;
+	}
+	result.push_str(&text[last..]);
+	result
+}
+
+/// Whether the type arguments after the text belong to a generic type,
+/// instead of a collection literal
+fn is_generic_type(before: &str) -> bool {
+	lazy_static! {
+		static ref WORD : Regex = Regex::new(r"\b(\w+)$").unwrap();
+	}
+
+	match WORD.captures(before) {
+		Some(cap) => !matches!(cap.get(1).unwrap().as_str(),
+			"return" | "in" | "yield" | "await" | "throw" | "else" | "case"),
+		None => before.ends_with('>'),
+	}
+}
+
+/// Whether the bracket after the text starts a collection literal, instead
+/// of an index, a block or a parameter list
+fn is_literal(before: &str, literal: &str) -> bool {
+	lazy_static! {
+		static ref WORD : Regex = Regex::new(r"\b(\w+)$").unwrap();
+		static ref ARRAY : Regex = Regex::new(r"\[\]\s+[\w@]+\s*=$").unwrap();
+		static ref CASE : Regex = Regex::new(r"(?:^|[;{}])\s*(?:case\b[^;{}]*|default\s*):$").unwrap();
+		static ref LABEL : Regex = Regex::new(r"[;{}]\s*[\w@]+\s*:$").unwrap();
+	}
+
+	let last = match before.chars().last() {
+		Some(last) => last,
+		None => return literal.starts_with('['),
+	};
+	if let Some(cap) = WORD.captures(before) {
+		return match cap.get(1).unwrap().as_str() {
+			"return" | "in" | "yield" | "await" | "throw" => true,
+			// `case [` starts a list pattern
+			"else" => literal.starts_with('['),
+			_ => false,
+		};
+	}
+	if literal.starts_with('[') {
+		return !matches!(last, ')' | ']' | '!' | '?' | '@' | '$');
+	}
+	// `=> { throw e; }` is the block body of a lambda
+	if utils::split_top_level(&literal[1..literal.len() - 1], ';').len() > 1 {
+		return false;
+	}
+	match last {
+		')' | ']' | '}' | ';' | '{' => false,
+		'=' => !ARRAY.is_match(before),
+		// `label: {` starts a labeled block
+		':' => !CASE.is_match(before) && !LABEL.is_match(before),
+		'(' | ',' => !is_parameter_list(&literal[1..literal.len() - 1]),
+		_ => true,
+	}
+}
+
+/// Whether the content of the braces is a list of named parameters
+fn is_parameter_list(content: &str) -> bool {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"(?s)^(?:@\w+\s+)?(?:required\s+)?(?:this\.[\w@]+|[\w.@]+(?:<[^=]*>)?\??\s+[\w@]+)(?:\s*[=:].*)?$").unwrap();
+	}
+
+	utils::split_top_level(content, ',').iter()
+		.any(|item| RE.is_match(item.trim()))
+}
+
+/// The names of the variables declared with generic types, and their types
+/// and type arguments. There is no scoping, like for the enum variables.
+fn declared_variables(input: &str) -> HashMap<String, (String, String)> {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"\b(?P<type>[\w.@]+)<(?P<args>[\w\s,<>?.@()]*)>\??\s+(?P<name>[\w@]+)\s*[;=,)]").unwrap();
+	}
+
+	RE.captures_iter(input).map(|cap| (
+		cap.name("name").unwrap().as_str().to_string(),
+		(cap.name("type").unwrap().as_str().to_string(), cap.name("args").unwrap().as_str().to_string()),
+	)).collect()
+}
+
+/// The type and type arguments of the variable declared or assigned
+/// right before the literal
+fn target_type(before: &str, variables: &HashMap<String, (String, String)>) -> Option<(String, String)> {
+	lazy_static! {
+		static ref DECLARATION : Regex = Regex::new(r"(?P<type>[\w.@]+)<(?P<args>[\w\s,<>?.@()]*)>\??\s+[\w@]+\s*=$").unwrap();
+		static ref ASSIGNMENT : Regex = Regex::new(r"\b(?P<name>[\w@]+)\s*(?:\?\?)?=$").unwrap();
+	}
+
+	if let Some(cap) = DECLARATION.captures(before) {
+		return Some((cap.name("type").unwrap().as_str().to_string(), cap.name("args").unwrap().as_str().to_string()));
+	}
+	ASSIGNMENT.captures(before).and_then(|cap| variables.get(cap.name("name").unwrap().as_str()).cloned())
+}
+
+fn build_literal(inner: &str, is_list: bool, args: Option<&str>, target: Option<(String, String)>, variables: &HashMap<String, (String, String)>, config: &Config, hoisted: &mut Vec<String>) -> String {
+	let elements = parse_elements(inner);
+	let (kind, args) = infer_kind(&elements, is_list, args, target.as_ref(), variables);
+	let ty = match kind {
+		Kind::List => format!("List<{}>", args),
+		Kind::Map => format!("Dictionary<{}>", args),
+		Kind::Set => format!("HashSet<{}>", args),
+	};
+
+	if elements.iter().any(|element| !matches!(element, elements::Element::Plain(_))) {
+		let element = if kind == Kind::Map { format!("KeyValuePair<{}>", args) } else { args };
+		return elements::lower(&elements, kind, &ty, &element, config, hoisted);
+	}
+
+	if kind != Kind::Map && target.is_some() && config.collection_expressions && config.csharp_version >= 12 {
+		return format!("[{}]", inner);
+	}
+	if inner.trim().is_empty() {
+		return format!("new {}()", ty);
+	}
+	let inner = if kind == Kind::Map {
+		utils::split_top_level(inner, ',').iter().map(|item| {
+			let key_end = top_level_colon(item);
+			match key_end {
+				Some(colon) => {
+					let key = &item[..colon];
+					let space = &key[..key.len() - key.trim_start().len()];
+					format!("{}[{}] ={}", space, key.trim(), &item[colon + 1..])
+				},
+				None => item.to_string(),
+			}
+		}).collect::<Vec<String>>().join(",")
+	} else {
+		inner.to_string()
+	};
+	let open = if inner.starts_with(char::is_whitespace) { "" } else { " " };
+	let close = if inner.ends_with(char::is_whitespace) { "" } else { " " };
+	format!("new {} {{{}{}{}}}", ty, open, inner, close)
+}
+
+/// The .NET type of the literal `[...]` or `{...}` without type arguments,
+/// inferred from its elements, like `List<int>` for `[1, 2]` and
+/// `Dictionary<dynamic, dynamic>` for `{}`
+pub fn literal_type(literal: &str) -> Option<String> {
+	let is_list = literal.starts_with('[');
+	if !is_list && !literal.starts_with('{') || utils::matching_bracket(literal, 0) != Some(literal.len() - 1) {
+		return None;
+	}
+	let elements = parse_elements(&literal[1..literal.len() - 1]);
+	Some(match infer_kind(&elements, is_list, None, None, &HashMap::new()) {
+		(Kind::List, args) => format!("List<{}>", args),
+		(Kind::Map, args) => format!("Dictionary<{}>", args),
+		(Kind::Set, args) => format!("HashSet<{}>", args),
+	})
+}
+
+fn parse_elements(inner: &str) -> Vec<elements::Element<'_>> {
+	utils::split_top_level(inner, ',').into_iter()
+		.filter(|item| !item.trim().is_empty())
+		.map(elements::parse)
+		.collect()
+}
+
+/// The kind of the literal and its type arguments, taken from the literal
+/// or the target type, or inferred from the elements and the element types
+/// of the spread variables
+fn infer_kind(elements: &[elements::Element], is_list: bool, args: Option<&str>, target: Option<&(String, String)>, variables: &HashMap<String, (String, String)>) -> (Kind, String) {
+	let target_args = target.map(|(_, args)| args.as_str());
+	let leaves = elements.iter().flat_map(|element| element.leaves()).collect::<Vec<&str>>();
+	let kind = if is_list {
+		Kind::List
+	} else if let Some(args) = args {
+		if utils::split_type_arguments(args).len() == 2 { Kind::Map } else { Kind::Set }
+	} else {
+		match target.map(|(ty, _)| ty.as_str()) {
+			Some("Dictionary") | Some("IDictionary") | Some("Map") => Kind::Map,
+			Some("HashSet") | Some("ISet") | Some("Set") => Kind::Set,
+			_ if elements.is_empty() => Kind::Map,
+			_ if entries(&leaves).iter().any(|(key, _)| key.is_some()) => Kind::Map,
+			_ => Kind::Set,
+		}
+	};
+
+	let args = args.or(target_args).map(String::from).unwrap_or_else(|| {
+		if kind == Kind::Map {
+			let entries = entries(&leaves);
+			format!("{}, {}",
+				infer_element_type(entries.iter().filter_map(|(key, _)| *key), vec![]),
+				infer_element_type(entries.iter().map(|(_, value)| *value), vec![]))
+		} else {
+			let spread_types = elements.iter()
+				.flat_map(|element| element.spreads())
+				.filter_map(|expr| variables.get(expr))
+				.filter(|(_, args)| utils::split_type_arguments(args).len() == 1)
+				.map(|(_, args)| args.trim().to_string())
+				.collect();
+			infer_element_type(leaves.iter().cloned(), spread_types)
+		}
+	});
+	(kind, args)
+}
+
+/// Split the entries of a map literal into keys and values. The key is
+/// None if the item is not a map entry.
+fn entries<'a>(items: &[&'a str]) -> Vec<(Option<&'a str>, &'a str)> {
+	items.iter()
+		.map(|item| match top_level_colon(item) {
+			Some(colon) => (Some(item[..colon].trim()), item[colon + 1..].trim()),
+			None => (None, item.trim()),
+		})
+		.collect()
+}
+
+/// The position of the first `:` out of brackets, which is not
+/// part of a conditional expression
+fn top_level_colon(item: &str) -> Option<usize> {
+	let mut depth = 0;
+	for (i, c) in item.char_indices() {
+		match c {
+			'(' | '[' | '{' => depth += 1,
+			')' | ']' | '}' => depth -= 1,
+			'?' if depth == 0 && item[i + 1..].starts_with(char::is_whitespace) => return None,
+			':' if depth == 0 => return Some(i),
+			_ => {}
+		}
+	}
+	None
+}
+
+/// Infer the common type of the elements and the known `types`, which is
+/// `dynamic` for no elements, and `object` for elements of different or
+/// unknown types
+fn infer_element_type<'a>(elements: impl Iterator<Item = &'a str>, mut types: Vec<String>) -> String {
+	for element in elements {
+		let element = element.trim();
+		if element.is_empty() || element == "null" {
+			continue;
+		}
+		match decls::infer_type(element) {
+			Some(ty) => if !types.contains(&ty) {
+				types.push(ty);
+			},
+			None => return String::from("object"),
+		}
+	}
+	types.sort();
+	types.dedup();
+	match types.len() {
+		0 => String::from("dynamic"),
+		1 => types.pop().unwrap(),
+		_ if types == ["double", "int"] => String::from("double"),
+		_ => String::from("object"),
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::mask::mask;
+
+	fn transpile(input: &str, config: &Config) -> String {
+		let masked = mask(input);
+		masked.unmask(&transpile_collections(&masked.text, config))
+	}
+
+	#[test]
+	fn list_literals() {
+		assert_eq!(
+			transpile(r"
+  IEnumerable<BuildOp> _buildOps;
+  readonly List<TextBit> _children = <TextBit>[];
+  var a = [1, 2.5], b = ['a', 'b'][0], c = [];
+  meta._buildOps ??= [];
+  for (var x in [a[0], f(b)[1]]) {}
+  return [
+    [1],
+  ];", &Config::default()),
+			r"
+  IEnumerable<BuildOp> _buildOps;
+  readonly List<TextBit> _children = new List<TextBit>();
+  var a = new List<double> { 1, 2.5 }, b = new List<string> { 'a', 'b' }[0], c = new List<dynamic>();
+  meta._buildOps ??= new List<BuildOp>();
+  for (var x in new List<object> { a[0], f(b)[1] }) {}
+  return new List<List<int>> {
+    new List<int> { 1 },
+  };"
+		);
+	}
+
+	#[test]
+	fn map_and_set_literals() {
+		assert_eq!(
+			transpile(r"
+  Map<string, int> counts = {};
+  var m = {'a': 1, 'b': x ? 2 : 3};
+  var s = <string>{}, t = {1, 2};
+  f({'k': [1]});
+  if (a) {
+    switch (b) { case 1: { break; } }
+  } else {
+    return {1};
+  }", &Config::default()),
+			r"
+  Map<string, int> counts = new Dictionary<string, int>();
+  var m = new Dictionary<string, object> { ['a'] = 1, ['b'] = x ? 2 : 3 };
+  var s = new HashSet<string>(), t = new HashSet<int> { 1, 2 };
+  f(new Dictionary<string, List<int>> { ['k'] = new List<int> { 1 } });
+  if (a) {
+    switch (b) { case 1: { break; } }
+  } else {
+    return new HashSet<int> { 1 };
+  }"
+		);
+	}
+
+	#[test]
+	fn not_literals() {
+		let input = r"
+void f(int a, {int b = 1, this.c}) {
+  a[0] = b?[1] + c![2];
+  public static readonly E[] values = { E.a };
+  g((x) { return x; });
+  h(() => { throw e; });
+  var n = switch (xs) { [] => 0, [var h, ...] => h, {'a': var a} => a };
+  b: {
+    if (a) { break b; }
+  }
+}";
+		assert_eq!(transpile(input, &Config::default()), input);
+	}
+
+	#[test]
+	fn control_elements_linq() {
+		assert_eq!(
+			transpile(r"
+  var a = <int>[1, if (b) 2 else 3, ...c, ...?d, for (var e in f) e * 2];
+  var g = <string>{for (var h in i) if (h.ok) h.name};
+  var j = {'k': 1, if (l) 'm': 2, ...n};
+  List<int> xs;
+  var o = [1, for (var x in xs) x * 2, ...xs, ...?p];
+  var q = [...xs];", &Config::default()),
+			r"
+  var a = new int[] { 1 }.Concat((b ? new int[] { 2 } : new int[] { 3 })).Concat(c).Concat((d ?? Enumerable.Empty<int>())).Concat(f.Select(e => e * 2)).ToList();
+  var g = i.Where(h => h.ok).Select(h => h.name).ToHashSet();
+  var j = (new KeyValuePair<string, int>[] { new KeyValuePair<string, int>('k', 1) }.Concat((l ? new KeyValuePair<string, int>[] { new KeyValuePair<string, int>('m', 2) } : Enumerable.Empty<KeyValuePair<string, int>>())).Concat(n).ToDictionary(_entry => _entry.Key, _entry => _entry.Value) as Dictionary<string, int>);
+  List<int> xs;
+  var o = new object[] { 1 }.Concat(xs.Select(x => x * 2).Cast<object>()).Concat(xs.Cast<object>()).Concat((p?.Cast<object>() ?? Enumerable.Empty<object>())).ToList();
+  var q = xs.ToList();"
+		);
+	}
+
+	#[test]
+	fn control_elements_builder() {
+		let config = Config { collection_lowering: crate::config::CollectionLowering::Builder, ..Config::default() };
+		assert_eq!(
+			transpile(r"
+  if (x) {
+    f(a, <Object>[1, if (b) 2, ...?d, <int>[for (var i = 0; i < n; i++) i]]);
+  }
+  return <string, int>{'k': 1, for (var e in f) e.key: e.value, ...n};", &config),
+			r"
+  if (x) {
+    var _collection1 = new List<Object>();
+    _collection1.Add(1);
+    if (b) _collection1.Add(2);
+    if (d != null) _collection1.AddRange(d);
+    var _collection0 = new List<int>();
+    for (var i = 0; i < n; i++) _collection0.Add(i);
+    _collection1.Add(_collection0);
+    f(a, _collection1);
+  }
+  var _collection2 = new Dictionary<string, int>();
+  _collection2['k'] = 1;
+  foreach (var e in f) _collection2[e.key] = e.value;
+  foreach (var _entry in n) _collection2[_entry.Key] = _entry.Value;
+  return _collection2;"
+		);
+	}
+
+	#[test]
+	fn collection_expressions() {
+		let config = Config { collection_expressions: true, ..Config::default() };
+		assert_eq!(
+			transpile(r"List<int> a = [1, 2]; var b = [3]; Map<string, int> c = {};", &config),
+			r"List<int> a = [1, 2]; var b = new List<int> { 3 }; Map<string, int> c = new Dictionary<string, int>();"
+		);
+	}
+}
@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+/// How the collection literals with `if`, `for` and spread elements
+/// are lowered, since the C# collection initializers cannot have them
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CollectionLowering {
+	/// Chains of LINQ `Concat`, `Where` and `Select`, which are expressions.
+	/// Literals with classic `for` elements fall back to builders.
+	Linq,
+	/// Statements adding the elements to a temporary collection,
+	/// put before the statement using the collection
+	Builder,
+}
+
+/// Options of the transpiler
+#[derive(Debug, Clone)]
+pub struct Config {
+	/// Mapping from the names of dart types to the names of .NET types.
+	/// Generic types are mapped by their names only, e.g. the entry
+	/// `Map` to `Dictionary` maps `Map<K, V>` to `Dictionary<K, V>`.
+	pub types : HashMap<String, String>,
+	/// Names of the classes declared out of the input, like those of the
+	/// frameworks, whose constructor calls need `new`. The classes declared
+	/// in the input are always known.
+	pub classes : HashSet<String>,
+	/// Whether the input is null-safe dart. If not, the output is
+	/// put in a `#nullable disable` context, where every reference
+	/// is nullable, like in legacy dart.
+	pub null_safety : bool,
+	/// The major version of the C# language of the output. Features
+	/// newer than that are lowered to older constructs.
+	pub csharp_version : u32,
+	/// Whether the list and set literals assigned to variables of known
+	/// types become collection expressions `[a, b]`, since C# 12
+	pub collection_expressions : bool,
+	/// How the `if`, `for` and spread elements of collections are lowered
+	pub collection_lowering : CollectionLowering,
+	/// The number of uses from which a record type with named fields is
+	/// promoted to a generated `record struct`, or None to keep all the
+	/// records as value tuples
+	pub record_structs : Option<usize>,
+}
+
+impl Default for Config {
+	fn default() -> Config {
+		let types = [
+			("String", "string"),
+			("int", "int"),
+			("double", "double"),
+			("num", "double"),
+			("bool", "bool"),
+			("dynamic", "dynamic"),
+			("Object", "object"),
+			("List", "List"),
+			("Map", "Dictionary"),
+			("Set", "HashSet"),
+			("Iterable", "IEnumerable"),
+			("Iterator", "IEnumerator"),
+			("Future", "Task"),
+			("Stream", "IAsyncEnumerable"),
+		].iter().map(|(dart, csharp)| (dart.to_string(), csharp.to_string())).collect();
+
+		let classes = [
+			// dart:core and dart:async, mapped or not
+			"DateTime", "Duration", "TimeSpan", "StringBuffer", "StringBuilder",
+			"RegExp", "Regex", "Uri", "Stopwatch", "Random", "Exception",
+			"List", "Dictionary", "HashSet", "Completer", "TaskCompletionSource",
+			"StreamController",
+			"Error", "ArgumentError", "StateError", "RangeError", "UnsupportedError",
+			"UnimplementedError", "FormatException", "TypeError",
+			// flutter
+			"Text", "TextSpan", "WidgetSpan", "TextStyle", "RichText",
+			"Container", "Padding", "Center", "Align", "SizedBox", "Column",
+			"Row", "Stack", "Expanded", "Flexible", "Wrap", "ListView",
+			"GestureDetector", "Icon", "Image", "Color", "Offset", "Size",
+			"Rect", "Radius", "BorderRadius", "BoxDecoration", "Border",
+			"BorderSide", "EdgeInsets", "Key", "ValueKey", "GlobalKey",
+			"TapGestureRecognizer", "Builder", "LayoutBuilder",
+		].iter().map(|name| name.to_string()).collect();
+
+		Config {
+			types,
+			classes,
+			null_safety: true,
+			csharp_version: 12,
+			collection_expressions: false,
+			collection_lowering: CollectionLowering::Linq,
+			record_structs: None,
+		}
+	}
+}
+
+impl Config {
+	/// Map the dart type to the .NET type, overriding the built-in
+	/// mapping if there is one, e.g. `with_type("int", "long")`
+	pub fn with_type(mut self, dart: &str, csharp: &str) -> Config {
+		self.types.insert(dart.to_string(), csharp.to_string());
+		self
+	}
+}
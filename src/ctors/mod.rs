@@ -0,0 +1,143 @@
+use regex::Captures;
+use regex::Regex;
+use std::collections::HashSet;
+use crate::config::Config;
+use crate::diagnostics;
+use crate::diagnostics::DiagnosticKind;
+use crate::keywords;
+use crate::patterns;
+use crate::utils;
+
+/// Insert `new` before the constructor calls, which dart calls like
+/// functions, e.g. `NodeMetadata()` becomes `new NodeMetadata()`.
+///
+/// A call is a constructor call if the name is that of a class declared
+/// in the input, or of a class in the configured table. Calls of the
+/// functions declared in the input are kept. Other calls of capitalized
+/// names are kept but reported, since they are probably constructor
+/// calls of unknown classes.
+///
+/// Calls of named constructors `X.named()` are kept as they are, since
+/// they are transpiled as static methods. Constructor declarations,
+/// and functions with capitalized names, are told apart from the calls
+/// by what comes before them: a type, a modifier, or the start of a
+/// member in a class body.
+pub fn insert_new(input: &str, config: &Config) -> String {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"(?P<pre>[.@]\s*)?\b(?P<name>[A-Z]\w*)(?P<generic><[\w\s,<>?.@]*>)?\s*\(").unwrap();
+	}
+
+	let classes = declared_classes(input);
+	let functions = RE.captures_iter(input)
+		.filter(|cap| cap.name("pre").is_none() && is_declaration(input, cap.get(0).unwrap().start()))
+		.map(|cap| cap["name"].to_string())
+		.collect::<HashSet<String>>();
+	RE.replace_all(input, |cap: &Captures| -> String {
+		let whole = cap.get(0).unwrap();
+		let name = cap.name("name").unwrap().as_str();
+		if cap.name("pre").is_some() || keywords::is_dart_keyword(name)
+			|| is_declaration(input, whole.start()) || patterns::in_pattern(input, whole.start()) {
+			return whole.as_str().to_string();
+		}
+		if classes.contains(name) || config.classes.contains(name) {
+			format!("new {}", whole.as_str())
+		} else if functions.contains(name) {
+			whole.as_str().to_string()
+		} else {
+			format!("{}{}", diagnostics::marker(DiagnosticKind::UnknownConstructor), whole.as_str())
+		}
+	}).to_string()
+}
+
+/// Whether the capitalized name starting at `start` is declared there,
+/// as a constructor or a function, rather than called
+fn is_declaration(input: &str, start: usize) -> bool {
+	lazy_static! {
+		static ref BEFORE : Regex = Regex::new(r"(?:^|[^\w\s]|\b(?P<word>\w+))\s*$").unwrap();
+		static ref TYPE_END : Regex = Regex::new(r"(?:\w[?>]|[^=-]>)\s*$").unwrap();
+	}
+
+	let before = &input[..start];
+	let trimmed = before.trim_end();
+	let is_member_start = trimmed.is_empty() || trimmed.ends_with([';', '{', '}']);
+	if (is_member_start && utils::in_class_body(input, start)) || TYPE_END.is_match(before) {
+		return true;
+	}
+	match BEFORE.captures(before).and_then(|cap| cap.name("word")) {
+		Some(word) => {
+			let word = word.as_str();
+			word == "new" || is_modifier(word) || !keywords::is_dart_keyword(word)
+		},
+		None => false,
+	}
+}
+
+/// The names of the classes declared in the input
+fn declared_classes(input: &str) -> HashSet<String> {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"\b(?:class|mixin)\s+(\w+)").unwrap();
+	}
+
+	RE.captures_iter(input).map(|cap| cap.get(1).unwrap().as_str().to_string()).collect()
+}
+
+fn is_modifier(word: &str) -> bool {
+	matches!(word,
+		"public" | "private" | "protected" | "internal" | "static" | "factory"
+		| "external" | "const" | "abstract" | "override" | "virtual" | "sealed")
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn new_inserted() {
+		let (output, diagnostics) = diagnostics::extract(&insert_new(r"
+class NodeMetadata {
+  NodeMetadata(this.a);
+  NodeMetadata.named() : a = Foo();
+  public NodeMetadata copy() => NodeMetadata(a);
+  static Widget Build(int a) => Text(CssLength.parse(a));
+}
+void f() {
+  meta ??= NodeMetadata();
+  var x = cond ? List<int>() : Helper(meta);
+  return new NodeMetadata(), WidgetSpan(child: Build(1));
+}", &Config::default()));
+		assert_eq!(
+			output,
+			r"
+class NodeMetadata {
+  NodeMetadata(this.a);
+  NodeMetadata.named() : a = Foo();
+  public NodeMetadata copy() => new NodeMetadata(a);
+  static Widget Build(int a) => new Text(CssLength.parse(a));
+}
+void f() {
+  meta ??= new NodeMetadata();
+  var x = cond ? new List<int>() : Helper(meta);
+  return new NodeMetadata(), new WidgetSpan(child: Build(1));
+}"
+		);
+		assert_eq!(diagnostics.iter().map(|d| (d.line, d.subject.as_str())).collect::<Vec<_>>(),
+			vec![(4, "Foo"), (10, "Helper")]);
+	}
+
+	#[test]
+	fn new_not_inserted() {
+		let input = r"
+typedef NodeMetadata NodeMetadataCollector(NodeMetadata meta);
+IEnumerable<Widget> Build(int a) {}
+@Deprecated(a)
+class A {
+  private A(int index, string name) {}
+  factory A() {}
+  Widget Function(int) f;
+}
+var x = switch (p) { Point(x: 0) => 0, _ => 1 };
+switch (p) { case Point(x: var x): print(x); }";
+		assert_eq!(insert_new(input, &Config::default()), input);
+	}
+}
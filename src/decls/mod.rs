@@ -0,0 +1,212 @@
+use regex::Captures;
+use regex::Regex;
+use crate::mask;
+use crate::utils;
+
+/// Transpile the `final`, `var` and `const` declarations, which must be
+/// run after the types are mapped to .NET types.
+///
+/// Locals:
+/// - `final x =` becomes `var x =`, and `final Type x =` becomes `Type x =`
+/// - `const` is kept only for primitive types and strings
+///
+/// Fields, i.e. declarations directly inside a class body:
+/// - `final` becomes `readonly`
+/// - `const` of primitive types and strings stays `const`, which is
+///   implicitly static in C#; others become `static readonly`
+/// - untyped fields get the type inferred from the initializer,
+///   or `dynamic` if that fails
+///
+/// `const` before a value, like `const Foo()`, is removed.
+pub fn transpile_declarations(input: &str) -> String {
+	lazy_static! {
+		static ref CONST_VALUE : Regex = Regex::new(r"(?P<pre>(?:[=(,:\[]|=>|\breturn)\s*)\bconst\s+").unwrap();
+		static ref RE : Regex = Regex::new(r"(?x)
+			(?P<static>\bstatic\s+)?
+			\b(?P<keyword>final|const|var)\s+
+			(?:(?P<type>[\w.@]+(?:<[\w\s,<>?.@()]*>)?\??|\([\w\s,<>?.@()]*\)\??)\s+)?
+			(?P<name>[\w@]+)
+			(?P<after>\s*(?:[=;,]|\bin\b))
+		").unwrap();
+	}
+
+	let input = CONST_VALUE.replace_all(input, "$pre");
+	RE.replace_all(&input, |cap: &Captures| -> String {
+		let start = cap.get(0).unwrap().start();
+		let keyword = cap.name("keyword").unwrap().as_str();
+		let name = cap.name("name").unwrap().as_str();
+		let after = cap.name("after").unwrap().as_str();
+		let value = if after.trim() == "=" {
+			let rest = &input[cap.get(0).unwrap().end()..];
+			utils::split_top_level(rest, ';')[0]
+		} else {
+			""
+		};
+		let declared = cap.name("type").map(|ty| ty.as_str().to_string());
+		let is_static = cap.name("static").is_some();
+
+		if !utils::in_class_body(&input, start) {
+			let declaration = match (keyword, declared) {
+				("const", None) => match infer_type(value) {
+					Some(ty) if is_const_type(&ty) => format!("const {} {}", ty, name),
+					_ => format!("var {}", name),
+				},
+				("const", Some(ty)) if is_const_type(&ty) => format!("const {} {}", ty, name),
+				("var", _) | (_, None) => format!("var {}", name),
+				(_, Some(ty)) => format!("{} {}", ty, name),
+			};
+			let modifier = if is_static { "static " } else { "" };
+			return format!("{}{}{}", modifier, declaration, after);
+		}
+
+		let ty = declared.or_else(|| infer_type(value))
+			.unwrap_or_else(|| String::from("dynamic"));
+		let modifiers = match keyword {
+			"const" if is_const_type(&ty) => "const ",
+			"const" => "static readonly ",
+			"final" if is_static => "static readonly ",
+			"final" => "readonly ",
+			_ if is_static => "static ",
+			_ => "",
+		};
+		format!("{}{} {}{}", modifiers, ty, name, after)
+	}).to_string()
+}
+
+/// Whether the value is a string literal, whose interpolated expressions,
+/// if any, are the only text out of the placeholders, in parentheses
+fn is_string(value: &str) -> bool {
+	let mut depth = 0;
+	value.starts_with(|c: char| mask::span_index(c).is_some())
+		&& value.chars().all(|c| {
+			match c {
+				'(' => depth += 1,
+				')' => depth -= 1,
+				_ => {}
+			}
+			depth > 0 || c == ')' || c.is_whitespace() || mask::span_index(c).is_some()
+		})
+}
+
+/// Whether a C# constant may be of this type
+fn is_const_type(ty: &str) -> bool {
+	matches!(ty,
+		"bool" | "byte" | "sbyte" | "char" | "decimal" | "double" | "float"
+		| "int" | "uint" | "long" | "ulong" | "short" | "ushort" | "string")
+}
+
+/// Infer the .NET type of a declaration from its initializer,
+/// which only works for literals, collections and constructor calls.
+/// The collections without type arguments have the types they are
+/// given by `collections::literal_type`.
+pub fn infer_type(value: &str) -> Option<String> {
+	lazy_static! {
+		static ref INT : Regex = Regex::new(r"^-?(?:0[xX][0-9a-fA-F]+|\d+)$").unwrap();
+		static ref DOUBLE : Regex = Regex::new(r"^-?(?:\d+\.\d+(?:[eE][+-]?\d+)?|\d+[eE][+-]?\d+)$").unwrap();
+		static ref COLLECTION : Regex = Regex::new(r"^<(?P<args>[\w\s,<>?.@]*)>\s*(?P<open>[\[{])").unwrap();
+		static ref CONSTRUCTOR : Regex = Regex::new(r"^(?:new\s+)?(?P<type>[A-Z]\w*(?:<[\w\s,<>?.@]*>)?)\s*[({]").unwrap();
+	}
+
+	let value = value.trim();
+	if value.is_empty() {
+		None
+	} else if is_string(value) {
+		Some(String::from("string"))
+	} else if INT.is_match(value) {
+		Some(String::from("int"))
+	} else if DOUBLE.is_match(value) {
+		Some(String::from("double"))
+	} else if value == "true" || value == "false" {
+		Some(String::from("bool"))
+	} else if let Some(cap) = COLLECTION.captures(value) {
+		let args = cap.name("args").unwrap().as_str().trim();
+		if cap.name("open").unwrap().as_str() == "[" {
+			Some(format!("List<{}>", args))
+		} else if utils::split_type_arguments(args).len() == 1 {
+			Some(format!("HashSet<{}>", args))
+		} else {
+			Some(format!("Dictionary<{}>", args))
+		}
+	} else if let Some(ty) = crate::collections::literal_type(value) {
+		Some(ty)
+	} else {
+		CONSTRUCTOR.captures(value).map(|cap| cap.name("type").unwrap().as_str().to_string())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::mask::mask;
+
+	fn transpile(input: &str) -> String {
+		let masked = mask(input);
+		masked.unmask(&transpile_declarations(&masked.text))
+	}
+
+	#[test]
+	fn locals_transpiled() {
+		assert_eq!(
+			transpile(r"
+void f() {
+  final ops = meta._buildOps as List<BuildOp>;
+  final int count = 0;
+  var i = 1;
+  const limit = 10;
+  const List<int> items = const [1];
+  for (final child in children) {}
+}"),
+			r"
+void f() {
+  var ops = meta._buildOps as List<BuildOp>;
+  int count = 0;
+  var i = 1;
+  const int limit = 10;
+  List<int> items = [1];
+  for (var child in children) {}
+}"
+		);
+	}
+
+	#[test]
+	fn fields_transpiled() {
+		assert_eq!(
+			transpile(r"
+class A extends B<C> {
+  final bool isBlockElement;
+  final _children = <TextBit>[];
+  static const double ratio = 1.5;
+  static const empty = A(0);
+  const name = 'a';
+  static final cache = <string, int>{};
+  var count = 0;
+  final data;
+  final _inputs = [];
+  final _options = {};
+  var ids = {1, 2};
+
+  void f() {
+    final x = 1;
+  }
+}"),
+			r"
+class A extends B<C> {
+  readonly bool isBlockElement;
+  readonly List<TextBit> _children = <TextBit>[];
+  const double ratio = 1.5;
+  static readonly A empty = A(0);
+  const string name = 'a';
+  static readonly Dictionary<string, int> cache = <string, int>{};
+  int count = 0;
+  readonly dynamic data;
+  readonly List<dynamic> _inputs = [];
+  readonly Dictionary<dynamic, dynamic> _options = {};
+  HashSet<int> ids = {1, 2};
+
+  void f() {
+    var x = 1;
+  }
+}"
+		);
+	}
+}
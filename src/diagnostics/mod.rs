@@ -0,0 +1,103 @@
+use std::fmt;
+
+/// The markers are taken from the other private use plane of unicode than
+/// the placeholders of the masked spans, so that the passes can insert them
+/// without access to a shared list. Like the placeholders, they are neither
+/// word characters nor spaces for the regexes of the passes.
+const MARKER_BASE : u32 = 0x100000;
+
+/// What may need a manual fix in the output
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DiagnosticKind {
+	/// A call of a capitalized name which is neither a known class nor
+	/// a function declared in the input, so `new` may be missing
+	UnknownConstructor,
+	/// A `sync*` or `async*` lambda, since C# has no iterator lambdas
+	IteratorLambda,
+	/// `StreamController.broadcast()`, since a channel delivers each
+	/// event to one listener only
+	BroadcastStream,
+}
+
+const KINDS : [DiagnosticKind; 3] = [
+	DiagnosticKind::UnknownConstructor,
+	DiagnosticKind::IteratorLambda,
+	DiagnosticKind::BroadcastStream,
+];
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+	pub kind : DiagnosticKind,
+	/// The line in the output, starting from 1
+	pub line : usize,
+	/// The column in the output, in characters, starting from 1
+	pub column : usize,
+	/// The word at the position, like the name of the called class
+	pub subject : String,
+}
+
+impl fmt::Display for Diagnostic {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let message = match self.kind {
+			DiagnosticKind::UnknownConstructor => "may be a constructor call of an unknown class, missing `new`",
+			DiagnosticKind::IteratorLambda => "is an iterator lambda, which C# does not support",
+			DiagnosticKind::BroadcastStream => "is a broadcast stream, which the channel only delivers to one listener",
+		};
+		write!(f, "{}:{}: `{}` {}", self.line, self.column, self.subject, message)
+	}
+}
+
+/// The marker to insert right before the code the diagnostic is about
+pub fn marker(kind: DiagnosticKind) -> char {
+	let index = KINDS.iter().position(|k| *k == kind).unwrap();
+	std::char::from_u32(MARKER_BASE + index as u32).unwrap()
+}
+
+/// Remove the markers from the output, and report them as diagnostics
+pub fn extract(text: &str) -> (String, Vec<Diagnostic>) {
+	let mut result = String::with_capacity(text.len());
+	let mut diagnostics = vec![];
+	let mut line = 1;
+	let mut column = 1;
+	for (i, c) in text.char_indices() {
+		let index = (c as u32).wrapping_sub(MARKER_BASE) as usize;
+		if let Some(kind) = KINDS.get(index) {
+			let rest = text[i + c.len_utf8()..].trim_start();
+			// `new` may be inserted between the marker and the class name
+			let rest = rest.strip_prefix("new ").map(str::trim_start).unwrap_or(rest);
+			let subject = rest
+				.split(|c: char| !c.is_alphanumeric() && c != '_' && c != '.')
+				.next()
+				.unwrap_or("");
+			let subject = if subject.is_empty() { rest.chars().take(1).collect() } else { subject.to_string() };
+			diagnostics.push(Diagnostic { kind: *kind, line, column, subject });
+			continue;
+		}
+		if c == '\n' {
+			line += 1;
+			column = 1;
+		} else {
+			column += 1;
+		}
+		result.push(c);
+	}
+	(result, diagnostics)
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn markers_extracted() {
+		let text = format!("var a = {}Foo(1);\nf({}() {{}});", marker(DiagnosticKind::UnknownConstructor), marker(DiagnosticKind::IteratorLambda));
+		let (text, diagnostics) = extract(&text);
+		assert_eq!(text, "var a = Foo(1);\nf(() {});");
+		assert_eq!(diagnostics, vec![
+			Diagnostic { kind: DiagnosticKind::UnknownConstructor, line: 1, column: 9, subject: String::from("Foo") },
+			Diagnostic { kind: DiagnosticKind::IteratorLambda, line: 2, column: 3, subject: String::from("(") },
+		]);
+		assert_eq!(diagnostics[0].to_string(), "1:9: `Foo` may be a constructor call of an unknown class, missing `new`");
+	}
+}
@@ -0,0 +1,330 @@
+use regex::Captures;
+use regex::Regex;
+use crate::mask::Masked;
+use crate::mask::SpanKind;
+
+/// Transpile the dart `///` doc comments into C# XML documentation.
+/// The first paragraph becomes the `<summary>`, the others go to
+/// `<remarks>`. References `[name]` become `<paramref>` if `name` is
+/// a parameter of the documented function, otherwise `<see cref>`, with
+/// the type arguments in braces like `List{String}`.
+/// Fenced code blocks become `<code>`, and inline code becomes `<c>`.
+///
+/// The doc comments are masked, so this works on the spans of the masked
+/// text. The whole converted block is put into the span of the first line,
+/// and the lines of the other spans are removed from the text.
+pub fn transpile_doc_comments(masked: &mut Masked) {
+	let lines : Vec<String> = masked.text.split('\n').map(String::from).collect();
+	let mut result : Vec<String> = vec![];
+	let mut i = 0;
+	while i < lines.len() {
+		let mut run = vec![];
+		while i + run.len() < lines.len() {
+			match doc_span(masked, &lines[i + run.len()]) {
+				Some(index) => run.push(index),
+				None => break,
+			}
+		}
+		if run.is_empty() {
+			result.push(lines[i].clone());
+			i += 1;
+			continue;
+		}
+
+		let indent = &lines[i][..lines[i].len() - lines[i].trim_start().len()];
+		let declaration = lines[i + run.len()..].join("\n");
+		let params = declared_params(&declaration);
+		let doc = run.iter().map(|index| {
+			strip_doc_marker(&masked.spans[*index].text)
+		}).collect::<Vec<&str>>();
+		let xml = doc_to_xml(&doc, &params);
+		masked.spans[run[0]].text = xml.iter()
+			.map(|line| if line.is_empty() {
+				String::from("///")
+			} else {
+				format!("/// {}", line)
+			})
+			.collect::<Vec<String>>()
+			.join(&format!("\n{}", indent));
+		for index in run.iter().skip(1) {
+			masked.spans[*index].text = String::new();
+		}
+		result.push(lines[i].clone());
+		i += run.len();
+	}
+	masked.text = result.join("\n");
+}
+
+/// If the line consists of a single doc comment, return its span index
+fn doc_span(masked: &Masked, line: &str) -> Option<usize> {
+	let mut chars = line.trim().chars();
+	let c = chars.next()?;
+	if chars.next().is_some() {
+		return None
+	}
+	let index = crate::mask::span_index(c)?;
+	if masked.spans.get(index)?.kind == SpanKind::DocComment {
+		Some(index)
+	} else {
+		None
+	}
+}
+
+fn strip_doc_marker(line: &str) -> &str {
+	let line = line.trim_start_matches('/');
+	line.strip_prefix(' ').unwrap_or(line).trim_end()
+}
+
+/// Find the names of the parameters of the declaration at the start of the
+/// text, if it is a function. The declaration ends at the first `{`, `;`
+/// or `=` out of parentheses.
+fn declared_params(declaration: &str) -> Vec<String> {
+	lazy_static! {
+		static ref FUNC_PARAM : Regex = Regex::new(r"\([^()]*\)").unwrap();
+		static ref DEFAULT : Regex = Regex::new(r"=.*$").unwrap();
+		static ref NAME : Regex = Regex::new(r"(\w+)\W*$").unwrap();
+	}
+
+	let mut depth = 0;
+	let mut open = None;
+	let mut close = None;
+	for (i, c) in declaration.char_indices() {
+		match c {
+			'(' => {
+				if depth == 0 && open.is_none() {
+					open = Some(i);
+				}
+				depth += 1;
+			},
+			')' => {
+				depth -= 1;
+				if depth == 0 && close.is_none() && open.is_some() {
+					close = Some(i);
+					break;
+				}
+			},
+			'{' | ';' if depth == 0 => break,
+			// Either `=>` or an initializer, but not `operator ==`
+			'=' if depth == 0 && !declaration[i..].starts_with("==")
+				&& !declaration[..i].ends_with('=') => break,
+			_ => {}
+		}
+	}
+	let params = match (open, close) {
+		(Some(open), Some(close)) => &declaration[open + 1..close],
+		_ => return vec![],
+	};
+	let params = FUNC_PARAM.replace_all(params, "");
+	params.split(',').filter_map(|item| {
+		let item = DEFAULT.replace(item, "");
+		NAME.captures(&item).map(|cap| cap.get(1).unwrap().as_str().to_string())
+	}).collect()
+}
+
+/// Convert the lines of a doc comment, without the `///`, into
+/// the lines of a C# XML documentation
+fn doc_to_xml(doc: &[&str], params: &[String]) -> Vec<String> {
+	let mut paragraphs : Vec<Vec<String>> = vec![vec![]];
+	let mut in_code = false;
+	for line in doc {
+		if line.trim_start().starts_with("```") {
+			in_code = !in_code;
+			paragraphs.last_mut().unwrap().push(String::from(
+				if in_code { "<code>" } else { "</code>" }));
+		} else if in_code {
+			paragraphs.last_mut().unwrap().push(escape(line));
+		} else if line.trim().is_empty() {
+			if !paragraphs.last().unwrap().is_empty() {
+				paragraphs.push(vec![]);
+			}
+		} else {
+			paragraphs.last_mut().unwrap().push(inline_markdown(line, params));
+		}
+	}
+	if in_code {
+		paragraphs.last_mut().unwrap().push(String::from("</code>"));
+	}
+	if paragraphs.last().unwrap().is_empty() {
+		paragraphs.pop();
+	}
+
+	let mut result = vec![String::from("<summary>")];
+	let mut paragraphs = paragraphs.into_iter();
+	if let Some(summary) = paragraphs.next() {
+		result.extend(summary);
+	}
+	result.push(String::from("</summary>"));
+	let remarks : Vec<Vec<String>> = paragraphs.collect();
+	if !remarks.is_empty() {
+		result.push(String::from("<remarks>"));
+		for (i, paragraph) in remarks.into_iter().enumerate() {
+			if i > 0 {
+				result.push(String::new());
+			}
+			result.push(String::from("<para>"));
+			result.extend(paragraph);
+			result.push(String::from("</para>"));
+		}
+		result.push(String::from("</remarks>"));
+	}
+	result
+}
+
+fn escape(text: &str) -> String {
+	text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Convert the inline markdown of a line: references, links, code,
+/// bold and italic text
+fn inline_markdown(line: &str, params: &[String]) -> String {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"(?x)
+			`(?P<code>[^`]+)`
+			|
+			\[(?P<text>[^\]]+)\]\((?P<url>[^)\s]+)\)
+			|
+			\[(?P<reference>[\w.]+(?:&lt;[\w.,\s&;?]*&gt;)?)\]
+			|
+			\*\*(?P<bold>[^*]+)\*\*
+			|
+			(?:\*(?P<italic>[^*\s][^*]*)\*)
+		").unwrap();
+	}
+
+	let line = escape(line);
+	RE.replace_all(&line, |cap: &Captures| -> String {
+		if let Some(code) = cap.name("code") {
+			format!("<c>{}</c>", code.as_str())
+		} else if let Some(url) = cap.name("url") {
+			format!("<see href=\"{}\">{}</see>", url.as_str(), cap.name("text").unwrap().as_str())
+		} else if let Some(reference) = cap.name("reference") {
+			let reference = reference.as_str();
+			if params.iter().any(|param| param == reference) {
+				format!("<paramref name=\"{}\"/>", reference)
+			} else {
+				// The type arguments of a cref are in braces
+				let reference = reference.replace("&lt;", "{").replace("&gt;", "}").replace(char::is_whitespace, "");
+				format!("<see cref=\"{}\"/>", reference)
+			}
+		} else if let Some(bold) = cap.name("bold") {
+			format!("<b>{}</b>", bold.as_str())
+		} else {
+			format!("<i>{}</i>", cap.name("italic").unwrap().as_str())
+		}
+	}).to_string()
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::mask::mask;
+
+	fn transpile(input: &str) -> String {
+		let mut masked = mask(input);
+		transpile_doc_comments(&mut masked);
+		masked.unmask(&masked.text)
+	}
+
+	#[test]
+	fn doc_comment_1() {
+		assert_eq!(
+			transpile(r"
+  /// Sets [meta] fields, creating a [NodeMetadata] if null.
+  NodeMetadata lazySet(NodeMetadata meta, {BuildOp buildOp}) {"),
+			r#"
+  /// <summary>
+  /// Sets <paramref name="meta"/> fields, creating a <see cref="NodeMetadata"/> if null.
+  /// </summary>
+  NodeMetadata lazySet(NodeMetadata meta, {BuildOp buildOp}) {"#
+		);
+	}
+
+	#[test]
+	fn doc_comment_2() {
+		assert_eq!(
+			transpile(r"/// Calls [f] for each style.
+///
+/// The `key` and `value` are **never** null,
+/// see [CSS](https://www.w3.org/Style/CSS/).
+///
+/// ```dart
+/// meta.styles((k, v) => print(k));
+/// ```
+void styles(void f(String key, String value)) {"),
+			r#"/// <summary>
+/// Calls <paramref name="f"/> for each style.
+/// </summary>
+/// <remarks>
+/// <para>
+/// The <c>key</c> and <c>value</c> are <b>never</b> null,
+/// see <see href="https://www.w3.org/Style/CSS/">CSS</see>.
+/// </para>
+///
+/// <para>
+/// <code>
+/// meta.styles((k, v) =&gt; print(k));
+/// </code>
+/// </para>
+/// </remarks>
+void styles(void f(String key, String value)) {"#
+		);
+	}
+
+	#[test]
+	fn doc_comment_3() {
+		assert_eq!(
+			transpile(r"class A {
+  /// The [List<String>] of *all* styles, by [Map<String, int>].
+  final List<String> styles;
+
+  // Not a doc comment [meta]
+  /// Builds with [tsb] and [priority].
+  A(this.tsb, {this.priority = 10}) : super();
+}"),
+			r#"class A {
+  /// <summary>
+  /// The <see cref="List{String}"/> of <i>all</i> styles, by <see cref="Map{String,int}"/>.
+  /// </summary>
+  final List<String> styles;
+
+  // Not a doc comment [meta]
+  /// <summary>
+  /// Builds with <paramref name="tsb"/> and <paramref name="priority"/>.
+  /// </summary>
+  A(this.tsb, {this.priority = 10}) : super();
+}"#
+		);
+	}
+
+	#[test]
+	fn unterminated_code() {
+		assert_eq!(
+			transpile(r"/// Prints.
+///
+/// ```
+/// print(a < b);
+void f() {}"),
+			r"/// <summary>
+/// Prints.
+/// </summary>
+/// <remarks>
+/// <para>
+/// <code>
+/// print(a &lt; b);
+/// </code>
+/// </para>
+/// </remarks>
+void f() {}"
+		);
+	}
+
+	#[test]
+	fn params_declared() {
+		assert_eq!(declared_params("int f(int a, [int b = 1]) {"), vec!["a", "b"]);
+		assert_eq!(declared_params("int f({required int a, this.b}) =>"), vec!["a", "b"]);
+		assert_eq!(declared_params("final int x = f(a);"), Vec::<String>::new());
+		assert_eq!(declared_params("class A {\n  A(int a);"), Vec::<String>::new());
+		assert_eq!(declared_params("bool operator ==(Object other) =>"), vec!["other"]);
+	}
+}
@@ -0,0 +1,611 @@
+use regex::Captures;
+use regex::Regex;
+use std::collections::HashSet;
+use crate::keywords;
+use crate::utils;
+
+/// An enum declaration found in the text
+struct EnumDecl<'a> {
+	/// Byte range of the whole declaration, from `enum` to the closing `}`
+	start : usize,
+	end : usize,
+	indent : &'a str,
+	name : &'a str,
+	/// The text between `{` and the first top level `;` (or the `}`)
+	members : &'a str,
+	/// The text after the first top level `;` of an enhanced enum,
+	/// i.e. the fields, constructors and methods
+	rest : Option<&'a str>,
+}
+
+impl<'a> EnumDecl<'a> {
+	/// An enhanced enum has fields, constructors, methods, or members
+	/// constructed with arguments
+	fn is_enhanced(&self) -> bool {
+		self.rest.map(|rest| !rest.trim().is_empty()).unwrap_or(false)
+			|| self.members.contains('(')
+	}
+}
+
+/// Transpile enum declarations and the uses of enums.
+///
+/// A simple enum becomes a C# enum, whose members are escaped if they
+/// are C# keywords. An enhanced enum becomes a sealed class with a
+/// static readonly instance for each member, which also provides the
+/// `index`, `name` and `values` of dart enums.
+///
+/// `E.values` becomes `Enum.GetValues` for simple enums, and `.name`
+/// and `.index` of an enum value become `ToString()` and a cast to int.
+/// The enum values are recognized either as `E.member`, or as variables
+/// declared with an enum type, in the block or the function declaring
+/// them.
+///
+/// The asserts of the constructors become `Debug.Assert`, for which
+/// `using System.Diagnostics;` is put at the start.
+pub fn transpile_enums(input: &str) -> String {
+	let decls = find_enums(input);
+	if decls.is_empty() {
+		return String::from(input);
+	}
+	let input = rewrite_variables(input, &decls);
+	let decls = find_enums(&input);
+
+	let mut result = String::new();
+	let mut last = 0;
+	for decl in decls.iter() {
+		result.push_str(&rewrite_members(&input[last..decl.start], &decls));
+		if decl.is_enhanced() {
+			result.push_str(&enhanced_enum(decl, &decls));
+		} else {
+			result.push_str(&simple_enum(decl));
+		}
+		last = decl.end;
+	}
+	result.push_str(&rewrite_members(&input[last..], &decls));
+	if result.contains("Debug.Assert(") && !input.contains("using System.Diagnostics;") {
+		result = format!("using System.Diagnostics;\n\n{}", result);
+	}
+	result
+}
+
+fn find_enums(input: &str) -> Vec<EnumDecl<'_>> {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"(?m)^(?P<indent>[ \t]*)enum\s+(?P<name>\w+)\s*\{").unwrap();
+	}
+
+	let mut result = vec![];
+	for cap in RE.captures_iter(input) {
+		let whole = cap.get(0).unwrap();
+		let open = whole.end() - 1;
+		let close = match utils::matching_bracket(input, open) {
+			Some(close) => close,
+			None => continue,
+		};
+		let body = &input[open + 1..close];
+		let (members, rest) = match utils::split_top_level(body, ';').as_slice() {
+			[members] => (*members, None),
+			[members, ..] => (*members, Some(&body[members.len() + 1..])),
+			[] => (body, None),
+		};
+		result.push(EnumDecl {
+			start: whole.start() + cap.name("indent").unwrap().as_str().len(),
+			end: close + 1,
+			indent: cap.name("indent").unwrap().as_str(),
+			name: cap.name("name").unwrap().as_str(),
+			members,
+			rest,
+		});
+	}
+	result
+}
+
+/// Split the members of an enum into (name, arguments) pairs.
+/// The ranges of the names in `members` are also returned, to
+/// allow rewriting the names in place.
+fn split_members(members: &str) -> Vec<(usize, usize, Option<&str>)> {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"^[^\w@]*(?P<name>\w+)\s*(?:\((?P<args>(?s).*)\))?\s*$").unwrap();
+	}
+
+	let mut result = vec![];
+	let mut offset = 0;
+	for item in utils::split_top_level(members, ',') {
+		if let Some(cap) = RE.captures(item) {
+			let name = cap.name("name").unwrap();
+			result.push((
+				offset + name.start(),
+				offset + name.end(),
+				cap.name("args").map(|args| args.as_str())));
+		}
+		offset += item.len() + 1;
+	}
+	result
+}
+
+fn simple_enum(decl: &EnumDecl) -> String {
+	let mut members = String::new();
+	let mut last = 0;
+	for (start, end, _) in split_members(decl.members) {
+		members.push_str(&decl.members[last..start]);
+		members.push_str(&keywords::escape_csharp_keyword(&decl.members[start..end]));
+		last = end;
+	}
+	members.push_str(&decl.members[last..]);
+	// The semicolon after the members is not allowed in C#
+	members.push_str(decl.rest.unwrap_or(""));
+	format!("{}enum {} {{{}}}",
+		if decl.name.starts_with('_') {""} else {"public "},
+		decl.name,
+		members)
+}
+
+fn enhanced_enum(decl: &EnumDecl, decls: &[EnumDecl]) -> String {
+	let rest = decl.rest.unwrap_or("");
+	let rest = rewrite_members(rest.strip_prefix('\n').unwrap_or(rest), decls);
+	let inner = rest.lines()
+		.find(|line| !line.trim().is_empty())
+		.map(|line| &line[..line.len() - line.trim_start().len()])
+		.map(String::from)
+		.unwrap_or_else(|| format!("{}  ", decl.indent));
+	let name = decl.name;
+
+	let members = split_members(decl.members).into_iter().map(|(start, end, args)| {
+		(keywords::escape_csharp_keyword(&decl.members[start..end]), args.map(str::trim).unwrap_or(""))
+	}).collect::<Vec<(String, &str)>>();
+
+	let mut result = format!("{}sealed class {} {{\n",
+		if name.starts_with('_') {""} else {"public "},
+		name);
+	for (index, (member, args)) in members.iter().enumerate() {
+		result.push_str(&format!("{}public static readonly {} {} = new {}({}, nameof({}){}{});\n",
+			inner, name, member, name, index, member,
+			if args.is_empty() {""} else {", "}, args));
+	}
+	result.push_str(&format!("{}public static readonly {}[] values = {{ {} }};\n\n",
+		inner, name, members.iter().map(|(member, _)| member.as_str()).collect::<Vec<&str>>().join(", ")));
+	result.push_str(&format!("{}public readonly int index;\n", inner));
+	result.push_str(&format!("{}public readonly string name;\n\n", inner));
+	result.push_str(&format!("{}public override string ToString() => name;\n\n", inner));
+	result.push_str(&format!("{}public static explicit operator int({} value) => value.index;\n", inner, name));
+	result.push_str(&public_fields(&enum_constructors(&rest, name)));
+	result.push_str(&format!("{}}}", decl.indent));
+	result
+}
+
+/// Make the fields of an enhanced enum public, unless they are private
+/// in dart, since the fields of a C# class are private by default
+fn public_fields(rest: &str) -> String {
+	lazy_static! {
+		static ref FIELD : Regex = Regex::new(r"(?m)^(?P<indent>[ \t]*)(?P<field>final\s+[\w.]+(?:<[\w\s,<>?.]*>)?\??\s+(?P<name>\w+)\s*[;=,])").unwrap();
+	}
+
+	FIELD.replace_all(rest, |cap: &Captures| -> String {
+		if cap["name"].starts_with('_') {
+			cap.get(0).unwrap().as_str().to_string()
+		} else {
+			format!("{}public {}", &cap["indent"], &cap["field"])
+		}
+	}).to_string()
+}
+
+/// Make the constructors of an enhanced enum private, add the index
+/// and name parameters, and turn the initializing formals `this.x` and
+/// the initializer list into assignments in the constructor body
+fn enum_constructors(rest: &str, name: &str) -> String {
+	let constructor = Regex::new(&format!(r"(?m)^(?P<indent>[ \t]*)(?:const\s+)?{}\s*\(", regex::escape(name))).unwrap();
+	let field = |param: &str| -> String {
+		let re = Regex::new(&format!(
+			r"(?:\b(?:final|late|const|static)\s+)*\b(?P<type>\w+(?:<[\w<>?,\s]*>)?\??)\s+{}\s*[;=]",
+			regex::escape(param))).unwrap();
+		match re.captures(rest) {
+			Some(cap) => cap.name("type").unwrap().as_str().to_string(),
+			None => String::from("dynamic"),
+		}
+	};
+
+	let mut result = String::new();
+	let mut last = 0;
+	for cap in constructor.captures_iter(rest) {
+		let whole = cap.get(0).unwrap();
+		let indent = cap.name("indent").unwrap().as_str();
+		let open = whole.end() - 1;
+		let close = match utils::matching_bracket(rest, open) {
+			Some(close) => close,
+			None => continue,
+		};
+		let after = &rest[close + 1..];
+		let end = match after.find([';', '{']) {
+			Some(offset) => close + 1 + offset,
+			None => continue,
+		};
+		let initializers = rest[close + 1..end].trim().trim_start_matches(':').trim();
+
+		let mut params = vec![String::from("int index"), String::from("string name")];
+		let mut statements = vec![String::from("this.index = index;"), String::from("this.name = name;")];
+		for param in split_params(&rest[open + 1..close]) {
+			let param = param.strip_prefix("required ").unwrap_or(param).trim();
+			if let Some(formal) = param.strip_prefix("this.") {
+				let (field_name, default) = match formal.find('=') {
+					Some(i) => (formal[..i].trim(), &formal[i..]),
+					None => (formal.trim(), ""),
+				};
+				params.push(format!("{} {}{}{}", field(field_name), field_name,
+					if default.is_empty() {""} else {" "}, default));
+				statements.push(format!("this.{} = {};", field_name, field_name));
+			} else {
+				params.push(String::from(param));
+			}
+		}
+		for initializer in utils::split_top_level(initializers, ',') {
+			let initializer = initializer.trim();
+			if initializer.is_empty() {
+				continue;
+			}
+			if let Some(condition) = initializer.strip_prefix("assert") {
+				statements.push(format!("Debug.Assert{};", condition));
+			} else {
+				statements.push(format!("this.{};", initializer));
+			}
+		}
+
+		result.push_str(&rest[last..whole.start()]);
+		result.push_str(&format!("{}private {}({}) {{\n", indent, name, params.join(", ")));
+		for statement in statements {
+			result.push_str(&format!("{}  {}\n", indent, statement));
+		}
+		if rest[end..].starts_with(';') {
+			result.push_str(&format!("{}}}", indent));
+			last = end + 1;
+		} else {
+			// Keep the original body after the assignments
+			let body = rest[end + 1..].trim_start_matches([' ', '\t']);
+			let body = body.strip_prefix('\n').unwrap_or(body);
+			last = rest.len() - body.len();
+		}
+	}
+	result.push_str(&rest[last..]);
+	result
+}
+
+/// Split a parameter list, including the optional and named
+/// parameters enclosed in `[]` and `{}`
+fn split_params(params: &str) -> Vec<&str> {
+	let mut result = vec![];
+	for param in utils::split_top_level(params, ',') {
+		let param = param.trim();
+		if param.starts_with('{') || param.starts_with('[') {
+			result.extend(split_params(&param[1..param.len() - 1]));
+		} else if !param.is_empty() {
+			result.push(param);
+		}
+	}
+	result
+}
+
+/// Find the variables, fields and parameters declared with an enum type,
+/// with their scopes
+fn enum_variables(input: &str, decls: &[EnumDecl]) -> Vec<(String, usize, usize)> {
+	let names = decls.iter().map(|decl| regex::escape(decl.name)).collect::<Vec<String>>().join("|");
+	let typed = Regex::new(&format!(r"\b(?:{})\??\s+(?P<var>\w+)\s*[;,=)}}\]]", names)).unwrap();
+	// The types inferred from a member, `var c = E.member;`, or from the
+	// values of the enum, `for (var v in E.values)`
+	let inferred = Regex::new(&format!(
+		r"\b(?:var|final)\s+(?P<var>\w+)\s*(?:=\s*(?:{})\.(?P<member>\w+)\s*[;,]|\s+in\s+(?:{})\.values\s*\))",
+		names, names)).unwrap();
+	typed.captures_iter(input)
+		.chain(inferred.captures_iter(input).filter(|cap| cap.name("member").map(|member| member.as_str() != "values").unwrap_or(true)))
+		.map(|cap| {
+			let var = cap.name("var").unwrap();
+			let (start, end) = declaration_scope(input, var.start());
+			(var.as_str().to_string(), start, end)
+		})
+		.collect()
+}
+
+/// The scope of the declaration at `pos`: the body of the function if it
+/// is a parameter, or the enclosing block, or the whole text
+fn declaration_scope(text: &str, pos: usize) -> (usize, usize) {
+	let mut open = match utils::enclosing_open(text, pos) {
+		Some(open) => open,
+		None => return (0, text.len()),
+	};
+	// The named and optional parameters are in brackets inside the parentheses
+	while text[..open].trim_end().ends_with(['(', ',']) {
+		match utils::enclosing_open(text, open) {
+			Some(outer) if text[outer..].starts_with('(') => open = outer,
+			_ => break,
+		}
+	}
+	let close = match utils::matching_bracket(text, open) {
+		Some(close) => close,
+		None => return (open, text.len()),
+	};
+	if !text[open..].starts_with('(') {
+		return (open, close + 1);
+	}
+	let mut body = utils::skip_spaces(text, close + 1);
+	if let Some(modifier) = ["async*", "async", "sync*"].iter().find(|modifier| text[body..].starts_with(*modifier)) {
+		body = utils::skip_spaces(text, body + modifier.len());
+	}
+	if text[body..].starts_with('{') {
+		(open, utils::matching_bracket(text, body).map(|close| close + 1).unwrap_or(text.len()))
+	} else {
+		(open, body + utils::split_top_level(&text[body..], ';')[0].len())
+	}
+}
+
+/// Rewrite `E.member`, `E.values` and their `.name` and `.index`
+fn rewrite_members(text: &str, decls: &[EnumDecl]) -> String {
+	let names = decls.iter().map(|decl| regex::escape(decl.name)).collect::<Vec<String>>().join("|");
+	let member = Regex::new(&format!(
+		r"\b(?P<enum>{})\.(?P<member>\w+)(?:\.(?P<property>name|index)\b)?",
+		names)).unwrap();
+	member.replace_all(text, |cap: &Captures| -> String {
+		let enum_name = cap.name("enum").unwrap().as_str();
+		let member = cap.name("member").unwrap().as_str();
+		let is_simple = decls.iter().any(|decl| decl.name == enum_name && !decl.is_enhanced());
+		if member == "values" {
+			let values = if is_simple {
+				format!("(({}[])Enum.GetValues(typeof({})))", enum_name, enum_name)
+			} else {
+				format!("{}.values", enum_name)
+			};
+			return match cap.name("property") {
+				Some(property) => format!("{}.{}", values, property.as_str()),
+				None => values,
+			}
+		}
+		let value = format!("{}.{}", enum_name, keywords::escape_csharp_keyword(member));
+		match cap.name("property").map(|property| property.as_str()) {
+			Some("name") => format!("{}.ToString()", value),
+			Some("index") => int_cast(&value, &text[cap.get(0).unwrap().end()..]),
+			_ => value,
+		}
+	}).to_string()
+}
+
+/// Rewrite `.name` and `.index` of the variables declared with an enum
+/// type, in their scopes
+fn rewrite_variables(text: &str, decls: &[EnumDecl]) -> String {
+	let variables = enum_variables(text, decls);
+	if variables.is_empty() {
+		return text.to_string();
+	}
+	let vars = variables.iter().map(|(var, _, _)| regex::escape(var)).collect::<HashSet<String>>()
+		.into_iter().collect::<Vec<String>>().join("|");
+	let property = Regex::new(&format!(
+		r"(?P<value>(?:\bthis\.)?\b(?P<var>{}))\.(?P<property>name|index)\b(?P<call>\s*\()?",
+		vars)).unwrap();
+	property.replace_all(text, |cap: &Captures| -> String {
+		let value = cap.name("value").unwrap().as_str();
+		let var = cap.name("var").unwrap();
+		let in_scope = variables.iter()
+			.any(|(name, start, end)| name == var.as_str() && *start <= var.start() && var.start() < *end);
+		if cap.name("call").is_some() || !in_scope {
+			return cap.get(0).unwrap().as_str().to_string();
+		}
+		match cap.name("property").unwrap().as_str() {
+			"name" => format!("{}.ToString()", value),
+			_ => int_cast(value, &text[cap.get(0).unwrap().end()..]),
+		}
+	}).to_string()
+}
+
+/// Cast the value to int, with parentheses if it is followed by
+/// a member access or an index, which bind tighter than the cast
+fn int_cast(value: &str, following: &str) -> String {
+	if following.starts_with('.') || following.starts_with('[') {
+		format!("((int){})", value)
+	} else {
+		format!("(int){}", value)
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn simple_enum_1() {
+		assert_eq!(
+			transpile_enums(r"enum CssBorderStyle { dashed, dotted, double, solid }"),
+			r"public enum CssBorderStyle { dashed, dotted, @double, solid }"
+		);
+	}
+
+	#[test]
+	fn simple_enum_2() {
+		assert_eq!(
+			transpile_enums(r"
+enum CssLengthUnit {
+  em,
+  px,
+}
+
+class A {
+  enum _B { object, string; }
+}"),
+			r"
+public enum CssLengthUnit {
+  em,
+  px,
+}
+
+class A {
+  enum _B { @object, @string }
+}"
+		);
+	}
+
+	#[test]
+	fn enum_uses() {
+		assert_eq!(
+			transpile_enums(r"enum CssBorderStyle { dashed, dotted, double, solid }
+
+void f(CssBorderStyle style, {CssBorderStyle other}) {
+  switch (style) {
+    case CssBorderStyle.double:
+      print(style.name + other.index.toString());
+      print(CssBorderStyle.double.name);
+      print(CssBorderStyle.solid.index);
+      print(CssBorderStyle.values.length);
+      print(this.style.name);
+      print(name);
+  }
+}"),
+			r"public enum CssBorderStyle { dashed, dotted, @double, solid }
+
+void f(CssBorderStyle style, {CssBorderStyle other}) {
+  switch (style) {
+    case CssBorderStyle.@double:
+      print(style.ToString() + ((int)other).toString());
+      print(CssBorderStyle.@double.ToString());
+      print((int)CssBorderStyle.solid);
+      print(((CssBorderStyle[])Enum.GetValues(typeof(CssBorderStyle))).length);
+      print(this.style.ToString());
+      print(name);
+  }
+}"
+		);
+	}
+
+	#[test]
+	fn enum_variables_scoped() {
+		assert_eq!(
+			transpile_enums(r"enum Color { red, green }
+
+void f(String name, [Color color]) {
+  print(color.index);
+}
+
+void g(Widget color) => print(color.index + name.index);
+
+class A {
+  Color tint;
+  void h() {
+    if (a) {
+      Color name = tint;
+      print(name.name);
+    }
+    print(tint.name + name.name);
+  }
+}
+
+void k() {
+  var c = Color.red;
+  for (final v in Color.values) print(v.name);
+  print(c.index + v.index);
+}"),
+			r"public enum Color { red, green }
+
+void f(String name, [Color color]) {
+  print((int)color);
+}
+
+void g(Widget color) => print(color.index + name.index);
+
+class A {
+  Color tint;
+  void h() {
+    if (a) {
+      Color name = tint;
+      print(name.ToString());
+    }
+    print(tint.ToString() + name.name);
+  }
+}
+
+void k() {
+  var c = Color.red;
+  for (final v in ((Color[])Enum.GetValues(typeof(Color)))) print(v.ToString());
+  print((int)c + v.index);
+}"
+		);
+	}
+
+	#[test]
+	fn enhanced_enum_1() {
+		assert_eq!(
+			transpile_enums(r"enum Planet {
+  mercury(mass: 3.303e+23, radius: 2.4397e6),
+  venus(mass: 4.869e+24, radius: 6.0518e6);
+
+  const Planet({required this.mass, required this.radius});
+
+  final double mass;
+  final double radius;
+
+  double get surfaceGravity => 6.67300E-11 * mass / (radius * radius);
+}
+
+void f(Planet p) => print(p.name + Planet.values.length.toString());"),
+			r"public sealed class Planet {
+  public static readonly Planet mercury = new Planet(0, nameof(mercury), mass: 3.303e+23, radius: 2.4397e6);
+  public static readonly Planet venus = new Planet(1, nameof(venus), mass: 4.869e+24, radius: 6.0518e6);
+  public static readonly Planet[] values = { mercury, venus };
+
+  public readonly int index;
+  public readonly string name;
+
+  public override string ToString() => name;
+
+  public static explicit operator int(Planet value) => value.index;
+
+  private Planet(int index, string name, double mass, double radius) {
+    this.index = index;
+    this.name = name;
+    this.mass = mass;
+    this.radius = radius;
+  }
+
+  public final double mass;
+  public final double radius;
+
+  double get surfaceGravity => 6.67300E-11 * mass / (radius * radius);
+}
+
+void f(Planet p) => print(p.ToString() + Planet.values.length.toString());"
+		);
+	}
+
+	#[test]
+	fn enhanced_enum_2() {
+		assert_eq!(
+			transpile_enums(r"enum Unit {
+  em(16, 'em'), px(1, 'px');
+
+  final int size;
+  final String suffix;
+  const Unit(this.size, String s) : suffix = s, assert(size > 0) {
+    print(size);
+  }
+}"),
+			r"using System.Diagnostics;
+
+public sealed class Unit {
+  public static readonly Unit em = new Unit(0, nameof(em), 16, 'em');
+  public static readonly Unit px = new Unit(1, nameof(px), 1, 'px');
+  public static readonly Unit[] values = { em, px };
+
+  public readonly int index;
+  public readonly string name;
+
+  public override string ToString() => name;
+
+  public static explicit operator int(Unit value) => value.index;
+
+  public final int size;
+  public final String suffix;
+  private Unit(int index, string name, int size, String s) {
+    this.index = index;
+    this.name = name;
+    this.size = size;
+    this.suffix = s;
+    Debug.Assert(size > 0);
+    print(size);
+  }
+}"
+		);
+	}
+}
@@ -0,0 +1,292 @@
+use super::Element;
+use super::Nothing;
+use super::TextPart;
+
+/// Match the first element, or the second if the first fails.
+/// Both alternatives must parse into the same Detail, use `map`
+/// to unify them if they don't.
+#[derive(Debug, Clone)]
+pub struct Choice<A, B> {
+	first: A,
+	second: B,
+}
+
+impl<A, B> Choice<A, B> {
+	pub fn new(first: A, second: B) -> Choice<A, B> {
+		Choice { first, second }
+	}
+}
+
+impl<A: Element, B: Element<Detail = A::Detail>> Element for Choice<A, B> {
+	type Detail = A::Detail;
+
+	fn find_at(&self, text: &str, start: usize) -> Option<(TextPart, A::Detail)> {
+		match self.first.find_at(text, start) {
+			Some((part, detail)) if part.start == start => Some((part, detail)),
+			_ => match self.second.find_at(text, start) {
+				Some((part, detail)) if part.start == start => Some((part, detail)),
+				_ => None
+			}
+		}
+	}
+}
+
+/// Match the element if possible. Otherwise match the empty string
+/// and parse into None.
+#[derive(Debug, Clone)]
+pub struct Optional<E> {
+	element: E,
+}
+
+impl<E> Optional<E> {
+	pub fn new(element: E) -> Optional<E> {
+		Optional { element }
+	}
+}
+
+impl<E: Element> Element for Optional<E> {
+	type Detail = Option<E::Detail>;
+
+	fn find_at(&self, text: &str, start: usize) -> Option<(TextPart, Option<E::Detail>)> {
+		match self.element.find_at(text, start) {
+			Some((part, detail)) if part.start == start => Some((part, Some(detail))),
+			_ => Some((TextPart::empty(start), None))
+		}
+	}
+}
+
+/// Match the element repeatedly, with an optional separator between
+/// the repetitions. The Details of the separators are dropped.
+/// Repetition stops as soon as an iteration matches nothing, so
+/// elements that may match the empty string do not loop forever.
+#[derive(Debug, Clone)]
+pub struct Repeat<E, S> {
+	element: E,
+	separator: S,
+	min: usize,
+	trailing: bool,
+}
+
+impl<E> Repeat<E, Nothing> {
+	/// Zero or more repetitions without separator
+	pub fn new(element: E) -> Repeat<E, Nothing> {
+		Repeat {
+			element,
+			separator: Nothing,
+			min: 0,
+			trailing: false
+		}
+	}
+}
+
+impl<E, S> Repeat<E, S> {
+	/// One or more repetitions separated by `separator`
+	pub fn separated(element: E, separator: S) -> Repeat<E, S> {
+		Repeat {
+			element,
+			separator,
+			min: 1,
+			trailing: false
+		}
+	}
+
+	/// Require at least `min` repetitions
+	pub fn at_least(mut self, min: usize) -> Repeat<E, S> {
+		self.min = min;
+		self
+	}
+
+	/// Accept (and consume) a separator after the last repetition,
+	/// like the trailing comma of a dart parameter list
+	pub fn allow_trailing(mut self) -> Repeat<E, S> {
+		self.trailing = true;
+		self
+	}
+}
+
+impl<E: Element, S: Element> Element for Repeat<E, S> {
+	type Detail = Vec<E::Detail>;
+
+	fn find_at(&self, text: &str, start: usize) -> Option<(TextPart, Vec<E::Detail>)> {
+		let mut details = vec![];
+		let mut end = start;
+		loop {
+			let mut next = end;
+			if !details.is_empty() {
+				match self.separator.find_at(text, next) {
+					Some((part, _)) if part.start == next => next = part.end,
+					_ => break
+				}
+			}
+			match self.element.find_at(text, next) {
+				Some((part, detail)) if part.start == next => {
+					details.push(detail);
+					let progressed = part.end > end;
+					end = part.end;
+					if !progressed {
+						break;
+					}
+				},
+				_ => {
+					if self.trailing && !details.is_empty() {
+						end = next;
+					}
+					break;
+				}
+			}
+		}
+		if details.len() < self.min {
+			return None
+		}
+		Some((TextPart::new(text, start, end), details))
+	}
+}
+
+/// Succeed without consuming anything if the element matches here
+#[derive(Debug, Clone)]
+pub struct Lookahead<E> {
+	element: E,
+}
+
+impl<E> Lookahead<E> {
+	pub fn new(element: E) -> Lookahead<E> {
+		Lookahead { element }
+	}
+}
+
+impl<E: Element> Element for Lookahead<E> {
+	type Detail = E::Detail;
+
+	fn find_at(&self, text: &str, start: usize) -> Option<(TextPart, E::Detail)> {
+		match self.element.find_at(text, start) {
+			Some((part, detail)) if part.start == start => Some((TextPart::empty(start), detail)),
+			_ => None
+		}
+	}
+}
+
+/// Succeed without consuming anything if the element does not match here
+#[derive(Debug, Clone)]
+pub struct NotAhead<E> {
+	element: E,
+}
+
+impl<E> NotAhead<E> {
+	pub fn new(element: E) -> NotAhead<E> {
+		NotAhead { element }
+	}
+}
+
+impl<E: Element> Element for NotAhead<E> {
+	type Detail = ();
+
+	fn find_at(&self, text: &str, start: usize) -> Option<(TextPart, ())> {
+		match self.element.find_at(text, start) {
+			Some((part, _)) if part.start == start => None,
+			_ => Some((TextPart::empty(start), ()))
+		}
+	}
+}
+
+/// Match the element and transform its Detail with a function
+#[derive(Debug, Clone)]
+pub struct Map<E, F> {
+	element: E,
+	f: F,
+}
+
+impl<E, F> Map<E, F> {
+	pub fn new(element: E, f: F) -> Map<E, F> {
+		Map { element, f }
+	}
+}
+
+impl<E: Element, F: Fn(E::Detail) -> T, T> Element for Map<E, F> {
+	type Detail = T;
+
+	fn find_at(&self, text: &str, start: usize) -> Option<(TextPart, T)> {
+		self.element.find_at(text, start)
+			.map(|(part, detail)| (part, (self.f)(detail)))
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::BasicElement;
+	use regex::Regex;
+
+	fn word() -> BasicElement {
+		BasicElement::new(Regex::new(r"^\s*(\w+)").unwrap())
+	}
+
+	fn symbol(s: &str) -> BasicElement {
+		BasicElement::new(Regex::new(&format!(r"^\s*({})", regex::escape(s))).unwrap())
+	}
+
+	fn texts(parts: Vec<TextPart>) -> Vec<String> {
+		parts.into_iter().map(|part| part.text).collect()
+	}
+
+	#[test]
+	fn choice_1() {
+		let element = symbol("=>").or(symbol("{"));
+		assert_eq!(element.find_at(" => a", 0).unwrap().1.text, "=>");
+		assert_eq!(element.find_at(" { a", 0).unwrap().1.text, "{");
+		assert_eq!(element.find_at(" ; a", 0), None);
+	}
+
+	#[test]
+	fn optional_1() {
+		let element = (word(), symbol("=").then(word()).optional());
+		let (part, (name, default)) = element.find_at("a = b", 0).unwrap();
+		assert_eq!(part.text, "a = b");
+		assert_eq!(name.text, "a");
+		assert_eq!(default.unwrap().1.text, "b");
+
+		let (part, (name, default)) = element.find_at("a, b", 0).unwrap();
+		assert_eq!(part.text, "a");
+		assert_eq!(name.text, "a");
+		assert_eq!(default, None);
+	}
+
+	#[test]
+	fn repeat_1() {
+		let (part, words) = word().repeat().find_at("a b c;", 0).unwrap();
+		assert_eq!(part.text, "a b c");
+		assert_eq!(texts(words), vec!["a", "b", "c"]);
+		assert_eq!(word().repeat().find_at(";", 0), Some((TextPart::empty(0), vec![])));
+		assert_eq!(word().repeat().at_least(1).find_at(";", 0), None);
+	}
+
+	#[test]
+	fn separated_1() {
+		let element = word().separated_by(symbol(","));
+		let (part, words) = element.find_at("a, b ,c, ", 0).unwrap();
+		assert_eq!(part.text, "a, b ,c");
+		assert_eq!(texts(words), vec!["a", "b", "c"]);
+
+		let (part, words) = element.allow_trailing().find_at("a, b ,c, ", 0).unwrap();
+		assert_eq!(part.text, "a, b ,c,");
+		assert_eq!(texts(words), vec!["a", "b", "c"]);
+	}
+
+	#[test]
+	fn lookahead_1() {
+		let element = (word(), Lookahead::new(symbol("(")));
+		assert_eq!(element.find_at("f (x)", 0).unwrap().0.text, "f");
+		assert_eq!(element.find_at("f = x", 0), None);
+
+		let element = (word(), NotAhead::new(symbol("(")));
+		assert_eq!(element.find_at("f (x)", 0), None);
+		assert_eq!(element.find_at("f = x", 0).unwrap().0.text, "f");
+	}
+
+	#[test]
+	fn map_1() {
+		let element = word().separated_by(symbol(","))
+			.map(|words| words.len());
+		assert_eq!(element.find_at("a, b, c", 0).unwrap().1, 3);
+	}
+}
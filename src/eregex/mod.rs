@@ -1,23 +1,84 @@
 use regex::Regex;
+use std::collections::HashMap;
 
-#[derive(Debug, PartialEq)]
+/// Combinators for composing Elements into larger grammar pieces
+mod combinators;
+
+pub use combinators::{Choice, Lookahead, Map, NotAhead, Optional, Repeat};
+
+#[derive(Debug, PartialEq, Clone)]
 pub struct TextPart {
 	pub start : usize,
 	pub end : usize,
 	pub text : String,
 }
 
+impl TextPart {
+	/// Create the TextPart covering `text[start..end]`
+	pub fn new(text: &str, start: usize, end: usize) -> TextPart {
+		TextPart {
+			start,
+			end,
+			text: text[start..end].to_string()
+		}
+	}
+
+	/// An empty TextPart located at `start`, used by elements that
+	/// match without consuming anything
+	pub fn empty(start: usize) -> TextPart {
+		TextPart {
+			start,
+			end: start,
+			text: String::new()
+		}
+	}
+}
+
 /// Element is the basic unit of parsing. The most simple Element is implemented
 /// directly by regular expression. More complex Element can be implemented by
 /// composing simpler Element.
+///
+/// Composite elements assume that every element they contain matches exactly
+/// at the given position, so the regex of a BasicElement used in a composition
+/// should be anchored with `^`.
 pub trait Element {
 	type Detail;
 	/// Given a text and a position, find out if it satisfies the pattern
 	/// of this element. If it satisfies, parse it into Detail.
 	fn find_at(&self, text: &str, start: usize) -> Option<(TextPart, Self::Detail)>;
+
+	/// Match this element followed immediately by `other`
+	fn then<E: Element>(self, other: E) -> (Self, E) where Self: Sized {
+		(self, other)
+	}
+
+	/// Match this element, or `other` if this element fails
+	fn or<E: Element<Detail = Self::Detail>>(self, other: E) -> Choice<Self, E> where Self: Sized {
+		Choice::new(self, other)
+	}
+
+	/// Match this element if possible, otherwise match nothing
+	fn optional(self) -> Optional<Self> where Self: Sized {
+		Optional::new(self)
+	}
+
+	/// Match this element zero or more times
+	fn repeat(self) -> Repeat<Self, Nothing> where Self: Sized {
+		Repeat::new(self)
+	}
+
+	/// Match this element one or more times, separated by `separator`
+	fn separated_by<S: Element>(self, separator: S) -> Repeat<Self, S> where Self: Sized {
+		Repeat::separated(self, separator)
+	}
+
+	/// Transform the Detail of this element
+	fn map<F, T>(self, f: F) -> Map<Self, F> where Self: Sized, F: Fn(Self::Detail) -> T {
+		Map::new(self, f)
+	}
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BasicElement {
 	regex : Regex
 }
@@ -33,23 +94,212 @@ impl BasicElement {
 impl Element for BasicElement {
 	type Detail = TextPart;
 
+	/// The Detail is the first capture group, or the whole match if the
+	/// regex has no group (or the group does not participate in the match)
 	fn find_at(&self, text: &str, start: usize) -> Option<(TextPart, TextPart)> {
-		if start >= text.len() {
+		if start > text.len() {
+			return None
+		}
+		let cap = self.regex.captures(&text[start..])?;
+		let mat = cap.get(0).unwrap();
+		let group = cap.get(1).unwrap_or(mat);
+		Some((TextPart {
+			start: start + mat.start(),
+			end: start + mat.end(),
+			text: mat.as_str().to_string()
+		}, TextPart {
+			start: start + group.start(),
+			end: start + group.end(),
+			text: group.as_str().to_string()
+		}))
+	}
+}
+
+/// Element matching a regex with any number of named groups, parsed into
+/// a map from the group names to the TextParts they captured. Groups that
+/// do not participate in the match are absent from the map.
+///
+/// Unlike BasicElement, the regex runs on the whole text, so `\b` and
+/// other assertions see the characters before `start`. The match must
+/// begin exactly at `start`, so the regex should not start with `^`.
+#[derive(Debug, Clone)]
+pub struct NamedElement {
+	regex : Regex
+}
+
+impl NamedElement {
+	pub fn new(regex : Regex) -> NamedElement {
+		NamedElement {
+			regex
+		}
+	}
+}
+
+impl Element for NamedElement {
+	type Detail = HashMap<String, TextPart>;
+
+	fn find_at(&self, text: &str, start: usize) -> Option<(TextPart, HashMap<String, TextPart>)> {
+		if start > text.len() {
+			return None
+		}
+		let mut locations = self.regex.capture_locations();
+		let mat = self.regex.captures_read_at(&mut locations, text, start)?;
+		// The leftmost match is found, so there is no match at `start`
+		// if this one begins later
+		if mat.start() != start {
 			return None
 		}
-		if let Some(mat) = self.regex.find(&text[start..]) {
-			let group = self.regex.captures(mat.as_str()).unwrap().get(1).unwrap();
-			Some((TextPart {
-				start: start + mat.start(),
-				end: start + mat.end(),
-				text: mat.as_str().to_string()
-			}, TextPart {
-				start: start + mat.start() + group.start(),
-				end: start + mat.start() + group.end(),
-				text: group.as_str().to_string()
-			}))
-		} else {
-			None
+		let mut groups = HashMap::new();
+		for (i, name) in self.regex.capture_names().enumerate() {
+			if let (Some(name), Some((s, e))) = (name, locations.get(i)) {
+				groups.insert(name.to_string(), TextPart::new(text, s, e));
+			}
+		}
+		Some((TextPart::new(text, mat.start(), mat.end()), groups))
+	}
+}
+
+/// The element that always matches the empty string
+#[derive(Debug, Clone, Copy)]
+pub struct Nothing;
+
+impl Element for Nothing {
+	type Detail = ();
+
+	fn find_at(&self, _text: &str, start: usize) -> Option<(TextPart, ())> {
+		Some((TextPart::empty(start), ()))
+	}
+}
+
+/// Implement Element for tuples of elements, which match their members
+/// one after another. The Detail is the tuple of the members' Details,
+/// so `(TypeName, Word, Optional<Default>)` parses into
+/// `(TextPart, TextPart, Option<TextPart>)`.
+macro_rules! sequence_element {
+	($($name:ident : $index:tt),+) => {
+		impl<$($name: Element),+> Element for ($($name,)+) {
+			type Detail = ($($name::Detail,)+);
+
+			fn find_at(&self, text: &str, start: usize) -> Option<(TextPart, Self::Detail)> {
+				let mut end = start;
+				let detail = ($({
+					let (part, detail) = self.$index.find_at(text, end)?;
+					if part.start != end {
+						return None
+					}
+					end = part.end;
+					detail
+				},)+);
+				Some((TextPart::new(text, start, end), detail))
+			}
 		}
+	};
+}
+
+sequence_element!(A: 0, B: 1);
+sequence_element!(A: 0, B: 1, C: 2);
+sequence_element!(A: 0, B: 1, C: 2, D: 3);
+sequence_element!(A: 0, B: 1, C: 2, D: 3, E: 4);
+sequence_element!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5);
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn word() -> BasicElement {
+		BasicElement::new(Regex::new(r"^\s*(\w+)").unwrap())
+	}
+
+	fn symbol(s: &str) -> BasicElement {
+		BasicElement::new(Regex::new(&format!(r"^\s*({})", regex::escape(s))).unwrap())
+	}
+
+	#[test]
+	fn sequence_1() {
+		assert_eq!(
+			(word(), word()).find_at("int a, int b", 0),
+			Some((TextPart::new("int a, int b", 0, 5), (
+				TextPart::new("int a, int b", 0, 3),
+				TextPart::new("int a, int b", 4, 5)
+			)))
+		);
+	}
+
+	#[test]
+	fn sequence_2() {
+		assert_eq!(
+			word().then(symbol(",")).then(word()).find_at("int a, int b", 4),
+			Some((TextPart::new("int a, int b", 4, 10), (
+				(
+					TextPart::new("int a, int b", 4, 5),
+					TextPart::new("int a, int b", 5, 6)
+				),
+				TextPart::new("int a, int b", 7, 10)
+			)))
+		);
+	}
+
+	#[test]
+	fn sequence_3() {
+		assert_eq!((word(), symbol("=")).find_at("int a, int b", 0), None);
+	}
+
+	#[test]
+	fn sequence_unanchored() {
+		let unanchored = BasicElement::new(Regex::new(r"(b)").unwrap());
+		assert_eq!((word(), unanchored).find_at("a c b", 0), None);
+	}
+
+	#[test]
+	fn nothing_at_end() {
+		assert_eq!(
+			(word(), Nothing).find_at("abc", 0),
+			Some((TextPart::new("abc", 0, 3), (TextPart::new("abc", 0, 3), ())))
+		);
+	}
+
+	#[test]
+	fn basic_without_group() {
+		let element = BasicElement::new(Regex::new(r"^\s*\w+").unwrap());
+		assert_eq!(
+			element.find_at("a bc", 1),
+			Some((TextPart::new("a bc", 1, 4), TextPart::new("a bc", 1, 4)))
+		);
+	}
+
+	#[test]
+	fn basic_group_in_context() {
+		// `\B` does not match at the start of the matched text on its own,
+		// so the group can only be found with the surrounding text
+		let element = BasicElement::new(Regex::new(r"\B(a)").unwrap());
+		assert_eq!(
+			element.find_at("ba", 0),
+			Some((TextPart::new("ba", 1, 2), TextPart::new("ba", 1, 2)))
+		);
+	}
+
+	#[test]
+	fn named_1() {
+		let element = NamedElement::new(Regex::new(
+			r"\s*(?P<type>\w+)\s+(?P<name>\w+)(?:\s*=\s*(?P<default>\w+))?").unwrap());
+		let text = "f(int a = 1, double b)";
+		let (part, groups) = element.find_at(text, 2).unwrap();
+		assert_eq!(part, TextPart::new(text, 2, 11));
+		assert_eq!(groups["type"], TextPart::new(text, 2, 5));
+		assert_eq!(groups["name"], TextPart::new(text, 6, 7));
+		assert_eq!(groups["default"], TextPart::new(text, 10, 11));
+
+		let (part, groups) = element.find_at(text, 12).unwrap();
+		assert_eq!(part.text, " double b");
+		assert_eq!(groups["name"].text, "b");
+		assert!(!groups.contains_key("default"));
+	}
+
+	#[test]
+	fn named_anchored() {
+		let element = NamedElement::new(Regex::new(r"\b(?P<word>\w+)").unwrap());
+		assert_eq!(element.find_at("ab cd", 1), None);
+		assert_eq!(element.find_at("ab cd", 2), None);
+		assert_eq!(element.find_at("ab cd", 3).unwrap().1["word"].text, "cd");
 	}
 }
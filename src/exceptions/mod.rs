@@ -0,0 +1,513 @@
+use regex::Captures;
+use regex::Regex;
+use crate::config::Config;
+use crate::keywords;
+use crate::mask;
+use crate::utils;
+
+/// The class wrapping the thrown objects which are not exceptions
+const THROWN_VALUE : &str = "ThrownValueException";
+
+/// Transpile the exception handling, which must be run after `new`
+/// is inserted before the constructor calls.
+///
+/// - `on T catch (e, st)` becomes `catch (T e)`, with `st` declared as
+///   `e.StackTrace` in the block, and `catch (e)` catches `Exception`
+/// - `rethrow` becomes `throw`
+/// - the dart core errors are mapped to the .NET exceptions
+/// - throwing a literal, or an object of a class declared in the input
+///   which is not an exception, wraps it in a generated exception class
+/// - for C# older than 7, which has no throw expressions, `=> throw e;`
+///   becomes a block body, `x ?? throw e` becomes
+///   `DartExceptions.OrThrow(x, () => e)`, and `c ? throw e : x` becomes
+///   `DartExceptions.ThrowIf(c, () => e, () => x)`, with a generated
+///   helper class
+pub fn transpile_exceptions(input: &str, config: &Config) -> String {
+	lazy_static! {
+		static ref RETHROW : Regex = Regex::new(r"\brethrow\s*;").unwrap();
+	}
+
+	let result = transpile_catches(input);
+	let result = RETHROW.replace_all(&result, "throw;").to_string();
+	let result = transpile_errors(&result);
+	let result = if config.csharp_version < 7 {
+		lower_throw_bodies(&result)
+	} else {
+		result
+	};
+	let result = wrap_thrown_values(&result);
+	if config.csharp_version < 7 {
+		lower_throw_expressions(&result)
+	} else {
+		result
+	}
+}
+
+fn transpile_catches(input: &str) -> String {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"(?x)
+			(?P<pre>\}\s*)
+			(?:on\s+(?P<type>[\w.@]+(?:<[\w\s,<>?.@()]*>)?)\s*)?
+			(?:catch\s*\(\s*(?P<error>[\w@]+)\s*(?:,\s*(?P<stack>[\w@]+)\s*)?\)\s*)?
+			\{
+		").unwrap();
+		static ref MESSAGE : Regex = Regex::new(r"\.message\b").unwrap();
+	}
+
+	let mut text = input.to_string();
+	let mut pos = 0;
+	while let Some(cap) = RE.captures(&text[pos..]) {
+		let whole = cap.get(0).unwrap();
+		let start = pos + cap.name("pre").unwrap().end();
+		let open = pos + whole.end() - 1;
+		pos = open + 1;
+		if cap.name("type").is_none() && cap.name("error").is_none() {
+			continue;
+		}
+		let close = match utils::matching_bracket(&text, open) {
+			Some(close) => close,
+			None => continue,
+		};
+		let ty = match cap.name("type").map(|ty| ty.as_str()) {
+			None | Some("object") | Some("dynamic") => "Exception",
+			Some(ty) => ty,
+		};
+		let stack = cap.name("stack").map(|stack| stack.as_str()).filter(|stack| *stack != "_");
+		let error = match cap.name("error").map(|error| error.as_str()) {
+			Some("_") if stack.is_some() => Some("_error"),
+			Some("_") | None => None,
+			Some(error) => Some(error),
+		};
+
+		let mut body = text[open + 1..close].to_string();
+		if let Some(error) = error {
+			let re = Regex::new(&format!(r"\b{}\.message\b", regex::escape(error))).unwrap();
+			body = re.replace_all(&body, |_: &Captures| format!("{}.Message", error)).to_string();
+		}
+		// The uses of the stack trace may be hidden in masked strings
+		if let (Some(error), Some(stack)) = (error, stack) {
+			let declaration = format!("var {} = {}.StackTrace;", stack, error);
+			match body.find('\n').filter(|newline| body[..*newline].trim().is_empty()) {
+				Some(newline) => {
+					let indent = utils::line_indent(&body, newline + 1).to_string();
+					body.insert_str(newline, &format!("\n{}{}", indent, declaration));
+				},
+				None => body.insert_str(0, &format!(" {}", declaration)),
+			}
+		}
+		let header = match error {
+			Some(error) => format!("catch ({} {}) {{", ty, error),
+			None => format!("catch ({}) {{", ty),
+		};
+		let replacement = format!("{}{}}}", header, body);
+		text.replace_range(start..close + 1, &replacement);
+		pos = start + header.len();
+	}
+	text
+}
+
+/// Map the dart core errors to the .NET exceptions, reordering the
+/// arguments of the constructors which differ
+fn transpile_errors(input: &str) -> String {
+	lazy_static! {
+		static ref CONSTRUCTOR : Regex = Regex::new(r"(?P<new>\bnew\s+)?\b(?P<name>ArgumentError|RangeError|FormatException)(?:\.(?P<named>\w+))?\s*\(").unwrap();
+		static ref NAME : Regex = Regex::new(r"(?P<pre>\.\s*)?\b(?P<name>ArgumentError|StateError|RangeError|UnsupportedError|UnimplementedError|TypeError|Error)\b").unwrap();
+	}
+
+	let mut text = input.to_string();
+	let mut pos = 0;
+	while let Some(cap) = CONSTRUCTOR.captures(&text[pos..]) {
+		let whole = cap.get(0).unwrap();
+		let start = pos + whole.start();
+		let open = pos + whole.end() - 1;
+		pos = open + 1;
+		let close = match utils::matching_bracket(&text, open) {
+			Some(close) => close,
+			None => continue,
+		};
+		let args = utils::split_top_level(&text[open + 1..close], ',').iter()
+			.map(|arg| arg.trim().to_string())
+			.filter(|arg| !arg.is_empty())
+			.collect::<Vec<String>>();
+		let arg = |i: usize| args.get(i).cloned().unwrap_or_else(|| String::from("null"));
+		let (exception, args) = match (cap.name("name").unwrap().as_str(), cap.name("named").map(|named| named.as_str())) {
+			("ArgumentError", None) => ("ArgumentException", args.clone()),
+			("ArgumentError", Some("value")) => ("ArgumentException", vec![arg(2), arg(1)]),
+			("ArgumentError", Some("notNull")) => ("ArgumentNullException", vec![arg(0)]),
+			("RangeError", None) => ("ArgumentOutOfRangeException", vec![String::from("null"), arg(0)]),
+			("RangeError", Some("value")) => ("ArgumentOutOfRangeException", vec![arg(1), arg(0), arg(2)]),
+			("RangeError", Some("range")) => ("ArgumentOutOfRangeException", vec![arg(3), arg(0), arg(4)]),
+			("RangeError", Some("index")) => ("ArgumentOutOfRangeException", vec![arg(2), arg(0), arg(3)]),
+			("FormatException", None) => ("FormatException", args.into_iter().take(1).collect()),
+			_ => continue,
+		};
+		let replacement = format!("new {}({})", exception, args.join(", "));
+		text.replace_range(start..close + 1, &replacement);
+		pos = start + replacement.len();
+	}
+
+	NAME.replace_all(&text, |cap: &Captures| -> String {
+		if cap.name("pre").is_some() {
+			return cap.get(0).unwrap().as_str().to_string();
+		}
+		String::from(match cap.name("name").unwrap().as_str() {
+			"ArgumentError" => "ArgumentException",
+			"StateError" => "InvalidOperationException",
+			"RangeError" => "ArgumentOutOfRangeException",
+			"UnsupportedError" => "NotSupportedException",
+			"UnimplementedError" => "NotImplementedException",
+			"TypeError" => "InvalidCastException",
+			_ => "Exception",
+		})
+	}).to_string()
+}
+
+/// Wrap the thrown objects which are not exceptions, and declare the
+/// wrapping class at the end if it is used
+fn wrap_thrown_values(input: &str) -> String {
+	lazy_static! {
+		static ref THROW : Regex = Regex::new(r"\bthrow\s+").unwrap();
+		static ref LITERAL : Regex = Regex::new(r"^(?:-?\d|\[|\{|true\b|false\b|null\b|new\s+(?:List|Dictionary|HashSet)\b)").unwrap();
+		static ref CLASS : Regex = Regex::new(r"\bclass\s+(?P<name>\w+)(?P<header>[^{]*)\{").unwrap();
+		static ref CONSTRUCTED : Regex = Regex::new(r"^new\s+(?P<name>\w+)").unwrap();
+	}
+
+	let values = CLASS.captures_iter(input)
+		.filter(|cap| !cap["header"].contains("Exception") && !cap["header"].contains("Error")
+			&& !cap["name"].ends_with("Exception") && !cap["name"].ends_with("Error"))
+		.map(|cap| cap["name"].to_string())
+		.collect::<Vec<String>>();
+	let mut text = input.to_string();
+	let mut pos = 0;
+	let mut wrapped = false;
+	while let Some(mat) = THROW.find_at(&text, pos) {
+		let start = mat.end();
+		pos = start;
+		// The thrown value of the first branch of `c ? throw e : x` ends at `:`
+		let end = branch_end(&text, start).unwrap_or_else(|| utils::expression_end(&text, start));
+		let value = text[start..end].trim_end().to_string();
+		let is_value = value.starts_with(|c: char| mask::span_index(c).is_some())
+			|| LITERAL.is_match(&value)
+			|| CONSTRUCTED.captures(&value).map(|cap| values.iter().any(|name| *name == cap["name"])).unwrap_or(false);
+		if !is_value {
+			continue;
+		}
+		let replacement = format!("new {}({})", THROWN_VALUE, value);
+		text.replace_range(start..start + value.len(), &replacement);
+		pos = start + replacement.len();
+		wrapped = true;
+	}
+	if !wrapped {
+		return text;
+	}
+	format!("{}\n\npublic class {} : Exception\n{{\n\tpublic object Value {{ get; }}\n\n\tpublic {}(object value) : base(value?.ToString())\n\t{{\n\t\tValue = value;\n\t}}\n}}\n",
+		text.trim_end(), THROWN_VALUE, THROWN_VALUE)
+}
+
+/// Turn the arrow bodies `=> throw e;` into blocks, `{ throw e; }` for the
+/// members and `=> { throw e; }` for the lambdas
+fn lower_throw_bodies(input: &str) -> String {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"=>\s*throw\b").unwrap();
+	}
+
+	let mut text = input.to_string();
+	let mut pos = 0;
+	while let Some(mat) = RE.find_at(&text, pos) {
+		let arrow = mat.start();
+		let throw = mat.end() - "throw".len();
+		let end = utils::expression_end(&text, throw);
+		let statement = text[throw..end].trim_end().to_string();
+		let (range, replacement) = if text[end..].starts_with(';') && is_member_arrow(&text, arrow) {
+			(arrow..end + 1, format!("{{ {}; }}", statement))
+		} else {
+			(arrow..throw + statement.len(), format!("=> {{ {}; }}", statement))
+		};
+		pos = arrow + replacement.len();
+		text.replace_range(range, &replacement);
+	}
+	text
+}
+
+/// Whether the arrow at the position starts the body of a member, like
+/// `void f() => ...` or `int get x => ...`, rather than that of a lambda
+fn is_member_arrow(text: &str, arrow: usize) -> bool {
+	lazy_static! {
+		static ref PROPERTY : Regex = Regex::new(r"(?:\b(?P<word>[\w@]+)|[>?\]])\s+[\w@]+\s*$").unwrap();
+		static ref FUNCTION : Regex = Regex::new(r"(?:\b(?P<word>[\w@]+)|\boperator\s*[^\s\w(]+)\s*(?:<[\w\s,<>?.@]*>)?\s*$").unwrap();
+	}
+
+	let before = text[..arrow].trim_end();
+	let cap = if before.ends_with(')') {
+		match utils::matching_open(text, before.len() - 1) {
+			Some(open) => FUNCTION.captures(&text[..open]),
+			None => None,
+		}
+	} else {
+		PROPERTY.captures(before)
+	};
+	match cap {
+		Some(cap) => match cap.name("word").map(|word| word.as_str()) {
+			Some(word) => word == "get" || keywords::is_declaring_keyword(word) || !keywords::is_dart_keyword(word),
+			None => true,
+		},
+		None => false,
+	}
+}
+
+/// Lower `x ?? throw e` into `DartExceptions.OrThrow(x, () => e)`, and the
+/// conditional expressions with a throwing branch, `c ? throw e : x` and
+/// `c ? x : throw e`, into `DartExceptions.ThrowIf(c, () => e, () => x)`,
+/// and declare the helper class at the end if it is used
+fn lower_throw_expressions(input: &str) -> String {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"\?\?\s*throw\s+").unwrap();
+		static ref THEN : Regex = Regex::new(r"\?\s*throw\s+").unwrap();
+		static ref ELSE : Regex = Regex::new(r":\s*throw\s+").unwrap();
+	}
+
+	let mut text = input.to_string();
+	let mut lowered = false;
+	while let Some(mat) = RE.find(&text) {
+		let start = utils::skip_spaces(&text, operand_start(&text, mat.start()));
+		let end = utils::expression_end(&text, mat.end());
+		let operand = text[start..mat.start()].trim_end().to_string();
+		let exception = text[mat.end()..end].trim_end().to_string();
+		let replacement = format!("DartExceptions.OrThrow({}, () => {})", operand, exception);
+		text.replace_range(start..mat.end() + exception.len(), &replacement);
+		lowered = true;
+	}
+	let mut pos = 0;
+	while let Some(mat) = THEN.find_at(&text, pos) {
+		pos = mat.end();
+		let colon = match branch_end(&text, mat.end()) {
+			Some(colon) => colon,
+			None => continue,
+		};
+		let start = utils::skip_spaces(&text, operand_start(&text, mat.start()));
+		let end = utils::expression_end(&text, colon + 1);
+		let condition = text[start..mat.start()].trim_end().to_string();
+		let exception = text[mat.end()..colon].trim_end().to_string();
+		let value = text[colon + 1..end].trim().to_string();
+		let replacement = format!("DartExceptions.ThrowIf({}, () => {}, () => {})", condition, exception, value);
+		text.replace_range(start..colon + 1 + text[colon + 1..end].trim_end().len(), &replacement);
+		pos = start + replacement.len();
+		lowered = true;
+	}
+	pos = 0;
+	while let Some(mat) = ELSE.find_at(&text, pos) {
+		pos = mat.end();
+		let question = match ternary_question(&text, mat.start()) {
+			Some(question) => question,
+			None => continue,
+		};
+		let start = utils::skip_spaces(&text, operand_start(&text, question));
+		let end = utils::expression_end(&text, mat.end());
+		let condition = text[start..question].trim_end().to_string();
+		let value = text[question + 1..mat.start()].trim().to_string();
+		let exception = text[mat.end()..end].trim_end().to_string();
+		let replacement = format!("DartExceptions.ThrowIf(!({}), () => {}, () => {})", condition, exception, value);
+		text.replace_range(start..mat.end() + exception.len(), &replacement);
+		pos = start + replacement.len();
+		lowered = true;
+	}
+	if !lowered {
+		return text;
+	}
+	format!("{}\n\npublic static class DartExceptions\n{{\n\tpublic static T OrThrow<T>(T value, Func<Exception> exception) where T : class\n\t{{\n\t\tif (value == null) throw exception();\n\t\treturn value;\n\t}}\n\n\tpublic static T OrThrow<T>(T? value, Func<Exception> exception) where T : struct\n\t{{\n\t\tif (value == null) throw exception();\n\t\treturn value.Value;\n\t}}\n\n\tpublic static T ThrowIf<T>(bool condition, Func<Exception> exception, Func<T> value)\n\t{{\n\t\tif (condition) throw exception();\n\t\treturn value();\n\t}}\n}}\n",
+		text.trim_end())
+}
+
+/// The position of the `:` ending the first branch of a conditional
+/// expression, which starts at `start`
+fn branch_end(text: &str, start: usize) -> Option<usize> {
+	let mut depth = 0;
+	for (i, c) in text[start..].char_indices() {
+		match c {
+			'(' | '[' | '{' => depth += 1,
+			')' | ']' | '}' if depth == 0 => return None,
+			')' | ']' | '}' => depth -= 1,
+			';' | ',' if depth == 0 => return None,
+			':' if depth == 0 => return Some(start + i),
+			_ => {}
+		}
+	}
+	None
+}
+
+/// The position of the `?` of the conditional expression whose `:` is
+/// at `colon`, if the colon belongs to one, rather than to a case or a
+/// named argument
+fn ternary_question(text: &str, colon: usize) -> Option<usize> {
+	let bytes = text.as_bytes();
+	let mut i = colon;
+	while i > 0 {
+		i -= 1;
+		match bytes[i] {
+			b')' | b']' | b'}' => i = utils::matching_open(text, i)?,
+			b'(' | b'[' | b'{' | b';' | b',' | b':' => return None,
+			b'?' if bytes[i + 1].is_ascii_whitespace() && (i == 0 || bytes[i - 1] != b'?') => return Some(i),
+			_ => {}
+		}
+	}
+	None
+}
+
+/// The start of the left operand of `??` ending at `end`, which extends
+/// to the operators of lower precedence, like `=` or `?:`, or to the
+/// enclosing bracket
+fn operand_start(text: &str, end: usize) -> usize {
+	let bytes = text.as_bytes();
+	let mut i = end;
+	while i > 0 {
+		let c = bytes[i - 1];
+		match c {
+			b')' | b']' => match utils::matching_open(text, i - 1) {
+				Some(open) => i = open,
+				None => return i,
+			},
+			b'(' | b'[' | b'{' | b'}' | b',' | b';' | b':' => return i,
+			b'=' if bytes[i] != b'=' && (i < 2 || !matches!(bytes[i - 2], b'=' | b'!' | b'<' | b'>')) => return i,
+			b'>' if i >= 2 && bytes[i - 2] == b'=' => return i,
+			b'?' if i >= 2 && bytes[i - 2] == b'?' => return i,
+			b'?' if text[i..].starts_with(char::is_whitespace) => return i,
+			_ if c.is_ascii_alphabetic() || c == b'_' => {
+				let word_start = text[..i].rfind(|c: char| !(c.is_alphanumeric() || c == '_')).map(|j| j + 1).unwrap_or(0);
+				if !text[..word_start].ends_with('.') && matches!(&text[word_start..i], "return" | "yield" | "in" | "case" | "else") {
+					return i;
+				}
+				i = word_start;
+			},
+			_ => i -= 1,
+		}
+	}
+	i
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn catches_transpiled() {
+		assert_eq!(
+			transpile_exceptions(r"
+  try {
+    f();
+  } on FormatException catch (e, st) {
+    print(e.message);
+    print(st);
+  } on StateError {
+    rethrow;
+  } catch (e) { log(e); }", &Config::default()),
+			r"
+  try {
+    f();
+  } catch (FormatException e) {
+    var st = e.StackTrace;
+    print(e.Message);
+    print(st);
+  } catch (InvalidOperationException) {
+    throw;
+  } catch (Exception e) { log(e); }"
+		);
+	}
+
+	#[test]
+	fn errors_mapped() {
+		assert_eq!(
+			transpile_exceptions(r"
+  if (a == null) throw new ArgumentError.notNull(name);
+  if (i < 0) throw RangeError.range(i, 0, n, name);
+  int get z => throw new UnsupportedError(message);", &Config { csharp_version: 6, ..Config::default() }),
+			r"
+  if (a == null) throw new ArgumentNullException(name);
+  if (i < 0) throw new ArgumentOutOfRangeException(name, i, null);
+  int get z { throw new NotSupportedException(message); }"
+		);
+	}
+
+	#[test]
+	fn throw_expressions_lowered() {
+		assert_eq!(
+			transpile_exceptions(r"
+  return f(a?.b ?? throw new FormatException(message, source, 1), x == y ? 1 : c[0] ?? throw 42);
+  g(() => throw 1);
+  var v = c ? throw 2 : 1;
+  var w = x == y ? f(1, 2) : throw new StateError(message);
+  switch (v) {
+    case 1: throw 3;
+  }", &Config { csharp_version: 6, ..Config::default() }),
+			r"
+  return f(DartExceptions.OrThrow(a?.b, () => new FormatException(message)), x == y ? 1 : DartExceptions.OrThrow(c[0], () => new ThrownValueException(42)));
+  g(() => { throw new ThrownValueException(1); });
+  var v = DartExceptions.ThrowIf(c, () => new ThrownValueException(2), () => 1);
+  var w = DartExceptions.ThrowIf(!(x == y), () => new InvalidOperationException(message), () => f(1, 2));
+  switch (v) {
+    case 1: throw new ThrownValueException(3);
+  }
+
+public class ThrownValueException : Exception
+{
+	public object Value { get; }
+
+	public ThrownValueException(object value) : base(value?.ToString())
+	{
+		Value = value;
+	}
+}
+
+public static class DartExceptions
+{
+	public static T OrThrow<T>(T value, Func<Exception> exception) where T : class
+	{
+		if (value == null) throw exception();
+		return value;
+	}
+
+	public static T OrThrow<T>(T? value, Func<Exception> exception) where T : struct
+	{
+		if (value == null) throw exception();
+		return value.Value;
+	}
+
+	public static T ThrowIf<T>(bool condition, Func<Exception> exception, Func<T> value)
+	{
+		if (condition) throw exception();
+		return value();
+	}
+}
+"
+		);
+	}
+
+	#[test]
+	fn thrown_values_wrapped() {
+		assert_eq!(
+			transpile_exceptions(r"
+class Failure {}
+void f() {
+  if (a) throw 42;
+  throw new Failure();
+}", &Config::default()),
+			r"
+class Failure {}
+void f() {
+  if (a) throw new ThrownValueException(42);
+  throw new ThrownValueException(new Failure());
+}
+
+public class ThrownValueException : Exception
+{
+	public object Value { get; }
+
+	public ThrownValueException(object value) : base(value?.ToString())
+	{
+		Value = value;
+	}
+}
+"
+		);
+	}
+}
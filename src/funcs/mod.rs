@@ -1,7 +1,7 @@
 use regex::Captures;
 use regex::Regex;
 use std::borrow::Cow;
-use crate::utils;
+use crate::keywords;
 
 /// Process parameter list of function
 mod param_list;
@@ -12,6 +12,7 @@ mod param_item;
 /// Remove the pair of curly brace indicating the range of named
 /// parameters and provide default values if not exists yet.
 /// Transform functional parameter into Action and Function.
+/// Move the `async` modifier before the return type.
 /// This will ignore the function headers without any function
 /// body, because it is hard to differentiate them from function
 /// invocations.
@@ -21,7 +22,7 @@ pub fn transpile_func_head<'a>(input: &'a str) -> Cow::<'a, str> {
 		static ref RE : Regex = Regex::new(r"(?x)(?m)
 			^(?P<leading_space>\s*)
 			(?:
-				(?P<rtype>\w+) # Return type
+				(?P<rtype>\w+(?:<[\w\s,<>?]*>)?\??) # Return type
 				\s+
 			)? # No return type for initializer
 			(?P<fname>\w+) # Function name
@@ -41,6 +42,7 @@ pub fn transpile_func_head<'a>(input: &'a str) -> Cow::<'a, str> {
 				)
 			\s*\) # End of parameter list
 
+			(?:\s*(?P<modifier>async)\b)? # Moved before the return type
       (?P<trailing>\s*(?::|=>|\{)) # To differentiate function header from funtion invocation
 		"
 		).unwrap();
@@ -53,18 +55,26 @@ pub fn transpile_func_head<'a>(input: &'a str) -> Cow::<'a, str> {
 			Some(name) => name.as_str(),
 			None => panic!("No function name!")
 		};
-    if utils::is_keyword(func_name) {
-      return cap.get(0).unwrap().as_str().to_string();
-    }
+		// Control flow statements like `switch (x) {` or `catch (e) {`,
+		// and expressions like `return (a) {`
+		if keywords::is_dart_reserved_word(func_name)
+			|| return_type.map(|t| keywords::starts_statement(t.as_str())).unwrap_or(false) {
+			return cap.get(0).unwrap().as_str().to_string();
+		}
+		// Already transpiled, e.g. the constructors generated for enums
+		if let Some("public") | Some("private") | Some("protected") | Some("internal") = return_type.map(|t| t.as_str()) {
+			return cap.get(0).unwrap().as_str().to_string();
+		}
 		let is_public = !func_name.starts_with("_");
 		let params = match cap.name("params") {
 			Some(content) => content.as_str(),
 			None => panic!("No parameter list!")
 		};
     let trailing = cap.name("trailing").unwrap().as_str();
-		return format!("{}{}{}{}({}){}",
+		format!("{}{}{}{}{}({}){}",
 			leading_space,
 			if is_public {"public "} else {""},
+			if cap.name("modifier").is_some() {"async "} else {""},
 			if let Some(typename) = return_type {
 				format!("{} ", typename.as_str())
 			} else {
@@ -290,4 +300,50 @@ this.widgets = null) : assert((block == null) != (widgets == null));
 }"
 		);
 	}
-}
\ No newline at end of file
+
+	#[test]
+	fn correct_func_head_9() {
+		assert_eq!(
+			transpile_func_head(r"
+  private Unit(int index, string name) {
+    this.index = index;
+  }"),
+			r"
+  private Unit(int index, string name) {
+    this.index = index;
+  }"
+		);
+	}
+
+	#[test]
+	fn control_flow_kept() {
+		let input = r"
+  switch (x) {
+    case 1:
+      try {
+      } on Exception catch (e) {
+      }
+      return (a) {
+      };
+  }
+  while (i < 10) {
+  }
+  await for (final e in stream) {
+  }
+  else if (a) {
+  }";
+		assert_eq!(transpile_func_head(input), input);
+	}
+
+	#[test]
+	fn async_func_head() {
+		assert_eq!(
+			transpile_func_head(r"
+  Future<List<int>> load(String url) async {
+  void _reset() async => await clear();"),
+			r"
+  public async Future<List<int>> load(String url) {
+  async void _reset() => await clear();"
+		);
+	}
+}
@@ -3,7 +3,7 @@ use regex::Regex;
 /// Given a stirng representing an item in parameter list
 /// Add default value (null) to it if it doesn't have yet
 /// If there is @required, replace it with a /* TODO: check null */
-pub fn append_default_value<'a>(input: &'a str) -> String {
+pub fn append_default_value(input: &str) -> String {
 	lazy_static! {
 		static ref RE : Regex = Regex::new(r"(?P<nospace>\S)(?P<trailing>\s*)$").unwrap();
 	}
@@ -18,7 +18,7 @@ pub fn append_default_value<'a>(input: &'a str) -> String {
 
 /// If a function parameter is a function, replace it with an
 /// Action or Function type parameter
-pub fn create_function_action<'a>(input: &'a str) -> String {
+pub fn create_function_action(input: &str) -> String {
 	lazy_static! {
 		static ref RE : Regex = Regex::new(r"(?x)
 			^(?P<leading>\s*)(?P<rtype>\w+)\s+(?P<fname>\w+)\s*\( # Function return type and name
@@ -45,7 +45,7 @@ pub fn create_function_action<'a>(input: &'a str) -> String {
 					format!("{}Action<{}> {}{}", leading, params, func_name, trailing)
 				};
 			}
-			return if params.is_empty() {
+			if params.is_empty() {
 				format!("{}Function<{}> {}{}", leading, return_type, func_name, trailing)
 			} else {
 				format!("{}Function<{}, {}> {}{}", leading, params, return_type, func_name, trailing)
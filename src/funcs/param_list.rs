@@ -39,27 +39,21 @@ fn split_positioned_named(input: &str) -> (Option<&str>, Option<&str>) {
 	let mut named = Option::None;
 
 	for cap in RE.captures_iter(input) {
-		match cap.name("positional") {
-			Some(expr) => {
-				let expr = expr.as_str().trim();
-				positional = if expr.is_empty() {
-					None
-				} else {
-					Option::Some(expr)
-				}
-			},
-			None => {},
+		if let Some(expr) = cap.name("positional") {
+			let expr = expr.as_str().trim();
+			positional = if expr.is_empty() {
+				None
+			} else {
+				Option::Some(expr)
+			}
 		};
-		match cap.name("named") {
-			Some(expr) => {
-				let expr = expr.as_str().trim();
-				named = if expr.is_empty() {
-					None
-				} else {
-					Option::Some(expr)
-				}
-			},
-			None => {},
+		if let Some(expr) = cap.name("named") {
+			let expr = expr.as_str().trim();
+			named = if expr.is_empty() {
+				None
+			} else {
+				Option::Some(expr)
+			}
 		};
 	}
 
@@ -96,6 +90,8 @@ fn split_parameter_list(input: &str) -> (Vec<String>, Vec<String>) {
 /// - TypeName<T> param = value
 /// - TypeName<T,K> param
 /// - TypeName<T,K> param = value
+/// - (T, K) param
+/// - TypeName? param
 /// - TypeName param()
 /// - TypeName param(TypeName a)
 /// - TypeName param(TypeName a, TypeName b)
@@ -127,9 +123,13 @@ fn split_single_parameter_list(input: &str) -> Vec<String> {
 						(?: # The name of the type, or just `this.`
 							this\.
 							|
-							\w+(?: # TypeName
-								<\s*(\w+\s*,)*\w*\w+\s*> # Optional template parameters
-							)?\s+ # TypeName must be splitted with parameter name
+							(?:
+								\w+(?: # TypeName
+									<(?:[\w\s,?.()]|<[\w\s,?.()]*>)*> # Optional template parameters
+								)?
+								|
+								\([\w\s,<>?.{}]*\) # Record type
+							)\??\s+ # TypeName must be splitted with parameter name
 						)\w+ # End of type name or `this.`
 						(?:\s*=[^,]*)? # Optional default value
 						|
@@ -142,9 +142,8 @@ fn split_single_parameter_list(input: &str) -> Vec<String> {
 	let mut result : Vec<String> = vec![];
 
 	for cap in RE.captures_iter(&input) {
-		match cap.name("item") {
-			Some(expr) => result.push(expr.as_str().to_string()),
-			None => {},
+		if let Some(expr) = cap.name("item") {
+			result.push(expr.as_str().to_string());
 		};
 	}
 
@@ -499,4 +498,16 @@ this.onTap = null"
 			""
 		);
 	}
-}
\ No newline at end of file
+
+	#[test]
+	fn parameter_list_splitted_7() {
+		assert_eq!(
+			split_parameter_list(
+				r"(int, String) pair, Map<String, List<int>> m, {String? name}"),
+			(
+				vec![String::from("(int, String) pair"), String::from("Map<String, List<int>> m")],
+				vec![String::from("String? name")]
+			)
+		);
+	}
+}
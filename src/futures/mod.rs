@@ -0,0 +1,319 @@
+use regex::Captures;
+use regex::Regex;
+use std::collections::HashSet;
+use crate::utils;
+
+/// Transpile the futures into tasks, which must be run after the types are
+/// mapped, `Future` to `Task`, and before `new` is inserted.
+///
+/// - `Task<void>` becomes `Task`
+/// - the async lambdas `(x) async =>` become `async (x) =>`
+/// - `Future.value`, `Future.wait`, `Future.delayed`, `Future.error` and
+///   `Future(f)` become `Task.FromResult`, `Task.WhenAll`, `Task.Delay`,
+///   `Task.FromException` and `Task.Run`
+/// - `Duration(seconds: 1)` becomes `TimeSpan.FromSeconds(1)`
+/// - `Completer<T>` becomes `TaskCompletionSource<T>`, and the members of
+///   the completers, `future`, `complete` and so on, are mapped. The task
+///   returned from an async function is awaited
+pub fn transpile_futures(input: &str) -> String {
+	lazy_static! {
+		static ref VOID : Regex = Regex::new(r"\bTask<void>").unwrap();
+	}
+
+	let result = VOID.replace_all(input, "Task").to_string();
+	let result = transpile_async_lambdas(&result);
+	let result = transpile_future_constructors(&result);
+	let result = transpile_durations(&result);
+	transpile_completers(&result)
+}
+
+fn transpile_async_lambdas(input: &str) -> String {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"\)\s*async\s*(?P<body>=>|\{)").unwrap();
+		static ref BEFORE : Regex = Regex::new(r"(?:[(,=:?\[{]|=>|\breturn)\s*$").unwrap();
+	}
+
+	let mut text = input.to_string();
+	let mut pos = 0;
+	while let Some(cap) = RE.captures(&text[pos..]) {
+		let whole = cap.get(0).unwrap();
+		let close = pos + whole.start();
+		let end = pos + whole.end();
+		pos = end;
+		let open = match utils::matching_open(&text, close) {
+			Some(open) => open,
+			None => continue,
+		};
+		if !BEFORE.is_match(&text[..open]) {
+			continue;
+		}
+		let arrow = if &cap["body"] == "=>" { ") =>" } else { ") {" };
+		text.replace_range(close..end, arrow);
+		text.insert_str(open, "async ");
+		pos = close + "async ".len() + arrow.len();
+	}
+	text
+}
+
+fn transpile_future_constructors(input: &str) -> String {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"(?P<new>\bnew\s+)?\b(?:Future|Task)(?P<args><[\w\s,<>?.@()]*>)?(?:\.(?P<named>\w+))?\s*\(").unwrap();
+	}
+
+	let mut text = input.to_string();
+	let mut pos = 0;
+	while let Some(cap) = RE.captures(&text[pos..]) {
+		let whole = cap.get(0).unwrap();
+		let start = pos + whole.start();
+		let open = pos + whole.end() - 1;
+		pos = open + 1;
+		let close = match utils::matching_bracket(&text, open) {
+			Some(close) => close,
+			None => continue,
+		};
+		let type_args = cap.name("args").map(|args| args.as_str()).unwrap_or("");
+		let args = utils::split_top_level(&text[open + 1..close], ',').iter()
+			.map(|arg| arg.trim().to_string())
+			.filter(|arg| !arg.is_empty())
+			.collect::<Vec<String>>();
+		let replacement = match (cap.name("named").map(|named| named.as_str()), args.len()) {
+			(Some("value"), 0) => String::from("Task.CompletedTask"),
+			(Some("value"), _) => format!("Task.FromResult{}({})", type_args, args[0]),
+			(Some("wait"), 1) => format!("Task.WhenAll({})", task_array(&args[0])),
+			(Some("wait"), _) => format!("Task.WhenAll({})", args.join(", ")),
+			(Some("delayed"), 1) => format!("Task.Delay({})", args[0]),
+			(Some("delayed"), _) => format!("Task.Delay({}).ContinueWith({})", args[0], continuation(&args[1])),
+			(Some("error"), _) => format!("Task.FromException{}({})", type_args, args[0]),
+			(Some("sync"), _) | (Some("microtask"), _) | (None, _) => format!("Task.Run({})", args.join(", ")),
+			_ => continue,
+		};
+		text.replace_range(start..close + 1, &replacement);
+		pos = start + replacement.len();
+	}
+	text
+}
+
+/// The list literal of tasks passed to `Future.wait` as an array, which
+/// `Task.WhenAll` takes, since a list would be typed as a list of objects
+fn task_array(list: &str) -> String {
+	lazy_static! {
+		static ref LITERAL : Regex = Regex::new(r"^(?:<(?P<args>[\w\s,<>?.@()]*)>\s*)?\[").unwrap();
+	}
+
+	let cap = match LITERAL.captures(list) {
+		Some(cap) if list.ends_with(']') => cap,
+		_ => return list.to_string(),
+	};
+	let elements = list[cap.get(0).unwrap().end()..list.len() - 1].trim();
+	match (cap.name("args").map(|args| args.as_str().trim()), elements.is_empty()) {
+		(Some(args), true) => format!("Array.Empty<{}>()", args),
+		(None, true) => String::from("Array.Empty<Task>()"),
+		(Some(args), false) => format!("new {}[] {{ {} }}", args, elements),
+		(None, false) => format!("new[] {{ {} }}", elements),
+	}
+}
+
+/// The callback of `Future.delayed` as the continuation of the delay task,
+/// which takes the task as its parameter
+fn continuation(callback: &str) -> String {
+	lazy_static! {
+		static ref LAMBDA : Regex = Regex::new(r"^\(\s*\)\s*(?P<body>=>|\{)").unwrap();
+	}
+
+	match LAMBDA.captures(callback) {
+		Some(cap) => format!("_ {}{}", if &cap["body"] == "=>" { "=>" } else { "=> {" }, &callback[cap.get(0).unwrap().end()..]),
+		None => format!("_ => {}()", callback),
+	}
+}
+
+fn transpile_durations(input: &str) -> String {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"(?P<new>\bnew\s+)?\bDuration\s*\(").unwrap();
+		static ref TYPE : Regex = Regex::new(r"(?P<pre>\.\s*)?\bDuration\b").unwrap();
+		static ref ARG : Regex = Regex::new(r"^(?P<unit>days|hours|minutes|seconds|milliseconds|microseconds)\s*:\s*(?P<value>[\s\S]+)$").unwrap();
+	}
+
+	let mut text = input.to_string();
+	let mut pos = 0;
+	while let Some(mat) = RE.find_at(&text, pos) {
+		let start = mat.start();
+		let open = mat.end() - 1;
+		pos = open + 1;
+		let close = match utils::matching_bracket(&text, open) {
+			Some(close) => close,
+			None => continue,
+		};
+		let args = utils::split_top_level(&text[open + 1..close], ',').iter()
+			.filter(|arg| !arg.trim().is_empty())
+			.map(|arg| ARG.captures(arg.trim()).map(|cap| (cap["unit"].to_string(), cap["value"].trim().to_string())))
+			.collect::<Option<Vec<(String, String)>>>();
+		let args = match args {
+			Some(args) => args,
+			None => continue,
+		};
+		let replacement = match args.len() {
+			0 => String::from("TimeSpan.Zero"),
+			1 => {
+				let (unit, value) = &args[0];
+				let unit = match unit.as_str() {
+					"microseconds" => "Microseconds",
+					_ => &unit[..],
+				};
+				let mut chars = unit.chars();
+				let first = chars.next().unwrap().to_uppercase().collect::<String>();
+				format!("TimeSpan.From{}{}({})", first, chars.as_str(), value)
+			},
+			_ => args.iter()
+				.map(|(unit, value)| {
+					let mut chars = unit.chars();
+					let first = chars.next().unwrap().to_uppercase().collect::<String>();
+					format!("TimeSpan.From{}{}({})", first, chars.as_str(), value)
+				})
+				.collect::<Vec<String>>()
+				.join(" + "),
+		};
+		let replacement = if args.len() > 1 { format!("({})", replacement) } else { replacement };
+		text.replace_range(start..close + 1, &replacement);
+		pos = start + replacement.len();
+	}
+
+	TYPE.replace_all(&text, |cap: &Captures| -> String {
+		match cap.name("pre") {
+			Some(_) => cap.get(0).unwrap().as_str().to_string(),
+			None => String::from("TimeSpan"),
+		}
+	}).to_string()
+}
+
+fn transpile_completers(input: &str) -> String {
+	lazy_static! {
+		static ref TYPE : Regex = Regex::new(r"(?P<pre>\.\s*)?\bCompleter(?P<args><[\w\s,<>?.@()]*>)?").unwrap();
+		static ref DECLARED : Regex = Regex::new(r"\bTaskCompletionSource\b(?:<[\w\s,<>?.@()]*>)?\??\s+(?P<name>[\w@]+)|(?P<assigned>[\w@]+)\s*=\s*(?:new\s+)?TaskCompletionSource\b").unwrap();
+	}
+
+	let text = TYPE.replace_all(input, |cap: &Captures| -> String {
+		if cap.name("pre").is_some() {
+			return cap.get(0).unwrap().as_str().to_string();
+		}
+		match cap.name("args").map(|args| args.as_str()) {
+			None | Some("<void>") => String::from("TaskCompletionSource"),
+			Some(args) => format!("TaskCompletionSource{}", args),
+		}
+	}).to_string();
+
+	let completers = DECLARED.captures_iter(&text)
+		.filter_map(|cap| cap.name("name").or_else(|| cap.name("assigned")).map(|name| name.as_str().to_string()))
+		.collect::<HashSet<String>>();
+	if completers.is_empty() {
+		return text;
+	}
+	let names = completers.iter().map(|name| regex::escape(name)).collect::<Vec<String>>().join("|");
+	let member = Regex::new(&format!(r"\b(?P<name>{})(?P<dot>\s*\??\.\s*)(?P<member>future|complete|completeError|isCompleted)\b", names)).unwrap();
+	member.replace_all(&text, |cap: &Captures| -> String {
+		let member = match &cap["member"] {
+			"future" => "Task",
+			"complete" => "SetResult",
+			"completeError" => "SetException",
+			_ => "Task.IsCompleted",
+		};
+		let start = cap.get(0).unwrap().start();
+		let end = cap.get(0).unwrap().end();
+		// An async function returns the result of the task, not the task
+		let is_returned = &cap["member"] == "future" && text[end..].trim_start().starts_with(';')
+			&& returns_from_async(&text, start);
+		format!("{}{}{}{}", if is_returned { "await " } else { "" }, &cap["name"], &cap["dot"], member)
+	}).to_string()
+}
+
+/// Whether the expression at the position is returned from an async
+/// function, by `return` or by the arrow body
+fn returns_from_async(text: &str, pos: usize) -> bool {
+	lazy_static! {
+		static ref ASYNC : Regex = Regex::new(r"\basync\b").unwrap();
+		static ref CONTROL : Regex = Regex::new(r"^(?:if|else|for|foreach|while|do|switch|try|catch|finally|lock|using)\b").unwrap();
+	}
+
+	let before = text[..pos].trim_end();
+	let head = |end: usize| {
+		let start = text[..end].rfind([';', '{', '}']).map(|i| i + 1).unwrap_or(0);
+		text[start..end].trim_start()
+	};
+	if before.ends_with("=>") {
+		return ASYNC.is_match(head(before.len()));
+	}
+	if !before.ends_with("return") {
+		return false;
+	}
+	let mut pos = pos;
+	while let Some(open) = utils::enclosing_open(text, pos) {
+		pos = open;
+		if text[open..].starts_with('{') && !CONTROL.is_match(head(open)) {
+			return ASYNC.is_match(head(open));
+		}
+	}
+	false
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn futures_transpiled() {
+		assert_eq!(
+			transpile_futures(r"
+  public async Task<void> f() {
+    await Future.delayed(new Duration(milliseconds: 100));
+    var all = await Future.wait(tasks);
+    var both = await Future.wait([load(), load()]);
+    list.forEach((e) async {
+      await g(e);
+    });
+    return Future<int>.value(1);
+  }"),
+			r"
+  public async Task f() {
+    await Task.Delay(TimeSpan.FromMilliseconds(100));
+    var all = await Task.WhenAll(tasks);
+    var both = await Task.WhenAll(new[] { load(), load() });
+    list.forEach(async (e) {
+      await g(e);
+    });
+    return Task.FromResult<int>(1);
+  }"
+		);
+	}
+
+	#[test]
+	fn completers_transpiled() {
+		assert_eq!(
+			transpile_futures(r"
+  readonly Completer<void> _done = Completer<void>();
+  Task<int> f() {
+    var c = Completer<int>();
+    timer(() => c.complete(1), () => c.completeError(e));
+    if (_done.isCompleted) return c.future;
+  }
+  async Task<int> g() {
+    if (a) {
+      return c.future;
+    }
+  }
+  async Task<int> h() => c.future;"),
+			r"
+  readonly TaskCompletionSource _done = TaskCompletionSource();
+  Task<int> f() {
+    var c = TaskCompletionSource<int>();
+    timer(() => c.SetResult(1), () => c.SetException(e));
+    if (_done.Task.IsCompleted) return c.Task;
+  }
+  async Task<int> g() {
+    if (a) {
+      return await c.Task;
+    }
+  }
+  async Task<int> h() => await c.Task;"
+		);
+	}
+}
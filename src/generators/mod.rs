@@ -0,0 +1,155 @@
+use regex::Regex;
+use crate::diagnostics;
+use crate::diagnostics::DiagnosticKind;
+use crate::utils;
+
+/// Transpile the generator functions into C# iterators, which must be run
+/// before the function headers, since `transpile_func_head` only knows the
+/// `async` modifier.
+///
+/// - `sync*` is removed, the `Iterable<T>` return type being mapped
+///   to `IEnumerable<T>` later
+/// - `async*` becomes `async`, the `Stream<T>` return type being mapped
+///   to `IAsyncEnumerable<T>` later
+/// - `yield x;` becomes `yield return x;`
+/// - `yield* xs;` becomes `foreach (var _itemN in xs) yield return _itemN;`,
+///   or `await foreach` in an `async*` function
+///
+/// C# has no iterator lambdas, so the generator lambdas are reported.
+pub fn transpile_generators(input: &str) -> String {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"(?P<head>\)|\bget\s+[\w@]+)\s*\b(?P<kind>sync|async)\s*\*\s*\{").unwrap();
+		static ref BEFORE : Regex = Regex::new(r"(?:[(,=:?\[{]|=>|\breturn)\s*$").unwrap();
+	}
+
+	let mut text = input.to_string();
+	let mut temps = 0;
+	let headers = RE.captures_iter(input)
+		.map(|cap| (cap.name("head").unwrap(), cap.get(0).unwrap().end() - 1, &cap["kind"] == "async"))
+		.map(|(head, open, is_async)| (head.start(), head.end(), open, is_async))
+		.collect::<Vec<(usize, usize, usize, bool)>>();
+	for (head_start, head_end, open, is_async) in headers.into_iter().rev() {
+		let close = match utils::matching_bracket(&text, open) {
+			Some(close) => close,
+			None => continue,
+		};
+		let body = transpile_yields(&text[open..=close], is_async, &mut temps);
+		text.replace_range(open..=close, &body);
+		text.replace_range(head_end..open, if is_async { " async " } else { " " });
+		let is_lambda = &text[head_start..head_end] == ")" && utils::matching_open(&text, head_start)
+			.map(|params| BEFORE.is_match(&text[..params]))
+			.unwrap_or(false);
+		if is_lambda {
+			let params = utils::matching_open(&text, head_start).unwrap();
+			text.insert(lambda_name(&text, params).unwrap_or(params), diagnostics::marker(DiagnosticKind::IteratorLambda));
+		}
+	}
+	text
+}
+
+/// The start of the name the lambda whose parameters start at `params` is
+/// given to: the variable or the named argument it is assigned to, or the
+/// function it is passed to
+fn lambda_name(text: &str, params: usize) -> Option<usize> {
+	lazy_static! {
+		static ref ASSIGNED : Regex = Regex::new(r"(?P<name>[\w@]+)\s*[=:]\s*$").unwrap();
+		static ref CALLED : Regex = Regex::new(r"(?P<name>[\w@]+)\s*(?:<[\w\s,<>?.@]*>)?\s*$").unwrap();
+	}
+
+	if let Some(cap) = ASSIGNED.captures(&text[..params]) {
+		return Some(cap.name("name").unwrap().start());
+	}
+	let open = utils::enclosing_open(text, params)?;
+	if !text[open..].starts_with('(') {
+		return None;
+	}
+	CALLED.captures(&text[..open]).map(|cap| cap.name("name").unwrap().start())
+}
+
+/// Transpile the `yield` statements of the body of a generator, whose
+/// nested generators are already transpiled
+fn transpile_yields(body: &str, is_async: bool, temps: &mut usize) -> String {
+	lazy_static! {
+		static ref YIELD : Regex = Regex::new(r"\byield\b(?P<star>\s*\*)?(?P<return>\s+return\b)?").unwrap();
+	}
+
+	let mut text = body.to_string();
+	let mut pos = 0;
+	while let Some(cap) = YIELD.captures(&text[pos..]) {
+		let whole = cap.get(0).unwrap();
+		let start = pos + whole.start();
+		let end = pos + whole.end();
+		if cap.name("return").is_some() {
+			pos = end;
+			continue;
+		}
+		if cap.name("star").is_none() {
+			text.replace_range(start..end, "yield return");
+			pos = start + "yield return".len();
+			continue;
+		}
+		let value_end = utils::expression_end(&text, end);
+		let item = format!("_item{}", temps);
+		*temps += 1;
+		let statement = format!("{}foreach (var {} in {}) yield return {}",
+			if is_async { "await " } else { "" }, item, text[end..value_end].trim(), item);
+		text.replace_range(start..value_end, &statement);
+		pos = start + statement.len();
+	}
+	text
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn sync_generators_transpiled() {
+		let (output, diagnostics) = diagnostics::extract(&transpile_generators(r"
+Iterable<int> range(int n) sync* {
+  for (var i = 0; i < n; i++) yield i;
+  yield* range(n - 1);
+}
+Iterable<Node> get children sync* {
+  yield* _children.where((c) => c.visible);
+}
+var evens = () sync* { yield 2; };
+print(list.expand((x) sync* { yield x; }));"));
+		assert_eq!(
+			output,
+			r"
+Iterable<int> range(int n) {
+  for (var i = 0; i < n; i++) yield return i;
+  foreach (var _item1 in range(n - 1)) yield return _item1;
+}
+Iterable<Node> get children {
+  foreach (var _item0 in _children.where((c) => c.visible)) yield return _item0;
+}
+var evens = () { yield return 2; };
+print(list.expand((x) { yield return x; }));"
+		);
+		assert_eq!(diagnostics.iter().map(|d| (d.kind, d.line, d.subject.as_str())).collect::<Vec<_>>(),
+			vec![(DiagnosticKind::IteratorLambda, 9, "evens"), (DiagnosticKind::IteratorLambda, 10, "expand")]);
+	}
+
+	#[test]
+	fn async_generators_transpiled() {
+		assert_eq!(
+			transpile_generators(r"
+Stream<String> lines(Stream<List<int>> source) async* {
+  await for (final chunk in source) {
+    yield utf8.decode(chunk);
+  }
+  yield* footer();
+}"),
+			r"
+Stream<String> lines(Stream<List<int>> source) async {
+  await for (final chunk in source) {
+    yield return utf8.decode(chunk);
+  }
+  await foreach (var _item0 in footer()) yield return _item0;
+}"
+		);
+	}
+}
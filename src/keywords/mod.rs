@@ -0,0 +1,398 @@
+use regex::Captures;
+use regex::Regex;
+use std::collections::HashSet;
+
+lazy_static! {
+	/// Reserved words of dart, which can never be identifiers
+	static ref DART_RESERVED : HashSet<&'static str> = [
+		"assert", "break", "case", "catch", "class", "const", "continue",
+		"default", "do", "else", "enum", "extends", "false", "final",
+		"finally", "for", "if", "in", "is", "new", "null", "rethrow",
+		"return", "super", "switch", "this", "throw", "true", "try", "var",
+		"void", "while", "with",
+	].iter().cloned().collect();
+
+	/// Built-in identifiers of dart, which are keywords in some places
+	/// but may also be used as identifiers, though not as type names
+	static ref DART_BUILT_IN : HashSet<&'static str> = [
+		"abstract", "as", "covariant", "deferred", "dynamic", "export",
+		"extension", "external", "factory", "Function", "get", "implements",
+		"import", "interface", "late", "library", "mixin", "operator", "part",
+		"required", "set", "static", "typedef",
+	].iter().cloned().collect();
+
+	/// Contextual keywords of dart, which are keywords only in some
+	/// constructs, like `async` after a parameter list or `on` in a try
+	/// statement, and normal identifiers elsewhere
+	static ref DART_CONTEXTUAL : HashSet<&'static str> = [
+		"async", "await", "base", "hide", "of", "on", "sealed", "show",
+		"sync", "when", "yield",
+	].iter().cloned().collect();
+
+	/// Reserved keywords of C#, which can only be used as identifiers
+	/// with the `@` prefix
+	static ref CSHARP_RESERVED : HashSet<&'static str> = [
+		"abstract", "as", "base", "bool", "break", "byte", "case", "catch",
+		"char", "checked", "class", "const", "continue", "decimal", "default",
+		"delegate", "do", "double", "else", "enum", "event", "explicit",
+		"extern", "false", "finally", "fixed", "float", "for", "foreach",
+		"goto", "if", "implicit", "in", "int", "interface", "internal", "is",
+		"lock", "long", "namespace", "new", "null", "object", "operator",
+		"out", "override", "params", "private", "protected", "public",
+		"readonly", "ref", "return", "sbyte", "sealed", "short", "sizeof",
+		"stackalloc", "static", "string", "struct", "switch", "this", "throw",
+		"true", "try", "typeof", "uint", "ulong", "unchecked", "unsafe",
+		"ushort", "using", "virtual", "void", "volatile", "while",
+	].iter().cloned().collect();
+
+	/// Contextual keywords of C# which may be taken as keywords where the
+	/// transpiled code uses identifiers, i.e. in declarations and
+	/// expressions. The others, like `get`, `value`, `where` or `add`, are
+	/// only keywords in accessors, query expressions, patterns and other
+	/// constructs where no dart identifier ends up.
+	static ref CSHARP_CONTEXTUAL : HashSet<&'static str> = [
+		"async", "await", "dynamic", "file", "managed", "nameof", "nint",
+		"notnull", "nuint", "record", "required", "scoped", "unmanaged",
+		"var", "yield",
+	].iter().cloned().collect();
+}
+
+/// Whether the word is a reserved word of dart
+pub fn is_dart_reserved_word(word: &str) -> bool {
+	DART_RESERVED.contains(word)
+}
+
+/// Whether the word is a built-in identifier of dart
+pub fn is_dart_built_in_identifier(word: &str) -> bool {
+	DART_BUILT_IN.contains(word)
+}
+
+/// Whether the word is a contextual keyword of dart
+pub fn is_dart_contextual_keyword(word: &str) -> bool {
+	DART_CONTEXTUAL.contains(word)
+}
+
+/// Whether the word is any kind of dart keyword: reserved word,
+/// built-in identifier or contextual keyword. A word for which this
+/// is true may still be an identifier, unless it is a reserved word.
+pub fn is_dart_keyword(word: &str) -> bool {
+	is_dart_reserved_word(word) || is_dart_built_in_identifier(word) || is_dart_contextual_keyword(word)
+}
+
+/// Whether the keyword may precede the name in a declaration of
+/// variable, field, parameter or function, like `final` or `void`.
+/// Other keywords before a name, like `return` or `await`, start
+/// a statement or an expression instead.
+pub fn is_declaring_keyword(word: &str) -> bool {
+	matches!(word, "var" | "final" | "const" | "void" | "late" | "dynamic" | "covariant" | "static" | "external")
+}
+
+/// Whether the word is a reserved keyword of C#
+pub fn is_csharp_keyword(word: &str) -> bool {
+	CSHARP_RESERVED.contains(word)
+}
+
+/// Whether the word is a contextual keyword of C#
+pub fn is_csharp_contextual_keyword(word: &str) -> bool {
+	CSHARP_CONTEXTUAL.contains(word)
+}
+
+/// Prefix the identifier with `@` if it is a C# keyword,
+/// reserved or contextual
+pub fn escape_csharp_keyword(word: &str) -> String {
+	if is_csharp_keyword(word) || is_csharp_contextual_keyword(word) {
+		format!("@{}", word)
+	} else {
+		String::from(word)
+	}
+}
+
+/// Whether the word starts a statement or an expression, so that a name
+/// after it cannot be declared, like `return`, `else`, `await` or `yield`
+pub fn starts_statement(word: &str) -> bool {
+	(is_dart_reserved_word(word) && !is_declaring_keyword(word))
+		|| (is_dart_contextual_keyword(word) && word != "base" && word != "sealed")
+}
+
+/// Whether the identifier has to be escaped in C#. Dart reserved words
+/// are never identifiers, so they are kept even if they are also C#
+/// keywords, like `null` or `this`.
+fn needs_escape(word: &str) -> bool {
+	!is_dart_reserved_word(word) && (is_csharp_keyword(word) || is_csharp_contextual_keyword(word))
+}
+
+/// The dart types whose names are C# keywords. When they are used as
+/// identifiers, `double.parse` is still taken for a static member of
+/// the type rather than a member of the variable.
+fn is_type_keyword(word: &str) -> bool {
+	matches!(word, "bool" | "double" | "dynamic" | "int")
+}
+
+/// Escape the identifiers colliding with C# keywords with `@`.
+///
+/// Identifiers are recognized in three places:
+/// - declarations of variables, fields, parameters and functions, i.e.
+///   a name after a type and before `;`, `,`, `=`, `)`, `}` or `(`
+/// - member accesses, i.e. a name after `.` or `?.`, and their receivers,
+///   i.e. a name before `.`, unless it is a type like `double`
+/// - any other appearance of a name declared in this file, unless it
+///   is in a type position: before a name, after `as` or `is`, or in
+///   type arguments
+///
+/// Enum members are escaped by the enum pass.
+pub fn escape_identifiers(input: &str) -> String {
+	lazy_static! {
+		static ref DECLARATION : Regex = Regex::new(r"(?P<name>\w+)\s*[;,=)}(]").unwrap();
+		static ref MEMBER : Regex = Regex::new(r"\.(?P<name>\w+)\b").unwrap();
+		static ref WORD : Regex = Regex::new(r"(?P<prefix>[.@]?)\b(?P<name>\w+)\b").unwrap();
+		static ref TYPE_USE : Regex = Regex::new(r"^(?:\??\s+\w|\s*<)").unwrap();
+		static ref TYPE_OPERATOR : Regex = Regex::new(r"\b(?:as|is!?)\s+$").unwrap();
+	}
+
+	let mut declared = HashSet::new();
+	for cap in DECLARATION.captures_iter(input) {
+		let name = cap.name("name").unwrap();
+		if needs_escape(name.as_str()) && is_declared_at(input, name.start())
+			&& !is_operator_declaration(input, name.start()) {
+			declared.insert(name.as_str().to_string());
+		}
+	}
+
+	let result = MEMBER.replace_all(input, |cap: &Captures| -> String {
+		let name = cap.name("name").unwrap().as_str();
+		if needs_escape(name) {
+			format!(".@{}", name)
+		} else {
+			cap.get(0).unwrap().as_str().to_string()
+		}
+	});
+
+	WORD.replace_all(&result, |cap: &Captures| -> String {
+		let whole = cap.get(0).unwrap();
+		let name = cap.name("name").unwrap();
+		let after = &result[name.end()..];
+		if !cap.name("prefix").unwrap().as_str().is_empty() || is_operator_declaration(&result, name.start()) {
+			return whole.as_str().to_string();
+		}
+		if !declared.contains(name.as_str()) {
+			return if needs_escape(name.as_str()) && (after.starts_with('.') || after.starts_with("?.")) && !is_type_keyword(name.as_str()) {
+				format!("@{}", name.as_str())
+			} else {
+				whole.as_str().to_string()
+			};
+		}
+		let is_type = TYPE_USE.is_match(after)
+			|| TYPE_OPERATOR.is_match(&result[..name.start()])
+			|| in_type_arguments(&result, name.start())
+			|| is_type_keyword(name.as_str()) && after.starts_with('.');
+		if is_declared_at(&result, name.start()) || !is_type {
+			format!("@{}", name.as_str())
+		} else {
+			whole.as_str().to_string()
+		}
+	}).to_string()
+}
+
+/// Whether the position is in the type arguments `<...>` of a generic type,
+/// whose `<` follows the name without spaces, unlike a comparison, or of
+/// a collection literal, whose `<` follows no operand
+fn in_type_arguments(text: &str, pos: usize) -> bool {
+	let is_operand_end = |c: char| c.is_alphanumeric() || c == '_' || c == ')' || c == ']';
+	let mut depth = 0;
+	for (i, c) in text[..pos].char_indices().rev() {
+		match c {
+			'>' if text[..i].ends_with('=') => {},
+			')' | ']' | '}' | '>' => depth += 1,
+			'(' | '[' | '{' if depth == 0 => return false,
+			'<' if depth == 0 => return text[..i].ends_with(is_operand_end) || !text[..i].trim_end().ends_with(is_operand_end),
+			'(' | '[' | '{' | '<' => depth -= 1,
+			';' => return false,
+			_ => {}
+		}
+	}
+	false
+}
+
+/// Whether the word starting at `start` is the `operator` of a user-defined
+/// operator, like `operator ==(` or `operator []=(`, which is not a name
+fn is_operator_declaration(text: &str, start: usize) -> bool {
+	lazy_static! {
+		static ref OPERATOR : Regex = Regex::new(r"^operator\s*[-+*/%~^&|<>=\[]").unwrap();
+	}
+
+	OPERATOR.is_match(&text[start..])
+}
+
+/// Whether the name starting at `start` is declared there, i.e. it follows
+/// a type or a declaring keyword like `final`, and is followed by `;`, `,`,
+/// `=`, `)`, `}` or `(`. Keywords other than the declaring ones are never
+/// type names, so `as double)` or `return object;` are not declarations.
+/// The type operators before a record type, like `x as (int, int)`, are
+/// not declared either.
+fn is_declared_at(text: &str, start: usize) -> bool {
+	lazy_static! {
+		static ref NAME_END : Regex = Regex::new(r"^(?P<name>\w+)\s*(?P<end>[;,=)}(])").unwrap();
+		static ref TYPE_END : Regex = Regex::new(r"(?:[^=]>|[?\]]|\b(?P<word>\w+))\s+$").unwrap();
+	}
+
+	match NAME_END.captures(&text[start..]) {
+		Some(cap) if &cap["end"] == "(" && matches!(&cap["name"], "as" | "is") => return false,
+		Some(_) => {},
+		None => return false,
+	}
+	match TYPE_END.captures(&text[..start]) {
+		Some(cap) => match cap.name("word") {
+			Some(word) => {
+				let word = word.as_str();
+				!is_dart_keyword(word) || is_declaring_keyword(word)
+			},
+			None => true,
+		},
+		None => false,
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn is_keyword_test() {
+		assert!(is_dart_keyword("if"));
+		assert!(is_dart_keyword("while"));
+		assert!(is_dart_keyword("for"));
+		assert!(is_dart_keyword("final"));
+		assert!(is_dart_keyword("class"));
+	}
+
+	#[test]
+	fn not_keyword_test() {
+		assert!(!is_dart_keyword("function"));
+		assert!(!is_dart_keyword("def"));
+		assert!(!is_dart_keyword("list"));
+		assert!(!is_dart_reserved_word("set"));
+		assert!(!is_dart_keyword("i f"));
+	}
+
+	#[test]
+	fn dart_keyword_kinds() {
+		assert!(is_dart_reserved_word("switch"));
+		assert!(is_dart_reserved_word("rethrow"));
+		assert!(!is_dart_reserved_word("async"));
+		assert!(is_dart_built_in_identifier("late"));
+		assert!(is_dart_built_in_identifier("required"));
+		assert!(is_dart_built_in_identifier("get"));
+		assert!(is_dart_contextual_keyword("await"));
+		assert!(is_dart_contextual_keyword("yield"));
+		for word in ["catch", "return", "late", "set", "async", "sync", "on"].iter() {
+			assert!(is_dart_keyword(word));
+		}
+		assert!(!is_dart_keyword("double"));
+		assert!(!is_dart_keyword("String"));
+	}
+
+	#[test]
+	fn keyword_tables() {
+		assert!(is_csharp_keyword("lock"));
+		assert!(!is_csharp_keyword("var"));
+		assert!(is_csharp_contextual_keyword("var"));
+		assert!(!is_csharp_contextual_keyword("where"));
+		assert_eq!(escape_csharp_keyword("double"), "@double");
+		assert_eq!(escape_csharp_keyword("await"), "@await");
+		assert_eq!(escape_csharp_keyword("dashed"), "dashed");
+		assert_eq!(escape_csharp_keyword("Double"), "Double");
+	}
+
+	#[test]
+	fn escape_declarations() {
+		assert_eq!(
+			escape_identifiers(r"
+void lock(Object object, {String event, List<int> params}) {
+  final base = object;
+  String string;
+  return null;
+}"),
+			r"
+void @lock(Object @object, {String @event, List<int> @params}) {
+  final @base = @object;
+  String @string;
+  return null;
+}"
+		);
+	}
+
+	#[test]
+	fn escape_uses() {
+		assert_eq!(
+			escape_identifiers(r"
+void f(bool out, int double) {
+  if (out) print(params.lock + this.base.string);
+  lock(out, double, 1.0);
+  double x = double + 1;
+  Map<String, double?> m = <double, int>{double: 1};
+  if (x is double) x = double.parse(s) as double;
+  var l = fixed?.length;
+  return out;
+}"),
+			r"
+void f(bool @out, int @double) {
+  if (@out) print(@params.@lock + this.@base.@string);
+  lock(@out, @double, 1.0);
+  double x = @double + 1;
+  Map<String, double?> m = <double, int>{@double: 1};
+  if (x is double) x = double.parse(s) as double;
+  var l = @fixed?.length;
+  return @out;
+}"
+		);
+	}
+
+	#[test]
+	fn escape_contextual_identifiers() {
+		assert_eq!(
+			escape_identifiers(r"
+void f(int await, int yield) {
+  var async = await + yield;
+  await g(async);
+  yield x;
+}"),
+			r"
+void f(int @await, int @yield) {
+  var @async = @await + @yield;
+  await g(@async);
+  yield x;
+}"
+		);
+	}
+
+	#[test]
+	fn escape_not_after_keywords() {
+		assert_eq!(
+			escape_identifiers(r"
+f(a) => (a as double) + (a is int ? 1 : 0);
+g(o) => object;
+h(o) => o is (int, int) ? o as (int, int) : null;"),
+			r"
+f(a) => (a as double) + (a is int ? 1 : 0);
+g(o) => object;
+h(o) => o is (int, int) ? o as (int, int) : null;"
+		);
+	}
+
+	#[test]
+	fn keep_keywords() {
+		assert_eq!(
+			escape_identifiers(r"
+Future<int> f() async {
+  var x = await g();
+  return x is int ? x : null;
+}"),
+			r"
+Future<int> f() async {
+  var x = await g();
+  return x is int ? x : null;
+}"
+		);
+	}
+}
@@ -4,23 +4,89 @@ use std::io::Read;
 use std::fs::File;
 use std::io;
 
+mod cascades;
+mod casts;
 mod cleanup;
+mod collections;
+mod config;
+mod ctors;
+mod decls;
+mod diagnostics;
+mod docs;
+mod enums;
+mod exceptions;
+mod generators;
+mod keywords;
+mod loops;
+/// Comments and string literals must not be touched by the rewriting
+/// passes, which know nothing about the dart lexical structure. So they
+/// are replaced by placeholders before the passes run, and put back after.
+mod mask;
 mod funcs;
+mod futures;
+mod streams;
+mod strings;
+mod switches;
+mod nullable;
+mod nullaware;
+mod operators;
+mod patterns;
+mod records;
+mod types;
 mod utils;
-mod eregex;
-mod tokens;
+pub mod eregex;
+pub mod tokens;
 
-pub fn transpile_file<'a>(filename: &str) -> Result<String, io::Error> {
+pub use config::CollectionLowering;
+pub use config::Config;
+pub use diagnostics::Diagnostic;
+pub use diagnostics::DiagnosticKind;
+
+pub fn transpile_file(filename: &str) -> Result<String, io::Error> {
 	let mut file = File::open(filename)?;
 	let mut contents = String::new();
 	file.read_to_string(&mut contents)?;
 	Ok(transpile(&contents))
 }
 
-pub fn transpile<'a>(input: &'a str) -> String {
-	let result = cleanup::remove_import(input).to_string();
+pub fn transpile(input: &str) -> String {
+	transpile_with_config(input, &Config::default())
+}
+
+pub fn transpile_with_config(input: &str, config: &Config) -> String {
+	transpile_with_diagnostics(input, config).0
+}
+
+/// Transpile the input, and report the places of the output
+/// which may need a manual fix
+pub fn transpile_with_diagnostics(input: &str, config: &Config) -> (String, Vec<Diagnostic>) {
+	let mut masked = mask::mask(input);
+	docs::transpile_doc_comments(&mut masked);
+	strings::transpile_strings(&mut masked);
+	let result = cleanup::remove_import(&masked.text).to_string();
+	let result = enums::transpile_enums(&result);
+	let result = generators::transpile_generators(&result);
 	let result = funcs::transpile_func_head(&result).to_string();
-	return result;
+	let result = keywords::escape_identifiers(&result);
+	let result = records::transpile_records(&result, config);
+	let result = types::transpile_types(&result, config);
+	let result = nullable::transpile_nullable(&result, config);
+	let result = patterns::transpile_patterns(&result);
+	let result = decls::transpile_declarations(&result);
+	let result = casts::transpile_casts(&result, config);
+	let result = operators::transpile_operators(&result, config);
+	let result = nullaware::transpile_null_aware(&result, config);
+	let result = cascades::transpile_cascades(&result);
+	let result = futures::transpile_futures(&result);
+	let result = streams::transpile_streams(&result);
+	let result = ctors::insert_new(&result, config);
+	let result = exceptions::transpile_exceptions(&result, config);
+	let result = collections::transpile_collections(&result, config);
+	let result = loops::transpile_loops(&result);
+	let result = switches::transpile_switches(&result);
+	let result = streams::append_support_classes(&result, config);
+	let result = strings::parenthesize_interpolations(&masked, &result);
+	diagnostics::extract(&masked.unmask(&result))
 }
 
 #[cfg(test)]
@@ -38,11 +104,107 @@ NodeMetadata lazySet(
   BuildOp buildOp,
   Iterable<String> stylesPrepend,
 })"),
-			r"NodeMetadata lazySet(
+			r"#nullable enable
+
+NodeMetadata lazySet(
   NodeMetadata meta, {
   BuildOp buildOp,
-  Iterable<String> stylesPrepend,
+  IEnumerable<string> stylesPrepend,
 })"
 		);
 	}
-}
\ No newline at end of file
+
+	#[test]
+	fn test_transpile_skips_comments_and_strings() {
+		assert_eq!(
+			transpile(r"import 'package:flutter/widgets.dart';
+
+// Called as lazySet(int a) {
+final s = '''
+import 'package:html/dom.dart' as dom;
+''';"),
+			r#"#nullable enable
+
+// Called as lazySet(int a) {
+var s = @"import 'package:html/dom.dart' as dom;
+";"#
+		);
+	}
+
+	#[test]
+	fn test_transpile_operators() {
+		assert_eq!(
+			transpile_with_config(r"class V {
+  final int x;
+  bool operator ==(Object other) => other is V && other.x == x;
+  bool operator <(V other) => x < other.x;
+  int operator [](int i) => x;
+}", &Config::default()),
+			r"#nullable enable
+
+class V {
+  readonly int x;
+  public override bool Equals(object? other) => other is V v && v.x == x;
+  public static bool operator ==(V? left, V? right) => Equals(left, right);
+  public static bool operator !=(V? left, V? right) => !Equals(left, right);
+  public bool LessThan(V other) => x < other.x;
+  public static bool operator <(V left, V other) => left.LessThan(other);
+  public static bool operator >(V left, V right) => right.LessThan(left);
+  public int this[int i] {
+    get => x;
+  }
+}"
+		);
+	}
+
+	#[test]
+	fn test_transpile_interpolations() {
+		assert_eq!(
+			transpile(r#"class Foo {}
+class Bar extends Foo {}
+var s = "${Foo() as Bar} ${a ~/ b} $a ${b > 0 ? 'x' : "y$a"}";"#),
+			r#"#nullable enable
+
+class Foo {}
+class Bar extends Foo {}
+var s = $"{(Bar)new Foo()} {(int)(a / b)} {a} {(b > 0 ? "x" : $"y{a}")}";"#
+		);
+	}
+	#[test]
+	fn test_transpile_streams() {
+		let (output, diagnostics) = transpile_with_diagnostics(r"final controller = StreamController<int>.broadcast();
+void start() {
+  controller.stream.listen((v) => print(v));
+}", &Config::default());
+		assert!(!output.contains("HashSet"));
+		assert!(output.contains("catch (OperationCanceledException)"));
+		assert_eq!(diagnostics.iter().map(|d| (d.kind, d.subject.as_str())).collect::<Vec<_>>(),
+			vec![(DiagnosticKind::BroadcastStream, "StreamController")]);
+	}
+	#[test]
+	fn test_transpile_switch_patterns() {
+		assert_eq!(
+			transpile(r"int first(List<int> xs) => switch (xs) { [] => 0, [var h, ...] => h };"),
+			r"#nullable enable
+
+public int first(List<int> xs) => xs switch { [] => 0, [var h, ..] => h };"
+		);
+	}
+	#[test]
+	fn test_transpile_records() {
+		let config = Config { record_structs: Some(2), ..Config::default() };
+		assert_eq!(
+			transpile_with_config(r"({int x, int y}) origin() => (x: 0, y: 0);
+({int x, int y}) swap(Object o) => o as ({int x, int y});
+(int, int) pair(Object o) => o as (int, int);", &config),
+			r"#nullable enable
+
+XYRecord origin() => new XYRecord(x: 0, y: 0);
+XYRecord swap(object o) => (XYRecord)o;
+(int, int) pair(object o) => ((int, int))o;
+
+public record struct XYRecord(int x, int y);
+"
+		);
+	}
+}
@@ -0,0 +1,270 @@
+use regex::Regex;
+use crate::utils;
+
+/// Transpile the `for-in` loops into `foreach` loops, and the labeled
+/// loops into `goto` statements
+pub fn transpile_loops(input: &str) -> String {
+	let result = transpile_for_in(input);
+	transpile_labels(&result)
+}
+
+/// `for (var x in xs)` becomes `foreach (var x in xs)`, and
+/// `await for (var e in stream)` becomes `await foreach (var e in stream)`.
+/// `final` is already turned into `var` or the type of the variable.
+///
+/// C# has no `foreach` over an existing variable, so `for (x in xs)`
+/// becomes `foreach (var _x in xs) { x = _x; ... }`.
+fn transpile_for_in(input: &str) -> String {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"\bfor(?P<space>\s*)\(").unwrap();
+		static ref HEADER : Regex = Regex::new(r"(?s)^\s*(?:var|final|[\w.@]+(?:<[\w\s,<>?.@]*>)?\??)\s+[\w@]+\s+in\s").unwrap();
+		static ref ASSIGNED : Regex = Regex::new(r"(?s)^(?P<space>\s*)(?P<name>[\w@]+)(?P<rest>\s+in\s.*)$").unwrap();
+	}
+
+	let mut text = input.to_string();
+	let starts = RE.captures_iter(input).map(|cap| (cap.get(0).unwrap().start(), cap.get(0).unwrap().end() - 1)).collect::<Vec<(usize, usize)>>();
+	for (start, open) in starts.into_iter().rev() {
+		let close = match utils::matching_bracket(&text, open) {
+			Some(close) => close,
+			None => continue,
+		};
+		let header = text[open + 1..close].to_string();
+		if HEADER.is_match(&header) {
+			text.replace_range(start..start + 3, "foreach");
+			continue;
+		}
+		let cap = match ASSIGNED.captures(&header) {
+			Some(cap) => cap,
+			None => continue,
+		};
+		let body_start = utils::skip_spaces(&text, close + 1);
+		let body_end = match utils::statement_end(&text, body_start) {
+			Some(end) => end,
+			None => continue,
+		};
+		let name = &cap["name"];
+		let temp = format!("_{}", name.trim_start_matches('@'));
+		let assignment = format!("{} = {};", name, temp);
+		let body = &text[body_start..body_end];
+		let body = if let Some(content) = body.strip_prefix('{') {
+			match content.find('\n') {
+				Some(newline) if content[..newline].trim().is_empty() => {
+					let indent = utils::line_indent(content, newline + 1);
+					format!("{{\n{}{}{}", indent, assignment, content)
+				},
+				_ if content.starts_with(char::is_whitespace) => format!("{{ {}{}", assignment, content),
+				_ => format!("{{ {} {}", assignment, content),
+			}
+		} else {
+			format!("{{ {} {} }}", assignment, body)
+		};
+		let header = format!("{}var {}{}", &cap["space"], temp, &cap["rest"]);
+		let replacement = format!("foreach{}({}){}{}", &text[start + 3..open], header, &text[close + 1..body_start], body);
+		text.replace_range(start..body_end, &replacement);
+	}
+	text
+}
+
+/// C# has no labeled `continue`, and `break` only leaves the innermost
+/// loop. So `continue label;` becomes `goto label_continue;` with the
+/// label at the end of the loop body, and `break label;` becomes
+/// `goto label_break;` with the label right after the loop. The labels
+/// are only put where they are used, and the body of the loop is wrapped
+/// in a block if it is a single statement.
+fn transpile_labels(input: &str) -> String {
+	lazy_static! {
+		static ref LABEL : Regex = Regex::new(r"(?m)^(?P<indent>[ \t]*)(?P<label>\w+):\s*(?P<statement>for|foreach|while|do|\{)").unwrap();
+	}
+
+	let mut text = input.to_string();
+	let mut from = 0;
+	while let Some(cap) = LABEL.captures(&text[from..]) {
+		let whole = cap.get(0).unwrap();
+		let label = cap.name("label").unwrap().as_str().to_string();
+		let indent = cap.name("indent").unwrap().as_str().to_string();
+		let label_start = from + cap.name("label").unwrap().start();
+		let statement = from + cap.name("statement").unwrap().start();
+		from += whole.end();
+		if label == "default" {
+			continue;
+		}
+		if let Some(wrapped) = wrap_body(&text, statement) {
+			text = wrapped;
+		}
+		let (body_open, body_close, end) = match loop_extent(&text, statement) {
+			Some(extent) => extent,
+			None => continue,
+		};
+
+		let body = &text[body_open..body_close];
+		let continue_re = Regex::new(&format!(r"\bcontinue\s+{}\s*;", label)).unwrap();
+		let break_re = Regex::new(&format!(r"\bbreak\s+{}\s*;", label)).unwrap();
+		let has_continue = continue_re.is_match(body);
+		let has_break = break_re.is_match(body);
+		let body = continue_re.replace_all(body, format!("goto {}_continue;", label).as_str());
+		let body = break_re.replace_all(&body, format!("goto {}_break;", label).as_str()).to_string();
+
+		let close_indent = utils::line_indent(&text, body_close);
+		let mut result = String::new();
+		result.push_str(&text[..label_start]);
+		result.push_str(&text[statement..body_open]);
+		result.push_str(&body);
+		if has_continue {
+			result.push_str(&format!("  {}_continue: ;\n{}", label, close_indent));
+		}
+		result.push_str(&text[body_close..end]);
+		if has_break {
+			result.push_str(&format!("\n{}{}_break: ;", indent, label));
+		}
+		result.push_str(&text[end..]);
+		text = result;
+		from = label_start;
+	}
+	text
+}
+
+/// The start of the body of the loop or block starting at `start`
+fn body_start(text: &str, start: usize) -> Option<usize> {
+	let rest = &text[start..];
+	if rest.starts_with('{') {
+		Some(start)
+	} else if rest.starts_with("do") {
+		Some(utils::skip_spaces(text, start + 2))
+	} else {
+		let open = start + rest.find('(')?;
+		Some(utils::skip_spaces(text, utils::matching_bracket(text, open)? + 1))
+	}
+}
+
+/// Wrap the body of the loop starting at `start` in a block, if it is a
+/// single statement, so that the labels can be put in it
+fn wrap_body(text: &str, start: usize) -> Option<String> {
+	let body_start = body_start(text, start)?;
+	if text[body_start..].starts_with('{') {
+		return None;
+	}
+	let body_end = utils::statement_end(text, body_start)?;
+	let indent = utils::line_indent(text, start);
+	Some(format!("{} {{\n{}  {}\n{}}}{}", text[..body_start].trim_end(), indent, &text[body_start..body_end], indent, &text[body_end..]))
+}
+
+/// Find the body of the loop or block starting at `start`: the position
+/// of `{`, the position of the matching `}`, and the end of the statement
+fn loop_extent(text: &str, start: usize) -> Option<(usize, usize, usize)> {
+	lazy_static! {
+		static ref DO_WHILE : Regex = Regex::new(r"^\s*while\s*\(").unwrap();
+	}
+
+	let body_open = body_start(text, start)?;
+	if !text[body_open..].starts_with('{') {
+		return None;
+	}
+	let body_close = utils::matching_bracket(text, body_open)?;
+	let mut end = body_close + 1;
+	if text[start..].starts_with("do") {
+		let cap = DO_WHILE.find(&text[end..])?;
+		let close = utils::matching_bracket(text, end + cap.end() - 1)?;
+		end = close + 1 + text[close + 1..].find(';')? + 1;
+	}
+	Some((body_open + 1, body_close, end))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn for_in_transpiled() {
+		assert_eq!(
+			transpile_loops(r"
+  for (var child in _children) {}
+  for (TextBit bit in bits) {}
+  await for (var e in stream) {}
+  for (var i = 0; i < n; i++) {}
+  for (x in xs) {}
+  for (x in xs) print(x);
+  for (x in xs) {
+    print(x);
+  }
+  for (y in ys) if (y > 0) { print(y); }
+  print(y);"),
+			r"
+  foreach (var child in _children) {}
+  foreach (TextBit bit in bits) {}
+  await foreach (var e in stream) {}
+  for (var i = 0; i < n; i++) {}
+  foreach (var _x in xs) { x = _x; }
+  foreach (var _x in xs) { x = _x; print(x); }
+  foreach (var _x in xs) {
+    x = _x;
+    print(x);
+  }
+  foreach (var _y in ys) { y = _y; if (y > 0) { print(y); } }
+  print(y);"
+		);
+	}
+
+	#[test]
+	fn labels_transpiled() {
+		assert_eq!(
+			transpile_loops(r"
+  outer:
+  for (var i = 0; i < n; i++) {
+    inner: for (var j in js) {
+      if (a) continue outer;
+      if (b) break outer;
+      if (c) continue inner;
+      break;
+    }
+  }
+  loop: do {
+    continue loop;
+  } while (d);
+  unused: while (e) {}
+  single: for (var k in ks) if (k) continue single;
+  switch (f) {
+    default: {}
+  }
+  nested: for (var a in as) for (var b in bs) { if (b) break nested; }
+  print(1);
+  lines:
+  for (var a in as)
+    for (var b in bs) { continue lines; }
+  print(2);"),
+			r"
+  for (var i = 0; i < n; i++) {
+    foreach (var j in js) {
+      if (a) goto outer_continue;
+      if (b) goto outer_break;
+      if (c) goto inner_continue;
+      break;
+      inner_continue: ;
+    }
+    outer_continue: ;
+  }
+  outer_break: ;
+  do {
+    goto loop_continue;
+    loop_continue: ;
+  } while (d);
+  while (e) {}
+  foreach (var k in ks) {
+    if (k) goto single_continue;
+    single_continue: ;
+  }
+  switch (f) {
+    default: {}
+  }
+  foreach (var a in as) {
+    foreach (var b in bs) { if (b) goto nested_break; }
+  }
+  nested_break: ;
+  print(1);
+  foreach (var a in as) {
+    foreach (var b in bs) { goto lines_continue; }
+    lines_continue: ;
+  }
+  print(2);"
+		);
+	}
+}
@@ -1,8 +1,13 @@
 use std::io::Write;
 use std::fs::File;
-use dart2csharp;
+use dart2csharp::Config;
 
 fn main() {
+	let input = std::fs::read_to_string("test.dart").unwrap();
+	let (output, diagnostics) = dart2csharp::transpile_with_diagnostics(&input, &Config::default());
 	let mut file = File::create("test.cs").unwrap();
-	file.write_all(dart2csharp::transpile_file("test.dart").unwrap().as_bytes()).unwrap();
+	file.write_all(output.as_bytes()).unwrap();
+	for diagnostic in diagnostics {
+		eprintln!("test.cs:{}", diagnostic);
+	}
 }
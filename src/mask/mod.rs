@@ -0,0 +1,443 @@
+/// The placeholders are taken from a private use plane of unicode, so they
+/// never appear in real source code, and they are neither word characters
+/// nor spaces for the regexes of the passes.
+const PLACEHOLDER_BASE : u32 = 0xF0000;
+const PLACEHOLDER_LIMIT : u32 = 0xFFFFE;
+/// The placeholders past the first plane are taken from the other private
+/// use plane, after the markers of the diagnostics
+const SPARE_BASE : u32 = 0x100100;
+const SPARE_LIMIT : u32 = 0x10FFFD;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SpanKind {
+	/// `// ...` up to the end of line, excluding the line break
+	LineComment,
+	/// `/// ...` up to the end of line, excluding the line break
+	DocComment,
+	/// `/* ... */`, which may be nested in dart
+	BlockComment,
+	/// A string literal without interpolation, including the quotes
+	/// and the `r` prefix
+	Str,
+	/// The part of an interpolated string literal up to its first
+	/// expression, e.g. `'a ${` of `'a ${b} c $d'`
+	StrStart,
+	/// The part of an interpolated string literal between two expressions,
+	/// e.g. `} c $`
+	StrMiddle,
+	/// The part of an interpolated string literal after its last
+	/// expression, e.g. `'`
+	StrEnd,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Span {
+	pub kind : SpanKind,
+	pub text : String,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Masked {
+	/// The input with comments and strings replaced by placeholders
+	pub text : String,
+	/// The masked spans, indexed by their placeholders. Passes that
+	/// care about comments or strings may modify them in place.
+	pub spans : Vec<Span>,
+}
+
+/// Get the placeholder standing for the span of given index,
+/// or None if there are no more placeholders
+pub fn placeholder(index: usize) -> Option<char> {
+	let first = (PLACEHOLDER_LIMIT - PLACEHOLDER_BASE + 1) as usize;
+	let code = if index < first {
+		PLACEHOLDER_BASE + index as u32
+	} else {
+		SPARE_BASE + (index - first) as u32
+	};
+	if code <= SPARE_LIMIT {
+		std::char::from_u32(code)
+	} else {
+		None
+	}
+}
+
+/// Get the index of the span a character stands for,
+/// or None if it is not a placeholder
+pub fn span_index(c: char) -> Option<usize> {
+	let code = c as u32;
+	if (PLACEHOLDER_BASE..=PLACEHOLDER_LIMIT).contains(&code) {
+		Some((code - PLACEHOLDER_BASE) as usize)
+	} else if (SPARE_BASE..=SPARE_LIMIT).contains(&code) {
+		Some((code - SPARE_BASE + PLACEHOLDER_LIMIT - PLACEHOLDER_BASE + 1) as usize)
+	} else {
+		None
+	}
+}
+
+impl Masked {
+	/// Get the span a character stands for, if it is a placeholder
+	pub fn span(&self, c: char) -> Option<&Span> {
+		span_index(c).and_then(|index| self.spans.get(index))
+	}
+
+	/// Put the masked spans back into a text produced from the masked text,
+	/// without the parentheses around the interpolated expressions
+	pub fn unmask(&self, text: &str) -> String {
+		let holes = self.interpolation_holes(text);
+		let mut result = String::with_capacity(text.len());
+		for (i, c) in text.char_indices() {
+			if holes.iter().any(|(open, close)| i == *open || i == *close) {
+				continue;
+			}
+			match self.span(c) {
+				Some(span) => result.push_str(&span.text),
+				None => result.push(c),
+			}
+		}
+		result
+	}
+
+	/// The positions of the parentheses around the interpolated expressions
+	/// in a text produced from the masked text
+	pub fn interpolation_holes(&self, text: &str) -> Vec<(usize, usize)> {
+		let bytes = text.as_bytes();
+		let mut holes = vec![];
+		for (i, c) in text.char_indices() {
+			let is_hole_start = self.span(c)
+				.map(|span| span.kind == SpanKind::StrStart || span.kind == SpanKind::StrMiddle)
+				.unwrap_or(false);
+			let open = i + c.len_utf8();
+			if !is_hole_start || bytes.get(open) != Some(&b'(') {
+				continue;
+			}
+			let mut depth = 0;
+			for (j, b) in bytes.iter().enumerate().skip(open) {
+				match b {
+					b'(' => depth += 1,
+					b')' => {
+						depth -= 1;
+						if depth == 0 {
+							holes.push((open, j));
+							break;
+						}
+					},
+					_ => {}
+				}
+			}
+		}
+		holes
+	}
+}
+
+/// Replace every comment and string literal in the input by a placeholder.
+///
+/// The expressions interpolated in strings are not masked, so that the
+/// passes transpile them too: each part of the string around them gets
+/// its own placeholder, and each expression is put in parentheses, e.g.
+/// `'a ${b} c'` becomes `P(b)P`, with `P` standing for `'a ${` and `} c'`.
+pub fn mask(input: &str) -> Masked {
+	let mut text = String::with_capacity(input.len());
+	let mut spans = vec![];
+	mask_range(input, 0, input.len(), &mut text, &mut spans);
+	Masked { text, spans }
+}
+
+/// Mask the part of the input from `start` to `end`
+fn mask_range(input: &str, start: usize, end: usize, text: &mut String, spans: &mut Vec<Span>) {
+	let mut pos = start;
+	while pos < end {
+		let rest = &input[pos..end];
+		let found = if rest.starts_with("///") {
+			Some((SpanKind::DocComment, line_end(input, pos)))
+		} else if rest.starts_with("//") {
+			Some((SpanKind::LineComment, line_end(input, pos)))
+		} else if rest.starts_with("/*") {
+			Some((SpanKind::BlockComment, block_comment_end(input, pos)))
+		} else if is_string_start(input, pos) {
+			pos = mask_string(input, pos, text, spans);
+			continue;
+		} else {
+			None
+		};
+		match found {
+			Some((kind, span_end)) => {
+				let span_end = span_end.min(end);
+				push_span(kind, &input[pos..span_end], text, spans);
+				pos = span_end;
+			},
+			None => {
+				let c = rest.chars().next().unwrap();
+				text.push(c);
+				pos += c.len_utf8();
+			}
+		}
+	}
+}
+
+/// Mask the string literal starting at `pos`, and return its end
+fn mask_string(input: &str, pos: usize, text: &mut String, spans: &mut Vec<Span>) -> usize {
+	let end = string_end(input, pos);
+	let holes = interpolations(input, pos, end);
+	let mut part_start = pos;
+	for (i, (hole_start, hole_end)) in holes.iter().enumerate() {
+		let kind = if i == 0 { SpanKind::StrStart } else { SpanKind::StrMiddle };
+		push_span(kind, &input[part_start..*hole_start], text, spans);
+		text.push('(');
+		mask_range(input, *hole_start, *hole_end, text, spans);
+		text.push(')');
+		part_start = *hole_end;
+	}
+	let kind = if holes.is_empty() { SpanKind::Str } else { SpanKind::StrEnd };
+	push_span(kind, &input[part_start..end], text, spans);
+	end
+}
+
+/// Replace the span by its placeholder, or keep it as is if there are
+/// no more placeholders, so that a huge input is still transpiled,
+/// though not as well
+fn push_span(kind: SpanKind, span: &str, text: &mut String, spans: &mut Vec<Span>) {
+	match placeholder(spans.len()) {
+		Some(placeholder) => {
+			text.push(placeholder);
+			spans.push(Span { kind, text: span.to_string() });
+		},
+		None => text.push_str(span),
+	}
+}
+
+/// The ranges of the expressions interpolated in the string literal from
+/// `pos` to `end`, i.e. `expr` of `${expr}`, or `name` of `$name`
+fn interpolations(input: &str, pos: usize, end: usize) -> Vec<(usize, usize)> {
+	let bytes = input.as_bytes();
+	if bytes[pos] == b'r' {
+		return vec![];
+	}
+	let is_word = |c: u8| c == b'_' || c.is_ascii_alphanumeric();
+	let mut holes = vec![];
+	let mut i = pos + 1;
+	while i < end {
+		if bytes[i] == b'\\' {
+			i += 2;
+		} else if bytes[i..end].starts_with(b"${") {
+			let hole_end = interpolation_end(input, i + 2).min(end);
+			holes.push((i + 2, hole_end - 1));
+			i = hole_end;
+		} else if bytes[i] == b'$' && i + 1 < end && (bytes[i + 1] == b'_' || bytes[i + 1].is_ascii_alphabetic()) {
+			let mut name_end = i + 1;
+			while name_end < end && is_word(bytes[name_end]) {
+				name_end += 1;
+			}
+			holes.push((i + 1, name_end));
+			i = name_end;
+		} else {
+			i += 1;
+		}
+	}
+	holes
+}
+
+/// Position of the line break ending the line containing `pos`,
+/// or the end of the input
+fn line_end(input: &str, pos: usize) -> usize {
+	match input[pos..].find('\n') {
+		Some(offset) => pos + offset,
+		None => input.len(),
+	}
+}
+
+/// Position right after the block comment starting at `pos`.
+/// An unterminated comment extends to the end of the input.
+fn block_comment_end(input: &str, pos: usize) -> usize {
+	let bytes = input.as_bytes();
+	let mut depth = 0;
+	let mut i = pos;
+	while i < bytes.len() {
+		if bytes[i..].starts_with(b"/*") {
+			depth += 1;
+			i += 2;
+		} else if bytes[i..].starts_with(b"*/") {
+			depth -= 1;
+			i += 2;
+			if depth == 0 {
+				return i;
+			}
+		} else {
+			i += 1;
+		}
+	}
+	input.len()
+}
+
+/// Whether a string literal starts at `pos`, i.e. a quote,
+/// or a quote preceded by the `r` of a raw string
+fn is_string_start(input: &str, pos: usize) -> bool {
+	let bytes = input.as_bytes();
+	let is_word = |i: usize| bytes[i] == b'_' || bytes[i] == b'$' || bytes[i].is_ascii_alphanumeric();
+	match bytes[pos] {
+		b'\'' | b'"' => true,
+		b'r' => pos + 1 < bytes.len()
+			&& (bytes[pos + 1] == b'\'' || bytes[pos + 1] == b'"')
+			&& (pos == 0 || !is_word(pos - 1)),
+		_ => false,
+	}
+}
+
+/// Position right after the string literal starting at `pos`. Handles raw
+/// strings, triple quoted strings, escapes, and `${...}` interpolations,
+/// which may contain nested string literals.
+/// An unterminated string extends to the end of the line (or the input,
+/// for triple quoted strings).
+fn string_end(input: &str, pos: usize) -> usize {
+	let bytes = input.as_bytes();
+	let mut i = pos;
+	let raw = bytes[i] == b'r';
+	if raw {
+		i += 1;
+	}
+	let quote = bytes[i];
+	let triple = bytes[i..].starts_with(&[quote, quote, quote]);
+	i += if triple { 3 } else { 1 };
+	while i < bytes.len() {
+		let c = bytes[i];
+		if triple && bytes[i..].starts_with(&[quote, quote, quote]) {
+			return i + 3;
+		} else if !triple && c == quote {
+			return i + 1;
+		} else if !triple && c == b'\n' {
+			return i;
+		} else if c == b'\\' && !raw {
+			i += 2;
+		} else if !raw && bytes[i..].starts_with(b"${") {
+			i = interpolation_end(input, i + 2);
+		} else {
+			i += 1;
+		}
+	}
+	input.len()
+}
+
+/// Position right after the `}` closing the interpolation
+/// whose expression starts at `pos`
+fn interpolation_end(input: &str, pos: usize) -> usize {
+	let bytes = input.as_bytes();
+	let mut depth = 1;
+	let mut i = pos;
+	while i < bytes.len() {
+		match bytes[i] {
+			b'{' => {
+				depth += 1;
+				i += 1;
+			},
+			b'}' => {
+				depth -= 1;
+				i += 1;
+				if depth == 0 {
+					return i;
+				}
+			},
+			_ if is_string_start(input, i) => {
+				i = string_end(input, i);
+			},
+			_ => {
+				i += 1;
+			}
+		}
+	}
+	input.len()
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn kinds(masked: &Masked) -> Vec<(SpanKind, &str)> {
+		masked.spans.iter().map(|span| (span.kind, span.text.as_str())).collect()
+	}
+
+	#[test]
+	fn mask_comments() {
+		let masked = mask(r"/// Doc
+int a; // foo(int a) {
+/* block /* nested */ still */ int b;");
+		assert_eq!(masked.text, format!("{}\nint a; {}\n{} int b;",
+			placeholder(0).unwrap(), placeholder(1).unwrap(), placeholder(2).unwrap()));
+		assert_eq!(kinds(&masked), vec![
+			(SpanKind::DocComment, "/// Doc"),
+			(SpanKind::LineComment, "// foo(int a) {"),
+			(SpanKind::BlockComment, "/* block /* nested */ still */"),
+		]);
+	}
+
+	#[test]
+	fn mask_strings() {
+		let masked = mask(r#"var a = 'it\'s' + "x" + r'\d' + bar'y';"#);
+		assert_eq!(kinds(&masked), vec![
+			(SpanKind::Str, r"'it\'s'"),
+			(SpanKind::Str, r#""x""#),
+			(SpanKind::Str, r"r'\d'"),
+			(SpanKind::Str, r"'y'"),
+		]);
+	}
+
+	#[test]
+	fn mask_multiline_strings() {
+		let masked = mask(r#"var a = '''
+import 'package:a/b.dart';
+// not a comment
+''';"#);
+		assert_eq!(masked.text, format!("var a = {};", placeholder(0).unwrap()));
+	}
+
+	#[test]
+	fn mask_many_spans() {
+		let count = (PLACEHOLDER_LIMIT - PLACEHOLDER_BASE + 1 + SPARE_LIMIT - SPARE_BASE + 1) as usize;
+		let input = "'a' ".repeat(count + 2);
+		let masked = mask(&input);
+		assert_eq!(masked.spans.len(), count);
+		assert_eq!(span_index(placeholder(count - 1).unwrap()), Some(count - 1));
+		assert_eq!(placeholder(count), None);
+		assert!(masked.text.ends_with(" 'a' 'a' "));
+		assert_eq!(masked.unmask(&masked.text), input);
+	}
+
+	#[test]
+	fn mask_interpolation() {
+		let masked = mask(r#"print('a ${b ? '}' : "${c}"} d $e' + 'e');"#);
+		assert_eq!(kinds(&masked), vec![
+			(SpanKind::StrStart, r"'a ${"),
+			(SpanKind::Str, r"'}'"),
+			(SpanKind::StrStart, r#""${"#),
+			(SpanKind::StrEnd, r#"}""#),
+			(SpanKind::StrMiddle, r"} d $"),
+			(SpanKind::StrEnd, r"'"),
+			(SpanKind::Str, r"'e'"),
+		]);
+		assert_eq!(masked.text, format!("print({}(b ? {} : {}(c){}){}(e){} + {});",
+			placeholder(0).unwrap(), placeholder(1).unwrap(), placeholder(2).unwrap(), placeholder(3).unwrap(),
+			placeholder(4).unwrap(), placeholder(5).unwrap(), placeholder(6).unwrap()));
+		assert_eq!(masked.unmask(&masked.text), r#"print('a ${b ? '}' : "${c}"} d $e' + 'e');"#);
+	}
+
+	#[test]
+	fn mask_quote_in_comment() {
+		let masked = mask(r"// don't
+var a = 'b';");
+		assert_eq!(kinds(&masked), vec![
+			(SpanKind::LineComment, r"// don't"),
+			(SpanKind::Str, r"'b'"),
+		]);
+	}
+
+	#[test]
+	fn unmask_1() {
+		let input = r"/// Doc
+void f(String s) { // comment
+  print('$s');
+}";
+		let masked = mask(input);
+		assert_eq!(masked.unmask(&masked.text), input);
+		assert_eq!(masked.unmask(&masked.text.replace("void", "public void")),
+			input.replace("void", "public void"));
+	}
+}
@@ -0,0 +1,205 @@
+use regex::Captures;
+use regex::Regex;
+use std::collections::HashSet;
+use crate::config::Config;
+use crate::types;
+
+/// Transpile the null-safety features of dart, working on the .NET types:
+/// - put the output in a `#nullable enable` context, or `#nullable disable`
+///   for legacy dart, since C# 8. In older versions, where the references
+///   are all nullable, the `?` of the reference types and the `!` null
+///   assertions of the references are removed
+/// - the `!` null assertion is kept for references, and becomes `.Value`
+///   for variables of nullable value types, like `int?`
+/// - `late` declarations without initializers are initialized with `null!`,
+///   or `default` for value types, and `late` is removed elsewhere. `final`
+///   is removed from them, since they are assigned after the initialization
+/// - the value types of declarations and parameters defaulting to `null`,
+///   which legacy dart allows, become nullable: `bool a = null` becomes
+///   `bool? a = null`
+///
+/// The `?` of nullable types needs no change.
+pub fn transpile_nullable(input: &str, config: &Config) -> String {
+	let result = transpile_late(input);
+	let result = transpile_null_defaults(&result);
+	let result = transpile_null_assertion(&result);
+	if config.csharp_version < 8 {
+		remove_nullable_references(&result)
+	} else if config.null_safety {
+		format!("#nullable enable\n\n{}", result)
+	} else {
+		format!("#nullable disable\n\n{}", result)
+	}
+}
+
+fn transpile_late(input: &str) -> String {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"(?x)
+			\blate\s+
+			(?P<final>final\s+)?
+			(?P<decl>
+				(?P<type>[\w.]+(?:<[\w\s,<>?.]*>)?\??)\s+
+				\w+
+			)
+			(?P<end>\s*;)?
+		").unwrap();
+	}
+
+	RE.replace_all(input, |cap: &Captures| -> String {
+		let decl = cap.name("decl").unwrap().as_str();
+		let ty = cap.name("type").unwrap().as_str();
+		let is_final = cap.name("final").is_some();
+		match cap.name("end") {
+			Some(end) if !ty.ends_with('?') && ty != "var" => {
+				let init = if types::is_value_type(ty) { "default" } else { "null!" };
+				format!("{} = {}{}", decl, init, end.as_str())
+			},
+			Some(end) => format!("{}{}", decl, end.as_str()),
+			None if is_final => format!("final {}", decl),
+			None => decl.to_string(),
+		}
+	}).to_string()
+}
+
+fn transpile_null_defaults(input: &str) -> String {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"(?P<pre>[^\w.?]|^)(?P<type>\w+)(?P<rest>\s+[\w@]+\s*=\s*null\b)").unwrap();
+	}
+
+	RE.replace_all(input, |cap: &Captures| -> String {
+		let ty = cap.name("type").unwrap().as_str();
+		if types::is_value_type(ty) {
+			format!("{}{}?{}", &cap["pre"], ty, &cap["rest"])
+		} else {
+			cap.get(0).unwrap().as_str().to_string()
+		}
+	}).to_string()
+}
+
+/// The names of the variables declared with nullable value types, anywhere
+/// in the input. There is no scoping.
+fn nullable_value_variables(input: &str) -> HashSet<String> {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"\b(?P<type>\w+)\?\s+(?P<name>\w+)\b").unwrap();
+	}
+
+	RE.captures_iter(input).filter(|cap| {
+		types::is_value_type(cap.name("type").unwrap().as_str())
+	}).map(|cap| cap.name("name").unwrap().as_str().to_string()).collect()
+}
+
+fn transpile_null_assertion(input: &str) -> String {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"(?P<expr>\b\w+|[)\]])!(?P<next>[^=]|$)").unwrap();
+	}
+
+	let variables = nullable_value_variables(input);
+	RE.replace_all(input, |cap: &Captures| -> String {
+		let expr = cap.name("expr").unwrap().as_str();
+		let next = cap.name("next").unwrap().as_str();
+		if variables.contains(expr) {
+			format!("{}.Value{}", expr, next)
+		} else {
+			cap.get(0).unwrap().as_str().to_string()
+		}
+	}).to_string()
+}
+
+/// Remove the `?` of the nullable reference types, and the `!` null
+/// assertions of the references, like `null!`. The enums and the structs
+/// declared in the input are value types.
+fn remove_nullable_references(input: &str) -> String {
+	lazy_static! {
+		static ref VALUE_TYPE : Regex = Regex::new(r"\b(?:enum|struct)\s+(?P<name>\w+)").unwrap();
+		static ref NULLABLE : Regex = Regex::new(r"\b(?P<type>[\w.@]+(?:<[\w\s,<>?.@]*>)?)\?(?P<next>\s+[\w@]|\s*[>,)\]])").unwrap();
+		static ref ASSERTION : Regex = Regex::new(r"(?P<expr>\b\w+|[)\]])!(?P<next>[^=]|$)").unwrap();
+	}
+
+	let value_types = VALUE_TYPE.captures_iter(input)
+		.map(|cap| cap["name"].to_string())
+		.collect::<HashSet<String>>();
+	let result = NULLABLE.replace_all(input, |cap: &Captures| -> String {
+		let ty = &cap["type"];
+		if types::is_value_type(ty) || value_types.contains(ty) {
+			cap.get(0).unwrap().as_str().to_string()
+		} else {
+			format!("{}{}", ty, &cap["next"])
+		}
+	});
+	ASSERTION.replace_all(&result, |cap: &Captures| -> String {
+		match &cap["expr"] {
+			"is" => cap.get(0).unwrap().as_str().to_string(),
+			expr => format!("{}{}", expr, &cap["next"]),
+		}
+	}).to_string()
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn late_transpiled() {
+		assert_eq!(
+			transpile_late(r"
+  late string name;
+  late final int count;
+  late List<string> items = [];
+  late string? label;
+  late final x = f();"),
+			r"
+  string name = null!;
+  int count = default;
+  List<string> items = [];
+  string? label;
+  final x = f();"
+		);
+	}
+
+	#[test]
+	fn null_defaults_transpiled() {
+		assert_eq!(
+			transpile_nullable(r"
+void f(string name = null, bool decoOver = null, {int? size = null}) {
+  double ratio = null;
+  if (decoOver!) {}
+}", &Config { null_safety: false, ..Config::default() }),
+			r"#nullable disable
+
+
+void f(string name = null, bool? decoOver = null, {int? size = null}) {
+  double? ratio = null;
+  if (decoOver.Value) {}
+}"
+		);
+	}
+
+	#[test]
+	fn null_assertion_transpiled() {
+		assert_eq!(
+			transpile_null_assertion(r"
+int? count;
+string? name;
+print(count! + name!.length + f()! + a[0]!);
+if (count != null && x is! int && !done) {}"),
+			r"
+int? count;
+string? name;
+print(count.Value + name!.length + f()! + a[0]!);
+if (count != null && x is! int && !done) {}"
+		);
+	}
+
+	#[test]
+	fn nullable_context() {
+		assert_eq!(transpile_nullable("string? a;", &Config::default()),
+			"#nullable enable\n\nstring? a;");
+		let config = Config { null_safety: false, ..Config::default() };
+		assert_eq!(transpile_nullable("string a;", &config),
+			"#nullable disable\n\nstring a;");
+		let config = Config { csharp_version: 7, ..Config::default() };
+		assert_eq!(transpile_nullable("enum Color { red }\nstring? a = b!.c;\nList<string?> d;\nColor? e;\nlate string f;", &config),
+			"enum Color { red }\nstring a = b.c;\nList<string> d;\nColor? e;\nstring f = null;");
+	}
+}
@@ -0,0 +1,145 @@
+use regex::Regex;
+use crate::config::Config;
+use crate::utils;
+
+/// Transpile the null-aware operators.
+///
+/// `?.` and `?[]` are the same in C#, and so is `??=` since C# 8.
+/// For older versions, `x ??= v;` is lowered to `if (x == null) x = v;`,
+/// or to `(x ?? (x = v))` inside an expression, see `lower_null_assignment`.
+///
+/// The null-aware spread `...?x` is lowered to the plain spread
+/// `...(x ?? [])`, which is transpiled together with the other spreads.
+/// The null-aware cascades are lowered together with the other cascades.
+pub fn transpile_null_aware(input: &str, config: &Config) -> String {
+	let result = lower_null_aware_spread(input);
+	if config.csharp_version < 8 {
+		lower_null_assignment(&result)
+	} else {
+		result
+	}
+}
+
+fn lower_null_aware_spread(input: &str) -> String {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"\.\.\.\?\s*").unwrap();
+	}
+
+	let mut result = String::new();
+	let mut last = 0;
+	for mat in RE.find_iter(input) {
+		if mat.start() < last {
+			continue;
+		}
+		let end = utils::expression_end(input, mat.end());
+		let empty = match enclosing_bracket(input, mat.start()) {
+			Some('{') => "{}",
+			_ => "[]",
+		};
+		result.push_str(&input[last..mat.start()]);
+		result.push_str(&format!("...({} ?? {})", input[mat.end()..end].trim_end(), empty));
+		last = end;
+	}
+	result.push_str(&input[last..]);
+	result
+}
+
+/// The innermost opening bracket before the position
+fn enclosing_bracket(text: &str, pos: usize) -> Option<char> {
+	let mut depth = 0;
+	for c in text[..pos].chars().rev() {
+		match c {
+			')' | ']' | '}' => depth += 1,
+			'(' | '[' | '{' if depth == 0 => return Some(c),
+			'(' | '[' | '{' => depth -= 1,
+			_ => {}
+		}
+	}
+	None
+}
+
+/// Lower `x ??= v`. A null-aware target, like `a?.b.c ??= v`, assigns
+/// nothing when `a?.b` is null, so the object is held in a temporary:
+/// `var _target0 = a?.b; if (_target0 != null && _target0.c == null) ...`
+fn lower_null_assignment(input: &str) -> String {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"(?P<target>[\w@]+(?:\??\.[\w@]+|\??\[[^\[\]\n]*\])*)\s*\?\?=\s*").unwrap();
+	}
+
+	let mut result = String::new();
+	let mut last = 0;
+	let mut temps = 0;
+	for cap in RE.captures_iter(input) {
+		let mat = cap.get(0).unwrap();
+		if mat.start() < last {
+			continue;
+		}
+		let target = cap.name("target").unwrap().as_str();
+		let end = utils::expression_end(input, mat.end());
+		let value = input[mat.end()..end].trim_end();
+		let before = input[last..mat.start()].trim_end();
+		let is_statement = (before.is_empty() || before.ends_with([';', '{', '}']))
+			&& input[end..].starts_with(';');
+		result.push_str(&input[last..mat.start()]);
+		let null_aware = target.rfind("?.").into_iter().chain(target.rfind("?[")).max();
+		if let Some(split) = null_aware {
+			let object = &target[..split];
+			let access = &target[split + 1..];
+			let temp = format!("_target{}", temps);
+			temps += 1;
+			if is_statement {
+				result.push_str(&format!("var {} = {}; if ({} != null && {}{} == null) {}{} = {};",
+					temp, object, temp, temp, access, temp, access, value));
+			} else {
+				result.push_str(&format!("({} is var {} && {} != null ? {}{} ?? ({}{} = {}) : {}?{})",
+					object, temp, temp, temp, access, temp, access, value, temp, access));
+			}
+		} else if is_statement {
+			result.push_str(&format!("if ({} == null) {} = {};", target, target, value));
+		} else {
+			result.push_str(&format!("({} ?? ({} = {}))", target, target, value));
+		}
+		last = if is_statement { end + 1 } else { end };
+	}
+	result.push_str(&input[last..]);
+	result
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn null_assignment_kept() {
+		let input = "meta ??= NodeMetadata();\nreturn a?.b?[0];";
+		assert_eq!(transpile_null_aware(input, &Config::default()), input);
+	}
+
+	#[test]
+	fn null_assignment_lowered() {
+		let config = Config { csharp_version: 7, ..Config::default() };
+		assert_eq!(
+			transpile_null_aware(r"
+  meta ??= NodeMetadata();
+  meta._buildOps ??= [];
+  return _styles[key] ??= f(a, b);
+  a?.next?.x ??= 4;
+  print(a?[0].y ??= 5);", &config),
+			r"
+  if (meta == null) meta = NodeMetadata();
+  if (meta._buildOps == null) meta._buildOps = [];
+  return (_styles[key] ?? (_styles[key] = f(a, b)));
+  var _target0 = a?.next; if (_target0 != null && _target0.x == null) _target0.x = 4;
+  print((a is var _target1 && _target1 != null ? _target1[0].y ?? (_target1[0].y = 5) : _target1?[0].y));"
+		);
+	}
+
+	#[test]
+	fn null_aware_spread_lowered() {
+		assert_eq!(
+			lower_null_aware_spread("[a, ...?b.c, d]; {...?m}; [...? f(x)]"),
+			"[a, ...(b.c ?? []), d]; {...(m ?? {})}; [...(f(x) ?? [])]"
+		);
+	}
+}
@@ -0,0 +1,682 @@
+use regex::Captures;
+use regex::Regex;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use crate::config::Config;
+use crate::decls;
+use crate::keywords;
+use crate::mask;
+use crate::types;
+use crate::utils;
+
+/// The class of the helpers of the operators without C# equivalents
+const HELPERS : &str = "DartOperators";
+
+/// Transpile the operators whose semantics differ, and the declarations
+/// of user-defined operators.
+///
+/// - `a ~/ b` becomes `(int)(a / b)`
+/// - `a % b` becomes `(a % n + n) % n` for a positive literal `n`, since
+///   the dart modulo is never negative, otherwise a call of a generated
+///   helper, unless `a` is a length
+/// - `a >>> b` becomes `(int)((uint)a >> b)` before C# 11
+/// - `identical(a, b)` becomes `object.ReferenceEquals(a, b)`, or `a == b`
+///   if an operand is of a known value type, which would be boxed
+/// - `operator ==` and the `hashCode` getter become the overrides of
+///   `Equals` and `GetHashCode`, with the `==` and `!=` operators
+/// - `operator []` and `operator []=` become an indexer
+/// - the other operators become methods, like `Add` for `+`, which the
+///   static C# operators call
+pub fn transpile_operators(input: &str, config: &Config) -> String {
+	let mut helpers = false;
+	let result = transpile_identical(input);
+	let result = transpile_compound_assignments(&result, config);
+	let result = transpile_binary(&result, "~/", "*/%", "", |left, right| {
+		Some(format!("(int)({} / {})", left, parenthesize(right)))
+	});
+	let result = transpile_binary(&result, "%", "*/%", "", |left, right| modulo(left, right, &mut helpers));
+	let result = if config.csharp_version < 11 {
+		transpile_binary(&result, ">>>", "*/%+-", "*/%+-", |left, right| {
+			Some(format!("(int)((uint){} >> {})", parenthesize(left), parenthesize(right)))
+		})
+	} else {
+		result
+	};
+	let result = transpile_equality(&result, config);
+	let result = transpile_declarations(&result, config);
+	if !helpers {
+		return result;
+	}
+	let modulo = ["int", "long", "double"].iter().map(|ty| format!(
+		"\tpublic static {} Mod({} a, {} b)\n\t{{\n\t\tvar r = a % b;\n\t\treturn r < 0 ? (b < 0 ? r - b : r + b) : r;\n\t}}\n", ty, ty, ty))
+		.collect::<Vec<String>>()
+		.join("\n");
+	format!("{}\n\npublic static class {}\n{{\n{}}}\n", result.trim_end(), HELPERS, modulo)
+}
+
+/// The dart modulo of the operands, or None if the left operand is a
+/// length, which is never negative. `helpers` is set if the helper is used.
+fn modulo(left: &str, right: &str, helpers: &mut bool) -> Option<String> {
+	lazy_static! {
+		static ref POSITIVE : Regex = Regex::new(r"^[1-9]\d*$").unwrap();
+		static ref LENGTH : Regex = Regex::new(r"(?:^\d+|\.(?:length|Length|Count))$").unwrap();
+	}
+
+	if LENGTH.is_match(left) {
+		None
+	} else if POSITIVE.is_match(right) {
+		Some(format!("(({} % {} + {}) % {})", left, right, right, right))
+	} else {
+		*helpers = true;
+		Some(format!("{}.Mod({}, {})", HELPERS, left, right))
+	}
+}
+
+fn parenthesize(operand: &str) -> String {
+	if is_postfix(operand) {
+		operand.to_string()
+	} else {
+		format!("({})", operand)
+	}
+}
+
+/// Whether the expression needs no parentheses as an operand
+fn is_postfix(expression: &str) -> bool {
+	let mut depth = 0;
+	for c in expression.chars() {
+		match c {
+			'(' | '[' | '{' => depth += 1,
+			')' | ']' | '}' => depth -= 1,
+			_ if depth > 0 => {},
+			_ if c.is_alphanumeric() || matches!(c, '_' | '.' | '?' | '!' | '@') => {},
+			_ if mask::span_index(c).is_some() => {},
+			_ => return false,
+		}
+	}
+	!expression.starts_with('!')
+}
+
+fn transpile_identical(input: &str) -> String {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"(?P<pre>\.\s*|\b(?P<word>[\w@]+)\s+)?\bidentical\s*\(").unwrap();
+	}
+
+	let value_variables = value_variables(input);
+	let value_types = value_types(input);
+	let mut text = input.to_string();
+	let mut pos = 0;
+	while let Some(cap) = RE.captures(&text[pos..]) {
+		let whole = cap.get(0).unwrap();
+		let start = pos + whole.start() + whole.as_str().rfind("identical").unwrap();
+		let open = pos + whole.end() - 1;
+		pos = open;
+		let is_call = match (cap.name("pre"), cap.name("word")) {
+			(Some(_), Some(word)) => keywords::is_dart_keyword(word.as_str()),
+			(Some(_), None) => false,
+			(None, _) => true,
+		};
+		if !is_call {
+			continue;
+		}
+		let close = utils::matching_bracket(&text, open);
+		let operands = close.map(|close| utils::split_top_level(&text[open + 1..close], ','));
+		let replacement = match (close, operands) {
+			(Some(close), Some(operands)) if operands.len() == 2 && operands.iter().any(|operand| {
+				is_value(operand.trim(), &value_variables, &value_types)
+			}) => {
+				let equality = format!("{} == {}", operands[0].trim(), operands[1].trim());
+				let enclosed = text[..start].trim_end().ends_with('(') && text[close + 1..].trim_start().starts_with(')');
+				let equality = if enclosed { equality } else { format!("({})", equality) };
+				text.replace_range(start..=close, &equality);
+				pos = start + equality.len();
+				continue;
+			},
+			_ => "object.ReferenceEquals",
+		};
+		text.replace_range(start..open, replacement);
+		pos = start + replacement.len();
+	}
+	text
+}
+
+/// The names of the variables declared with value types, without scoping
+fn value_variables(input: &str) -> HashSet<String> {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"\b(?P<type>\w+)\??\s+(?P<name>[\w@]+)\s*[;=,)]").unwrap();
+	}
+
+	let value_types = value_types(input);
+	RE.captures_iter(input)
+		.filter(|cap| types::is_value_type(&cap["type"]) || value_types.contains(&cap["type"]))
+		.map(|cap| cap["name"].to_string())
+		.collect()
+}
+
+/// The enums and structs declared in the input
+fn value_types(input: &str) -> HashSet<String> {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"\b(?:enum|struct)\s+(?P<name>\w+)").unwrap();
+	}
+
+	RE.captures_iter(input).map(|cap| cap["name"].to_string()).collect()
+}
+
+/// Whether the operand is a literal, a variable or an enum member of a
+/// value type
+fn is_value(operand: &str, variables: &HashSet<String>, value_types: &HashSet<String>) -> bool {
+	if variables.contains(operand) {
+		return true;
+	}
+	if let Some(ty) = decls::infer_type(operand) {
+		return types::is_value_type(&ty);
+	}
+	match operand.rfind('.') {
+		Some(dot) => value_types.contains(&operand[..dot]),
+		None => false,
+	}
+}
+
+/// Transpile `x ~/= y`, `x %= y`, and `x >>>= y` before C# 11
+fn transpile_compound_assignments(input: &str, config: &Config) -> String {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"(?P<target>[\w@][\w@.]*(?:\[[^\[\];]*\])?)\s*(?P<op>~/=|%=|>>>=)").unwrap();
+	}
+
+	let mut text = input.to_string();
+	let mut pos = 0;
+	while let Some(cap) = RE.captures(&text[pos..]) {
+		let whole = cap.get(0).unwrap();
+		let start = pos + whole.start();
+		let value_start = pos + whole.end();
+		pos = value_start;
+		let op = cap.name("op").unwrap().as_str();
+		if op == ">>>=" && config.csharp_version >= 11 {
+			continue;
+		}
+		let target = cap.name("target").unwrap().as_str().to_string();
+		let end = utils::expression_end(&text, value_start);
+		let value = text[value_start..end].trim();
+		let replacement = match op {
+			"~/=" => format!("{} = (int)({} / {})", target, target, parenthesize(value)),
+			// lowered by the `%` pass, which would lower a lowered modulo again
+			"%=" if modulo(&target, value, &mut false).is_some() => format!("{} = {} % {}", target, target, parenthesize(value)),
+			"%=" => continue,
+			_ => format!("{} = (int)((uint){} >> {})", target, target, parenthesize(value)),
+		};
+		let end = start + whole.as_str().len() + text[value_start..end].trim_end().len();
+		text.replace_range(start..end, &replacement);
+		pos = start + replacement.len();
+	}
+	text
+}
+
+/// Replace `left op right` by the result of `f(left, right)`, if any. The
+/// operands extend over the binary operators in `left_ops` and `right_ops`,
+/// which have a higher precedence than `op`.
+fn transpile_binary<F>(input: &str, op: &str, left_ops: &str, right_ops: &str, mut f: F) -> String
+	where F: FnMut(&str, &str) -> Option<String> {
+	let mut text = input.to_string();
+	let mut pos = 0;
+	while let Some(offset) = text[pos..].find(op) {
+		let op_start = pos + offset;
+		let op_end = op_start + op.len();
+		pos = op_end;
+		let before = text[..op_start].trim_end();
+		if text[op_end..].starts_with('=') || text[op_end..].starts_with('>')
+			|| before.ends_with("operator") || before.ends_with(['~', '>'])
+			|| op.starts_with('>') && !text[..op_start].ends_with(char::is_whitespace) {
+			continue;
+		}
+		let start = utils::skip_spaces(&text, left_operand_start(&text, op_start, left_ops));
+		let end = right_operand_end(&text, op_end, right_ops);
+		let left = text[start..op_start].trim().to_string();
+		let right = text[op_end..end].trim().to_string();
+		if left.is_empty() || right.is_empty() {
+			continue;
+		}
+		if let Some(replacement) = f(&left, &right) {
+			text.replace_range(start..end, &replacement);
+			pos = start + replacement.len();
+		}
+	}
+	text
+}
+
+/// The start of the left operand ending at `end`
+fn left_operand_start(text: &str, end: usize, ops: &str) -> usize {
+	let bytes = text.as_bytes();
+	let mut i = end;
+	loop {
+		let operand_end = text[..i].trim_end().len();
+		let operand_start = postfix_start(text, operand_end);
+		if operand_start == operand_end {
+			return i;
+		}
+		i = operand_start;
+		let mut j = text[..i].trim_end().len();
+		// Unary operators
+		while j > 0 && matches!(bytes[j - 1], b'-' | b'!' | b'~') && !ends_operand(text[..j - 1].trim_end()) {
+			i = j - 1;
+			j = text[..i].trim_end().len();
+		}
+		if j == 0 || !ops.as_bytes().contains(&bytes[j - 1]) || !ends_operand(text[..j - 1].trim_end().trim_end_matches('~')) {
+			return i;
+		}
+		i = if bytes[j - 1] == b'/' && j >= 2 && bytes[j - 2] == b'~' { j - 2 } else { j - 1 };
+	}
+}
+
+/// Whether the text ends with an operand, rather than an operator
+fn ends_operand(text: &str) -> bool {
+	lazy_static! {
+		static ref WORD : Regex = Regex::new(r"[\w@]+$").unwrap();
+	}
+
+	match WORD.find(text) {
+		Some(word) => !keywords::is_dart_keyword(word.as_str()) || matches!(word.as_str(), "this" | "null" | "true" | "false"),
+		None => text.ends_with(|c: char| matches!(c, ')' | ']' | '!') || mask::span_index(c).is_some()),
+	}
+}
+
+/// The start of the postfix expression ending at `end`
+fn postfix_start(text: &str, end: usize) -> usize {
+	let mut i = end;
+	while let Some(c) = text[..i].chars().next_back() {
+		match c {
+			')' | ']' => match utils::matching_open(text, i - 1) {
+				Some(open) => i = open,
+				None => break,
+			},
+			'.' => i -= 1,
+			'?' if text[i..].starts_with('.') || text[i..].starts_with('[') => i -= 1,
+			'!' if text[i..].starts_with(['.', '[', '(']) || i == end => i -= 1,
+			_ if c.is_alphanumeric() || c == '_' || c == '@' || c == '$' => {
+				let start = text[..i].rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == '@' || c == '$'))
+					.map(|j| j + text[j..].chars().next().unwrap().len_utf8()).unwrap_or(0);
+				let word = &text[start..i];
+				let is_member = text[..start].ends_with('.');
+				if !is_member && keywords::is_dart_keyword(word) && !matches!(word, "this" | "super" | "null" | "true" | "false") {
+					break;
+				}
+				i = start;
+			},
+			_ if mask::span_index(c).is_some() => i -= c.len_utf8(),
+			_ => break,
+		}
+	}
+	i
+}
+
+/// The end of the right operand starting at `start`
+fn right_operand_end(text: &str, start: usize, ops: &str) -> usize {
+	lazy_static! {
+		static ref PREFIX : Regex = Regex::new(r"^(?:[-!~]|(?:await|new)\b)\s*").unwrap();
+	}
+
+	let mut i = utils::skip_spaces(text, start);
+	loop {
+		while let Some(mat) = PREFIX.find(&text[i..]) {
+			i += mat.end();
+		}
+		i = postfix_end(text, i);
+		let j = utils::skip_spaces(text, i);
+		match text[j..].chars().next() {
+			Some(c) if ops.contains(c) && !text[j + 1..].starts_with('=') && !text[j..].starts_with("/*") => {
+				i = utils::skip_spaces(text, j + 1);
+			},
+			_ => return i,
+		}
+	}
+}
+
+/// The end of the postfix expression starting at `start`
+fn postfix_end(text: &str, start: usize) -> usize {
+	let mut i = start;
+	while let Some(c) = text[i..].chars().next() {
+		match c {
+			'(' | '[' => match utils::matching_bracket(text, i) {
+				Some(close) => i = close + 1,
+				None => break,
+			},
+			'.' => i += 1,
+			'?' if text[i..].starts_with("?.") || text[i..].starts_with("?[") => i += 1,
+			'!' if !text[i..].starts_with("!=") && i > start => i += 1,
+			_ if c.is_alphanumeric() || c == '_' || c == '@' || c == '$' => i += c.len_utf8(),
+			_ if mask::span_index(c).is_some() => i += c.len_utf8(),
+			_ => break,
+		}
+	}
+	i
+}
+
+/// The end of the member whose body starts at `start`, a block, an
+/// expression body, or `;`
+fn member_end(text: &str, start: usize) -> Option<usize> {
+	let start = utils::skip_spaces(text, start);
+	if text[start..].starts_with('{') {
+		utils::matching_bracket(text, start).map(|close| close + 1)
+	} else {
+		let body = utils::split_top_level(&text[start..], ';')[0];
+		if start + body.len() < text.len() {
+			Some(start + body.len() + 1)
+		} else {
+			None
+		}
+	}
+}
+
+fn transpile_equality(input: &str, config: &Config) -> String {
+	lazy_static! {
+		static ref EQUALS : Regex = Regex::new(r"(?:@override\s+)?\bbool\s+operator\s*==\s*\(\s*(?:covariant\s+)?[\w@.]+\??\s+(?P<param>[\w@]+)\s*\)").unwrap();
+		static ref HASH_CODE : Regex = Regex::new(r"(?:@override\s+)?\bint\s+get\s+hashCode\b").unwrap();
+		static ref HASH_CODE_USE : Regex = Regex::new(r"(?P<pre>\.\s*)?\b(?P<name>hashCode|runtimeType)\b").unwrap();
+		static ref HASH : Regex = Regex::new(r"\bObject\.hash\s*\(").unwrap();
+	}
+
+	let nullable = if config.null_safety { "?" } else { "" };
+	let mut text = input.to_string();
+	let mut pos = 0;
+	while let Some(cap) = EQUALS.captures(&text[pos..]) {
+		let whole = cap.get(0).unwrap();
+		let start = pos + whole.start();
+		let header_end = pos + whole.end();
+		let header = format!("public override bool Equals(object{} {})", nullable, &cap["param"]);
+		pos = header_end;
+		let class = match utils::enclosing_class(&text, start) {
+			Some(class) => class,
+			None => continue,
+		};
+		let end = match member_end(&text, header_end) {
+			Some(end) => end,
+			None => continue,
+		};
+		let indent = utils::line_indent(&text, start).to_string();
+		let operators = format!(
+			"\n{}public static bool operator ==({}{} left, {}{} right) => Equals(left, right);\n{}public static bool operator !=({}{} left, {}{} right) => !Equals(left, right);",
+			indent, class, nullable, class, nullable, indent, class, nullable, class, nullable);
+		text.insert_str(end, &operators);
+		text.replace_range(start..header_end, &header);
+		pos = end + operators.len() + header.len() - (header_end - start);
+	}
+
+	let text = HASH_CODE.replace_all(&text, "public override int GetHashCode()");
+	let text = HASH.replace_all(&text, "HashCode.Combine(");
+	HASH_CODE_USE.replace_all(&text, |cap: &Captures| -> String {
+		let pre = cap.name("pre").map(|pre| pre.as_str()).unwrap_or("");
+		let method = if &cap["name"] == "hashCode" { "GetHashCode()" } else { "GetType()" };
+		format!("{}{}", pre, method)
+	}).to_string()
+}
+
+/// A declaration of a user-defined operator
+struct Operator {
+	start : usize,
+	end : usize,
+	class : String,
+	op : String,
+	ret : String,
+	params : String,
+	body : String,
+	indent : String,
+}
+
+fn transpile_declarations(input: &str, config: &Config) -> String {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"(?x)
+			(?:@override\s+)?
+			(?:(?P<ret>[\w@.]+(?:<[\w\s,<>?.@()]*>)?\??)\s+)?
+			\boperator\s*
+			(?P<op>\[\]=|\[\]|~/|>>>|>>|<<|<=|>=|[-+*/%<>&|^~])
+			\s*\((?P<params>[^)]*)\)
+		").unwrap();
+	}
+
+	let mut operators = vec![];
+	for cap in RE.captures_iter(input) {
+		let whole = cap.get(0).unwrap();
+		if cap.name("ret").map(|ret| ret.as_str() == "static").unwrap_or(false) {
+			continue;
+		}
+		let class = match utils::enclosing_class(input, whole.start()) {
+			Some(class) => class,
+			None => continue,
+		};
+		let end = match member_end(input, whole.end()) {
+			Some(end) => end,
+			None => continue,
+		};
+		operators.push(Operator {
+			start: whole.start(),
+			end,
+			class,
+			op: cap["op"].to_string(),
+			ret: cap.name("ret").map(|ret| ret.as_str()).unwrap_or("dynamic").to_string(),
+			params: cap["params"].trim().to_string(),
+			body: input[whole.end()..end].to_string(),
+			indent: utils::line_indent(input, whole.start()).to_string(),
+		});
+	}
+
+	let mut replacements : Vec<(usize, usize, String)> = vec![];
+	let mut indexers : HashMap<String, (Option<&Operator>, Option<&Operator>)> = HashMap::new();
+	for operator in operators.iter() {
+		match operator.op.as_str() {
+			"[]" => indexers.entry(operator.class.clone()).or_default().0 = Some(operator),
+			"[]=" => indexers.entry(operator.class.clone()).or_default().1 = Some(operator),
+			_ => replacements.push((operator.start, operator.end, method(operator, &operators, config))),
+		}
+	}
+	for (getter, setter) in indexers.values() {
+		let first = getter.or(*setter).unwrap();
+		replacements.push((first.start, first.end, indexer(*getter, *setter)));
+		if let (Some(_), Some(setter)) = (getter, setter) {
+			let line_start = input[..setter.start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+			let start = if input[line_start..setter.start].trim().is_empty() { line_start } else { setter.start };
+			let end = if input[setter.end..].starts_with('\n') { setter.end + 1 } else { setter.end };
+			replacements.push((start, end, String::new()));
+		}
+	}
+
+	replacements.sort_by_key(|(start, _, _)| *start);
+	let mut text = input.to_string();
+	for (start, end, replacement) in replacements.into_iter().rev() {
+		text.replace_range(start..end, &replacement);
+	}
+	text
+}
+
+/// The indexer of the `[]` and `[]=` operators of a class
+fn indexer(getter: Option<&Operator>, setter: Option<&Operator>) -> String {
+	let first = getter.or(setter).unwrap();
+	let indent = &first.indent;
+	let reindent = |body: &str| body.replace('\n', "\n  ");
+	let mut ty = getter.map(|getter| getter.ret.clone());
+	let mut index = getter.map(|getter| getter.params.clone());
+	let mut accessors = vec![];
+	if let Some(getter) = getter {
+		accessors.push(format!("{}  get{}", indent, reindent(&getter.body)));
+	}
+	if let Some(setter) = setter {
+		let params = utils::split_top_level(&setter.params, ',');
+		let (value, params) = params.split_last().unwrap();
+		let (value_type, value_name) = match value.trim().rfind(char::is_whitespace) {
+			Some(space) => (value.trim()[..space].trim().to_string(), value.trim()[space + 1..].to_string()),
+			None => (String::from("dynamic"), value.trim().to_string()),
+		};
+		ty.get_or_insert(value_type);
+		index.get_or_insert(params.join(",").trim().to_string());
+		let re = Regex::new(&format!(r"(?P<pre>^|[^.\w@]){}\b", regex::escape(&value_name))).unwrap();
+		let body = re.replace_all(&setter.body, "${pre}value");
+		accessors.push(format!("{}  set{}", indent, reindent(&body)));
+	}
+	format!("public {} this[{}] {{\n{}\n{}}}", ty.unwrap(), index.unwrap(), accessors.join("\n"), indent)
+}
+
+/// The method of an operator, followed by the static C# operator calling it
+fn method(operator: &Operator, operators: &[Operator], config: &Config) -> String {
+	let unary = operator.params.is_empty();
+	let name = match operator.op.as_str() {
+		"+" => "Add",
+		"-" if unary => "Negate",
+		"-" => "Subtract",
+		"*" => "Multiply",
+		"/" => "Divide",
+		"~/" => "IntegerDivide",
+		"%" => "Modulo",
+		"<" => "LessThan",
+		">" => "GreaterThan",
+		"<=" => "LessThanOrEqual",
+		">=" => "GreaterThanOrEqual",
+		"&" => "BitwiseAnd",
+		"|" => "BitwiseOr",
+		"^" => "ExclusiveOr",
+		"<<" => "LeftShift",
+		">>" => "RightShift",
+		">>>" => "UnsignedRightShift",
+		_ => "OnesComplement",
+	};
+	let declaration = format!("public {} {}({}){}", operator.ret, name, operator.params, operator.body);
+	if operator.op == "~/" || operator.op == ">>>" && config.csharp_version < 11 {
+		return declaration;
+	}
+
+	let args = utils::split_top_level(&operator.params, ',').iter()
+		.filter_map(|param| param.trim().rsplit(char::is_whitespace).next())
+		.filter(|name| !name.is_empty())
+		.collect::<Vec<&str>>()
+		.join(", ");
+	let params = if unary {
+		format!("{} operand", operator.class)
+	} else {
+		format!("{} left, {}", operator.class, operator.params)
+	};
+	let receiver = if unary { "operand" } else { "left" };
+	let partner = match operator.op.as_str() {
+		"<" => Some(">"),
+		">" => Some("<"),
+		"<=" => Some(">="),
+		">=" => Some("<="),
+		_ => None,
+	};
+	let declared = format!("{}\n{}public static {} operator {}({}) => {}.{}({});",
+		declaration, operator.indent, operator.ret, operator.op, params, receiver, name, args);
+	match partner {
+		// C# requires the comparison operators in pairs, `l > r` is `r < l`
+		Some(partner) if !operators.iter().any(|other| other.class == operator.class && other.op == partner) =>
+			format!("{}\n{}public static {} operator {}({} left, {} right) => right.{}(left);",
+				declared, operator.indent, operator.ret, partner, operator.class, operator.class, name),
+		_ => declared,
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn operators_transpiled() {
+		assert_eq!(
+			transpile_operators(r"
+  var a = x * 2 ~/ (y + 1);
+  var b = -i % 3 == 0 || list.length % 2 == 0;
+  var c = n % m + h >>> 1;
+  total ~/= count;
+  index %= size;
+  r %= 3;
+  if (identical(a, b)) {}
+  int i;
+  var d = !identical(i, 0) || identical(E.a, e);
+enum E { a }", &Config { csharp_version: 10, ..Config::default() }),
+			r"
+  var a = (int)(x * 2 / (y + 1));
+  var b = ((-i % 3 + 3) % 3) == 0 || list.length % 2 == 0;
+  var c = (int)((uint)(DartOperators.Mod(n, m) + h) >> 1);
+  total = (int)(total / count);
+  index = DartOperators.Mod(index, size);
+  r = ((r % 3 + 3) % 3);
+  if (object.ReferenceEquals(a, b)) {}
+  int i;
+  var d = !(i == 0) || (E.a == e);
+enum E { a }
+
+public static class DartOperators
+{
+	public static int Mod(int a, int b)
+	{
+		var r = a % b;
+		return r < 0 ? (b < 0 ? r - b : r + b) : r;
+	}
+
+	public static long Mod(long a, long b)
+	{
+		var r = a % b;
+		return r < 0 ? (b < 0 ? r - b : r + b) : r;
+	}
+
+	public static double Mod(double a, double b)
+	{
+		var r = a % b;
+		return r < 0 ? (b < 0 ? r - b : r + b) : r;
+	}
+}
+"
+		);
+	}
+
+	#[test]
+	fn equality_transpiled() {
+		assert_eq!(
+			transpile_operators(r"
+class Point {
+  @override
+  bool operator ==(object other) =>
+      other is Point && other.x == x && other.runtimeType == runtimeType;
+
+  @override
+  int get hashCode => Object.hash(x.hashCode, y);
+}", &Config::default()),
+			r"
+class Point {
+  public override bool Equals(object? other) =>
+      other is Point && other.x == x && other.GetType() == GetType();
+  public static bool operator ==(Point? left, Point? right) => Equals(left, right);
+  public static bool operator !=(Point? left, Point? right) => !Equals(left, right);
+
+  public override int GetHashCode() => HashCode.Combine(x.GetHashCode(), y);
+}"
+		);
+	}
+
+	#[test]
+	fn operator_declarations_transpiled() {
+		assert_eq!(
+			transpile_operators(r"
+class Vector {
+  double operator [](int i) => i == 0 ? x : y;
+  void operator []=(int i, double v) {
+    if (i == 0) x = v; else y = v;
+  }
+  Vector operator +(Vector other) => Vector(x + other.x, y + other.y);
+  Vector operator -() => Vector(-x, -y);
+  bool operator <(Vector other) => length < other.length;
+}", &Config::default()),
+			r"
+class Vector {
+  public double this[int i] {
+    get => i == 0 ? x : y;
+    set {
+      if (i == 0) x = value; else y = value;
+    }
+  }
+  public Vector Add(Vector other) => Vector(x + other.x, y + other.y);
+  public static Vector operator +(Vector left, Vector other) => left.Add(other);
+  public Vector Negate() => Vector(-x, -y);
+  public static Vector operator -(Vector operand) => operand.Negate();
+  public bool LessThan(Vector other) => length < other.length;
+  public static bool operator <(Vector left, Vector other) => left.LessThan(other);
+  public static bool operator >(Vector left, Vector right) => right.LessThan(left);
+}"
+		);
+	}
+}
@@ -0,0 +1,602 @@
+use regex::Captures;
+use regex::Regex;
+use crate::utils;
+
+/// Transpile the dart 3 patterns out of switches:
+///
+/// - the destructuring declarations `var (a, b) = pair;`, which become C#
+///   deconstructions for records, and one declaration per element for
+///   lists, maps and objects
+/// - the `if (x case pattern when guard)` conditions, which become
+///   `if (x is pattern && guard)`
+///
+/// Map patterns, which C# lacks, are lowered to `TryGetValue` checks
+/// followed by typed `is` tests, see `map_pattern`.
+pub fn transpile_patterns(input: &str) -> String {
+	let mut temps = 0;
+	let result = transpile_destructuring(input, &mut temps);
+	transpile_if_case(&result, &mut temps)
+}
+
+fn transpile_destructuring(input: &str, temps: &mut usize) -> String {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"(?P<pre>(?:^|[;{}])\s*)\b(?:final|var)\s+(?P<pattern>[(\[{]|[A-Z][\w.@]*(?:<[\w\s,<>?.@]*>)?\()").unwrap();
+	}
+
+	let mut text = input.to_string();
+	let mut pos = 0;
+	while let Some(cap) = RE.captures(&text[pos..]) {
+		let start = pos + cap.name("pre").unwrap().end();
+		let open = pos + cap.name("pattern").unwrap().end() - 1;
+		pos = start + 1;
+		let close = match utils::matching_bracket(&text, open) {
+			Some(close) => close,
+			None => continue,
+		};
+		let pattern_start = pos - 1 + text[start..].find(char::is_whitespace).unwrap_or(0);
+		let pattern = text[pattern_start..close + 1].trim().to_string();
+		let after = utils::skip_spaces(&text, close + 1);
+		if !text[after..].starts_with('=') || text[after..].starts_with("==") {
+			continue;
+		}
+		let value_start = after + 1;
+		let value = utils::split_top_level(&text[value_start..], ';')[0];
+		let end = value_start + value.len();
+		if !text[end..].starts_with(';') {
+			continue;
+		}
+		let indent = utils::line_indent(&text, start).to_string();
+		let statements = destructure(&pattern, value.trim(), temps);
+		if statements.is_empty() {
+			continue;
+		}
+		let replacement = statements.join(&format!("\n{}", indent));
+		text.replace_range(start..end + 1, &replacement);
+		pos = start + replacement.len();
+	}
+	text
+}
+
+/// An element of a record pattern in a declaration, as the element of a
+/// C# deconstruction: `typed` may declare types, like `(int a, var b)`,
+/// while `untyped` is for the `var (a, b)` form.
+struct Element {
+	typed : String,
+	untyped : Option<String>,
+}
+
+/// The statements declaring the variables of the pattern, which must
+/// be a declaration pattern, from the value
+fn destructure(pattern: &str, value: &str, temps: &mut usize) -> Vec<String> {
+	lazy_static! {
+		static ref OBJECT : Regex = Regex::new(r"^[A-Z][\w.@]*(?:<[\w\s,<>?.@]*>)?\(").unwrap();
+		static ref CAST : Regex = Regex::new(r"^(?P<pattern>.+?)\s+as\s+(?P<type>[\w.@]+(?:<[\w\s,<>?.@]*>)?\??)$").unwrap();
+	}
+
+	let pattern = pattern.trim();
+	if let Some(element) = variable(pattern) {
+		return if element.typed == "_" {
+			vec![]
+		} else {
+			vec![format!("{} = {};", element.typed, value)]
+		};
+	}
+	if pattern.starts_with('(') {
+		if let Some(element) = record_element(pattern) {
+			return vec![match element.untyped {
+				Some(untyped) => format!("var {} = {};", untyped, value),
+				None => format!("{} = {};", element.typed, value),
+			}];
+		}
+	}
+
+	if let Some(cap) = CAST.captures(pattern) {
+		return destructure(&cap["pattern"], &format!("(({}){})", &cap["type"], value), temps);
+	}
+	if let Some(inner) = pattern.strip_suffix('!') {
+		return destructure(inner, &format!("({} ?? throw new InvalidOperationException())", value), temps);
+	}
+
+	let (value, mut statements) = evaluate(value, temps);
+	if pattern.starts_with('(') {
+		let elements = utils::split_top_level(&pattern[1..pattern.len() - 1], ',');
+		for (i, element) in elements.iter().enumerate() {
+			let (field, sub) = field_pattern(element);
+			let field = field.unwrap_or_else(|| format!("Item{}", i + 1));
+			statements.extend(destructure(&sub, &format!("{}.{}", value, field), temps));
+		}
+	} else if let Some(content) = pattern.strip_prefix('[') {
+		let elements = utils::split_top_level(&content[..content.len() - 1], ',');
+		let elements = elements.iter().map(|element| element.trim()).filter(|element| !element.is_empty()).collect::<Vec<&str>>();
+		let rest = elements.iter().position(|element| element.starts_with("..."));
+		for (i, element) in elements.iter().enumerate() {
+			let index = match rest {
+				Some(rest) if i == rest => {
+					let name = element[3..].trim();
+					if !name.is_empty() && name != "_" {
+						let after = elements.len() - rest - 1;
+						let skipped = if after == 0 {
+							format!("{}.Skip({}).ToList()", value, rest)
+						} else {
+							format!("{}.Skip({}).Take({}.Count - {}).ToList()", value, rest, value, rest + after)
+						};
+						statements.extend(destructure(name, &skipped, temps));
+					}
+					continue;
+				},
+				Some(rest) if i > rest => format!("{}.Count - {}", value, elements.len() - i),
+				_ => i.to_string(),
+			};
+			statements.extend(destructure(element, &format!("{}[{}]", value, index), temps));
+		}
+	} else if let Some(mat) = OBJECT.find(pattern) {
+		let fields = utils::split_top_level(&pattern[mat.end()..pattern.len() - 1], ',');
+		for field in fields {
+			if let (Some(name), sub) = field_pattern(field) {
+				statements.extend(destructure(&sub, &format!("{}.{}", value, name), temps));
+			}
+		}
+	} else if pattern.starts_with('{') {
+		for entry in utils::split_top_level(&pattern[1..pattern.len() - 1], ',') {
+			if let Some(colon) = entry.find(':') {
+				statements.extend(destructure(&entry[colon + 1..], &format!("{}[{}]", value, entry[..colon].trim()), temps));
+			}
+		}
+	} else {
+		// The variables of a failed `is not` test are definitely assigned after the throw
+		statements.push(format!("if ({} is not {}) throw new InvalidOperationException();", value, transpile_pattern(pattern)));
+	}
+	statements
+}
+
+/// Split `name: pattern` or `:var name` into the field name and the pattern
+fn field_pattern(element: &str) -> (Option<String>, String) {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"^(?P<name>[\w@]+)?\s*:(?P<pattern>.*)$").unwrap();
+		static ref NAME : Regex = Regex::new(r"([\w@]+)$").unwrap();
+	}
+
+	let element = element.trim();
+	match RE.captures(element) {
+		Some(cap) => {
+			let pattern = cap.name("pattern").unwrap().as_str().trim().to_string();
+			let name = cap.name("name").map(|name| name.as_str().to_string())
+				.or_else(|| NAME.captures(&pattern).map(|name| name[1].to_string()));
+			(name, pattern)
+		},
+		None => (None, element.to_string()),
+	}
+}
+
+/// The record pattern or the variable as the element of a C# deconstruction,
+/// or None if it contains other patterns
+fn record_element(pattern: &str) -> Option<Element> {
+	let pattern = pattern.trim();
+	if !pattern.starts_with('(') {
+		return variable(pattern);
+	}
+	let elements = utils::split_top_level(&pattern[1..pattern.len() - 1], ',').iter()
+		.map(|element| record_element(&field_pattern(element).1))
+		.collect::<Option<Vec<Element>>>()?;
+	let untyped = elements.iter()
+		.map(|element| element.untyped.clone())
+		.collect::<Option<Vec<String>>>()
+		.map(|untyped| format!("({})", untyped.join(", ")));
+	let typed = match &untyped {
+		Some(untyped) => format!("var {}", untyped),
+		None => format!("({})", elements.into_iter().map(|element| element.typed).collect::<Vec<String>>().join(", ")),
+	};
+	Some(Element { typed, untyped })
+}
+
+/// The variable pattern `x`, `var x`, `final x`, `T x` or `_`
+fn variable(pattern: &str) -> Option<Element> {
+	lazy_static! {
+		static ref UNTYPED : Regex = Regex::new(r"^(?:(?:var|final)\s+)?(?P<name>[\w@]+)$").unwrap();
+		static ref TYPED : Regex = Regex::new(r"^(?:final\s+)?(?P<type>[\w.@]+(?:<[\w\s,<>?.@]*>)?\??)\s+(?P<name>[\w@]+)$").unwrap();
+	}
+
+	if pattern == "_" {
+		Some(Element { typed: String::from("_"), untyped: Some(String::from("_")) })
+	} else if let Some(cap) = UNTYPED.captures(pattern) {
+		let name = cap.name("name").unwrap().as_str();
+		Some(Element { typed: format!("var {}", name), untyped: Some(name.to_string()) })
+	} else {
+		TYPED.captures(pattern).map(|cap| Element {
+			typed: format!("{} {}", &cap["type"], &cap["name"]),
+			untyped: None,
+		})
+	}
+}
+
+/// The value itself if it is a variable, otherwise a temporary
+/// variable holding it, with its declaration
+fn evaluate(value: &str, temps: &mut usize) -> (String, Vec<String>) {
+	if value.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '@' || c == '.') {
+		(value.to_string(), vec![])
+	} else {
+		let temp = format!("_value{}", temps);
+		*temps += 1;
+		let declaration = format!("var {} = {};", temp, value);
+		(temp, vec![declaration])
+	}
+}
+
+fn transpile_if_case(input: &str, temps: &mut usize) -> String {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"\bif\s*\(").unwrap();
+		static ref CASE : Regex = Regex::new(r"\s\bcase\b").unwrap();
+	}
+
+	let mut text = input.to_string();
+	let mut pos = 0;
+	while let Some(mat) = RE.find_at(&text, pos) {
+		pos = mat.end();
+		let open = mat.end() - 1;
+		let close = match utils::matching_bracket(&text, open) {
+			Some(close) => close,
+			None => continue,
+		};
+		let condition = &text[open + 1..close];
+		let case = match CASE.find_iter(condition).find(|case| is_top_level(condition, case.start())) {
+			Some(case) => case,
+			None => continue,
+		};
+		let subject = condition[..case.start()].trim();
+		let pattern = &condition[case.end()..];
+		let object = is_object(&text[..open], subject);
+		let map = map_type_arguments(&text[..open], subject);
+		let condition = match split_guard(pattern) {
+			Some((pattern, guard)) => format!("{} && {}", matches(subject, pattern, object, map.as_deref(), temps), guard),
+			None => matches(subject, pattern, object, map.as_deref(), temps),
+		};
+		text.replace_range(open + 1..close, &condition);
+	}
+	text
+}
+
+fn is_top_level(text: &str, pos: usize) -> bool {
+	let mut depth = 0;
+	for c in text[..pos].chars() {
+		match c {
+			'(' | '[' | '{' => depth += 1,
+			')' | ']' | '}' => depth -= 1,
+			_ => {}
+		}
+	}
+	depth == 0
+}
+
+/// Whether the position is in a pattern left for the switches, that of a
+/// `case` clause or of an arm of a switch expression, where the brackets
+/// are no collection literals nor constructor calls
+pub fn in_pattern(text: &str, pos: usize) -> bool {
+	lazy_static! {
+		static ref CASE : Regex = Regex::new(r"\bcase\b").unwrap();
+		static ref SWITCH : Regex = Regex::new(r"\bswitch\s*$").unwrap();
+	}
+
+	let before = &text[..pos];
+	if let Some(case) = CASE.find_iter(before).last() {
+		if pattern_continues(&before[case.end()..], &[':', ';']) {
+			return true;
+		}
+	}
+	let mut open = pos;
+	while let Some(enclosing) = utils::enclosing_open(text, open) {
+		open = enclosing;
+		if !text[open..].starts_with('{') {
+			continue;
+		}
+		let head = text[..open].trim_end();
+		let is_switch = head.ends_with(')') && utils::matching_open(text, head.len() - 1)
+			.map(|subject| SWITCH.is_match(&text[..subject]))
+			.unwrap_or(false);
+		if is_switch {
+			let arm = utils::split_top_level(&text[open + 1..pos], ',').pop().unwrap_or("");
+			return pattern_continues(arm, &[':', ';']) && !arm.contains("=>");
+		}
+	}
+	false
+}
+
+/// Whether the pattern starting the text goes on to its end, with no
+/// terminator out of brackets
+fn pattern_continues(text: &str, terminators: &[char]) -> bool {
+	let mut depth = 0;
+	for c in text.chars() {
+		match c {
+			'(' | '[' | '{' => depth += 1,
+			')' | ']' | '}' if depth == 0 => return false,
+			')' | ']' | '}' => depth -= 1,
+			_ if depth == 0 && terminators.contains(&c) => return false,
+			_ => {}
+		}
+	}
+	true
+}
+
+/// Whether the subject is a variable declared as `object` or `dynamic`
+/// in the text before it
+pub fn is_object(text: &str, subject: &str) -> bool {
+	if !subject.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '@') {
+		return false;
+	}
+	let re = Regex::new(&format!(r"\b(?:object|dynamic)\??\s+{}\b", regex::escape(subject))).unwrap();
+	re.is_match(text)
+}
+
+/// The type arguments of the map type the subject is declared with in
+/// the text before it, like `string, int` for `Dictionary<string, int> m`
+pub fn map_type_arguments(text: &str, subject: &str) -> Option<String> {
+	lazy_static! {
+		static ref MAP : Regex = Regex::new(r"\b(?:I?Dictionary|Map)<").unwrap();
+	}
+
+	if !subject.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '@') {
+		return None;
+	}
+	let declared = Regex::new(&format!(r"^>\??\s+{}\b", regex::escape(subject))).unwrap();
+	MAP.find_iter(text)
+		.filter_map(|mat| {
+			let close = utils::matching_bracket(text, mat.end() - 1)?;
+			if declared.is_match(&text[close..]) {
+				Some(text[mat.end()..close].to_string())
+			} else {
+				None
+			}
+		})
+		.last()
+}
+
+/// The condition that the subject, of type object if `object`, or a map
+/// with the type arguments `map`, matches the pattern
+fn matches(subject: &str, pattern: &str, object: bool, map: Option<&str>, temps: &mut usize) -> String {
+	let pattern = pattern.trim();
+	if pattern.starts_with('{') {
+		let (test, conditions) = map_pattern(pattern, map, temps);
+		format!("{} is {}{}", subject, test, conditions.iter().map(|condition| format!(" && {}", condition)).collect::<String>())
+	} else if object {
+		format!("{} is {}", subject, transpile_object_pattern(pattern))
+	} else {
+		format!("{} is {}", subject, transpile_pattern(pattern))
+	}
+}
+
+/// Lower the map pattern `{'key': p, ...}` into the type test of the map,
+/// and the conditions on the bound map. The map of the known type
+/// arguments `map`, like `string, int`, is tested as
+/// `IDictionary<string, int> _map0`, with the conditions
+/// `_map0.TryGetValue("key", out var _value1)` and `_value1 is p`.
+/// Since the generic interfaces are invariant, other maps are tested as
+/// `System.Collections.IDictionary _map0`, with the conditions
+/// `_map0.Contains("key")` and `_map0["key"] is p`.
+pub fn map_pattern(pattern: &str, map: Option<&str>, temps: &mut usize) -> (String, Vec<String>) {
+	let name = format!("_map{}", temps);
+	*temps += 1;
+	let entries = utils::split_top_level(&pattern[1..pattern.len() - 1], ',');
+	let entries = entries.iter()
+		.filter_map(|entry| {
+			let colon = entry.find(':')?;
+			Some((entry[..colon].trim(), entry[colon + 1..].trim()))
+		})
+		.collect::<Vec<(&str, &str)>>();
+	let value_type = map.map(|map| utils::split_type_arguments(map).pop().unwrap_or("object").to_string());
+	let test = match map {
+		Some(map) => format!("IDictionary<{}> {}", map, name),
+		None => format!("System.Collections.IDictionary {}", name),
+	};
+
+	let mut conditions = vec![];
+	for (key, value) in entries {
+		let value_name = match map {
+			Some(_) => {
+				let temp = format!("_value{}", temps);
+				*temps += 1;
+				conditions.push(format!("{}.TryGetValue({}, out var {})", name, key, temp));
+				temp
+			},
+			None => {
+				conditions.push(format!("{}.Contains({})", name, key));
+				format!("{}[{}]", name, key)
+			},
+		};
+		match value {
+			"_" => {},
+			_ if value.starts_with('{') => {
+				let nested = value_type.as_deref().and_then(map_arguments);
+				let (test, nested) = map_pattern(value, nested, temps);
+				conditions.push(format!("{} is {}", value_name, test));
+				conditions.extend(nested);
+			},
+			_ => {
+				let object = value_type.as_deref().map(is_object_type).unwrap_or(true);
+				conditions.push(format!("{} is {}", value_name, transpile(value, object)));
+			},
+		}
+	}
+	(test, conditions)
+}
+
+/// The type arguments of the map type, like `string, int` for
+/// `Dictionary<string, int>`
+fn map_arguments(map_type: &str) -> Option<&str> {
+	lazy_static! {
+		static ref MAP : Regex = Regex::new(r"^(?:I?Dictionary|Map)<(?P<args>.*)>\??$").unwrap();
+	}
+
+	MAP.captures(map_type.trim()).map(|cap| cap.name("args").unwrap().as_str())
+}
+
+fn is_object_type(value_type: &str) -> bool {
+	matches!(value_type.trim_end_matches('?'), "object" | "dynamic")
+}
+
+/// Transpile a dart pattern into a C# pattern
+///
+/// - `||` and `&&` become `or` and `and`
+/// - `final x` becomes `var x`, and `final T x` becomes `T x`
+/// - the object pattern `T(a: p, :var b)` becomes the property pattern
+///   `T { a: p, b: var b }`, and `:var b` in records becomes `b: var b`
+/// - the rest element `...rest` of list patterns becomes `.. var rest`
+/// - the null-check pattern `p?` becomes `not null and p`, and the
+///   null-assert pattern `p!` becomes `p`
+pub fn transpile_pattern(pattern: &str) -> String {
+	transpile(pattern, false)
+}
+
+/// Transpile a dart pattern matched against an `object?`, like the values
+/// of maps: its list patterns get the type test `System.Collections.IList and`,
+/// since C# applies list patterns to lists only, and the generic lists are
+/// invariant
+pub fn transpile_object_pattern(pattern: &str) -> String {
+	transpile(pattern, true)
+}
+
+fn transpile(pattern: &str, object: bool) -> String {
+	lazy_static! {
+		static ref FINAL : Regex = Regex::new(r"\bfinal\s+(?P<rest>[\w.@]+(?:<[\w\s,<>?.@]*>)?\??\s+[\w@]+|[\w@]+)").unwrap();
+		static ref SHORTHAND : Regex = Regex::new(r"(?P<pre>[(,]\s*):\s*(?P<pattern>(?:var|[\w.@]+(?:<[\w\s,<>?.@]*>)?\??)\s+(?P<name>[\w@]+))").unwrap();
+		static ref REST : Regex = Regex::new(r"\.\.\.(?:\s*(?P<name>[\w@]+))?").unwrap();
+		static ref OBJECT : Regex = Regex::new(r"\b(?P<type>[A-Z][\w.@]*(?:<[\w\s,<>?.@]*>)?)\(").unwrap();
+	}
+
+	let (pattern, guard) = match split_guard(pattern) {
+		Some((pattern, guard)) => (pattern, Some(guard)),
+		None => (pattern, None),
+	};
+	let mut result = pattern.replace("||", "or").replace("&&", "and");
+	result = FINAL.replace_all(&result, |cap: &Captures| -> String {
+		let rest = cap.name("rest").unwrap().as_str();
+		if rest.contains(char::is_whitespace) {
+			rest.to_string()
+		} else {
+			format!("var {}", rest)
+		}
+	}).to_string();
+	result = SHORTHAND.replace_all(&result, "$pre$name: $pattern").to_string();
+	result = REST.replace_all(&result, |cap: &Captures| -> String {
+		match cap.name("name") {
+			Some(name) if name.as_str() != "_" => format!(".. var {}", name.as_str()),
+			_ => String::from(".."),
+		}
+	}).to_string();
+
+	while let Some(cap) = OBJECT.captures(&result) {
+		let open = cap.get(0).unwrap().end() - 1;
+		let close = match utils::matching_bracket(&result, open) {
+			Some(close) => close,
+			None => break,
+		};
+		let fields = utils::split_top_level(&result[open + 1..close], ',').iter()
+			.map(|field| field.trim())
+			.filter(|field| !field.is_empty())
+			.collect::<Vec<&str>>()
+			.join(", ");
+		let fields = if fields.is_empty() { String::from("{ }") } else { format!("{{ {} }}", fields) };
+		result = format!("{} {}{}", &result[..open], fields, &result[close + 1..]);
+	}
+	if object {
+		result = type_list_patterns(&result);
+	}
+
+	let trimmed = result.trim_end();
+	result = if let Some(inner) = trimmed.strip_suffix('?') {
+		format!("not null and {}", inner)
+	} else if let Some(inner) = trimmed.strip_suffix('!') {
+		inner.to_string()
+	} else {
+		result
+	};
+	match guard {
+		Some(guard) => format!("{} when {}", result.trim_end(), guard),
+		None => result,
+	}
+}
+
+/// Prefix the list patterns out of brackets, and those of their elements,
+/// which are objects too, with `System.Collections.IList and`
+fn type_list_patterns(pattern: &str) -> String {
+	let mut result = String::new();
+	let mut pos = 0;
+	while let Some(offset) = pattern[pos..].find(['(', '[', '{']) {
+		let open = pos + offset;
+		let close = match utils::matching_bracket(pattern, open) {
+			Some(close) => close,
+			None => break,
+		};
+		result.push_str(&pattern[pos..open]);
+		if pattern[open..].starts_with('[') {
+			let elements = utils::split_top_level(&pattern[open + 1..close], ',').iter()
+				.map(|element| type_list_patterns(element))
+				.collect::<Vec<String>>();
+			result.push_str(&format!("System.Collections.IList and [{}]", elements.join(",")));
+		} else {
+			result.push_str(&pattern[open..close + 1]);
+		}
+		pos = close + 1;
+	}
+	result.push_str(&pattern[pos..]);
+	result
+}
+
+/// Split the guard `when ...` from the pattern
+pub fn split_guard(pattern: &str) -> Option<(&str, &str)> {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"\s\bwhen\s").unwrap();
+	}
+
+	let mat = RE.find(pattern)?;
+	Some((&pattern[..mat.start()], pattern[mat.end()..].trim()))
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::mask::mask;
+
+	fn transpile(input: &str) -> String {
+		let masked = mask(input);
+		masked.unmask(&transpile_patterns(&masked.text))
+	}
+
+	#[test]
+	fn destructuring() {
+		assert_eq!(
+			transpile(r"
+  var (a, b) = pair;
+  final (int x, (y, _)) = f();
+  var [first, ...rest, last] = list;
+  final Point(:x, y: int py) = point;
+  var {'id': int id, 'name': name!} = json;
+  final (p as Point, [a, ...]) = pair;"),
+			r"
+  var (a, b) = pair;
+  (int x, var (y, _)) = f();
+  var first = list[0];
+  var rest = list.Skip(1).Take(list.Count - 2).ToList();
+  var last = list[list.Count - 1];
+  var x = point.x;
+  int py = point.y;
+  int id = json['id'];
+  var name = (json['name'] ?? throw new InvalidOperationException());
+  var p = ((Point)pair.Item1);
+  var a = pair.Item2[0];"
+		);
+	}
+
+	#[test]
+	fn if_case() {
+		assert_eq!(
+			transpile(r"
+  if (pair case (int a, final b) when a > 0) {}
+  if (shape case Circle(:var radius) || Square(length: 1)) {}
+  if (json case {'name': String name, 'tags': [_, ...]}) {}"),
+			r"
+  if (pair is (int a, var b) && a > 0) {}
+  if (shape is Circle { radius: var radius } or Square { length: 1 }) {}
+  if (json is System.Collections.IDictionary _map0 && _map0.Contains('name') && _map0['name'] is String name && _map0.Contains('tags') && _map0['tags'] is System.Collections.IList and [_, ..]) {}"
+		);
+	}
+}
@@ -0,0 +1,286 @@
+use regex::Regex;
+use std::collections::HashMap;
+use crate::config::Config;
+use crate::keywords;
+use crate::utils;
+
+/// Transpile the dart 3 records into C# value tuples, which must be run
+/// before the types are mapped.
+///
+/// - the record types `(int, String)` and `({int x, int y})` become
+///   `(int, String)` and `(int x, int y)`, and `(int,)` becomes `ValueTuple<int>`
+/// - the record literals stay, except `(a,)` which becomes `ValueTuple.Create(a)`
+/// - the positional fields `$1`, `$2` become `Item1`, `Item2`
+///
+/// If `config.record_structs` is set, the record types with named fields
+/// used at least that many times are promoted to generated record structs,
+/// like `public record struct XYRecord(int x, int y);`, and the literals
+/// with the same names become constructor calls.
+pub fn transpile_records(input: &str, config: &Config) -> String {
+	lazy_static! {
+		static ref POSITIONAL : Regex = Regex::new(r"\.\s*\$(?P<index>\d+)\b").unwrap();
+	}
+
+	let result = POSITIONAL.replace_all(input, ".Item$index").to_string();
+	let mut result = transpile_record_types(&result);
+	if let Some(threshold) = config.record_structs {
+		result = promote_records(&result, threshold);
+	}
+	result
+}
+
+fn transpile_record_types(input: &str) -> String {
+	let mut text = input.to_string();
+	for (open, close) in record_types(input).into_iter().rev() {
+		let elements = elements(&text[open + 1..close]);
+		let replacement = if elements.len() == 1 && !text[open + 1..close].contains('{') {
+			format!("ValueTuple<{}>", elements[0])
+		} else {
+			format!("({})", elements.join(", "))
+		};
+		text.replace_range(open..close + 1, &replacement);
+	}
+	for (open, close) in single_literals(&text).into_iter().rev() {
+		let element = text[open + 1..close].trim().trim_end_matches(',').trim_end().to_string();
+		text.replace_range(open..close + 1, &format!("ValueTuple.Create({})", element));
+	}
+	text
+}
+
+/// The elements of a record type, with the named ones out of the braces
+fn elements(content: &str) -> Vec<String> {
+	let mut result = vec![];
+	for element in utils::split_type_arguments(content) {
+		let element = element.trim();
+		if let Some(named) = element.strip_prefix('{') {
+			let named = named.strip_suffix('}').unwrap_or(named);
+			result.extend(utils::split_type_arguments(named).iter()
+				.map(|element| element.trim().to_string())
+				.filter(|element| !element.is_empty()));
+		} else if !element.is_empty() {
+			result.push(element.to_string());
+		}
+	}
+	result
+}
+
+/// Find the ranges of the parentheses of record types. They are told apart
+/// from the parenthesized expressions and the record literals by their
+/// content, which can only be types, optionally with names, and by what
+/// surrounds them: `as` or `is` before, a declared name or the end of the
+/// type arguments after.
+pub fn record_types(input: &str) -> Vec<(usize, usize)> {
+	lazy_static! {
+		static ref ELEMENT : Regex = Regex::new(r"^(?:[\w.@]+(?:<[\w\s,<>?.@()]*>)?|\([\w\s,<>?.@(){}]*\))\??(?:\s+[\w@]+)?$").unwrap();
+		static ref AFTER : Regex = Regex::new(r"^\??\s*(?:(?P<word>[\w@]+)|[>,])").unwrap();
+		static ref AFTER_OPERATOR : Regex = Regex::new(r"(?:^|[^\w.@])(?:as|is!?)\s+$").unwrap();
+	}
+
+	let is_type = |content: &str| {
+		let mut elements = utils::split_type_arguments(content);
+		if elements.len() < 2 && !content.trim_start().starts_with('{') {
+			return false;
+		}
+		if elements.last().map(|last| last.trim().is_empty()) == Some(true) {
+			elements.pop();
+		}
+		elements.iter().all(|element| {
+			let element = element.trim();
+			match element.strip_prefix('{') {
+				Some(named) => named.ends_with('}') && utils::split_type_arguments(&named[..named.len() - 1]).iter()
+					.all(|element| element.trim().is_empty() || ELEMENT.is_match(element.trim()) && element.trim().contains(char::is_whitespace)),
+				None => ELEMENT.is_match(element),
+			}
+		})
+	};
+
+	let mut result = vec![];
+	for (open, _) in input.match_indices('(') {
+		let before = input[..open].trim_end();
+		let after_operator = AFTER_OPERATOR.is_match(&input[..open]);
+		if !after_operator && before.ends_with(|c: char| c.is_alphanumeric() || matches!(c, '_' | '@' | ')' | ']' | '$')) {
+			continue;
+		}
+		if before.ends_with('>') && !input[..open].ends_with(char::is_whitespace) {
+			continue;
+		}
+		let close = match utils::matching_bracket(input, open) {
+			Some(close) => close,
+			None => continue,
+		};
+		if !is_type(&input[open + 1..close]) {
+			continue;
+		}
+		let in_type_arguments = before.ends_with('<');
+		let followed = AFTER.captures(&input[close + 1..]).map(|cap| match cap.name("word") {
+			Some(word) => {
+				let word = word.as_str();
+				!keywords::is_dart_keyword(word) || keywords::is_declaring_keyword(word)
+			},
+			None => true,
+		}).unwrap_or(false);
+		if in_type_arguments || after_operator || followed {
+			result.push((open, close));
+		}
+	}
+	result
+}
+
+/// Find the single element record literals `(a,)`
+fn single_literals(input: &str) -> Vec<(usize, usize)> {
+	let mut result = vec![];
+	for (open, _) in input.match_indices('(') {
+		if is_call(&input[..open]) {
+			continue;
+		}
+		let close = match utils::matching_bracket(input, open) {
+			Some(close) => close,
+			None => continue,
+		};
+		let elements = utils::split_top_level(&input[open + 1..close], ',');
+		if elements.len() == 2 && elements[1].trim().is_empty() && !elements[0].trim().is_empty() {
+			result.push((open, close));
+		}
+	}
+	result
+}
+
+/// Whether the parentheses after this are those of a call, rather
+/// than a record literal
+fn is_call(before: &str) -> bool {
+	lazy_static! {
+		static ref WORD : Regex = Regex::new(r"[\w@]+$").unwrap();
+	}
+
+	let before = before.trim_end();
+	match WORD.find(before) {
+		Some(word) => !keywords::is_dart_keyword(word.as_str().trim_start_matches('@')),
+		None => before.ends_with([')', ']', '>', '$']) && !before.ends_with("=>"),
+	}
+}
+
+/// Promote the record types with named fields used at least `threshold`
+/// times to record structs declared at the end of the file
+fn promote_records(input: &str, threshold: usize) -> String {
+	lazy_static! {
+		static ref NAMED : Regex = Regex::new(r"^(?:[\w.@]+(?:<[\w\s,<>?.@()]*>)?|\([\w\s,<>?.@()]*\))\??\s+(?P<name>[\w@]+)$").unwrap();
+		static ref LITERAL_FIELD : Regex = Regex::new(r"^(?P<name>[\w@]+)\s*:").unwrap();
+	}
+
+	let fields_of = |content: &str| -> Option<Vec<String>> {
+		utils::split_type_arguments(content).iter()
+			.map(|element| NAMED.captures(element.trim()).map(|cap| cap["name"].to_string()))
+			.collect()
+	};
+
+	let mut shapes : Vec<(String, Vec<String>, usize)> = vec![];
+	let types = record_types(input);
+	for (open, close) in types.iter() {
+		let content = &input[open + 1..*close];
+		if let Some(fields) = fields_of(content) {
+			match shapes.iter_mut().find(|(shape, _, _)| shape == content) {
+				Some(shape) => shape.2 += 1,
+				None => shapes.push((content.to_string(), fields, 1)),
+			}
+		}
+	}
+	let promoted = shapes.into_iter()
+		.filter(|(_, _, count)| *count >= threshold)
+		.map(|(shape, fields, _)| {
+			let name = format!("{}Record", fields.iter().map(|field| {
+				let field = field.trim_start_matches('@');
+				let mut chars = field.chars();
+				match chars.next() {
+					Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+					None => String::new(),
+				}
+			}).collect::<String>());
+			(shape, (name, fields))
+		})
+		.collect::<HashMap<String, (String, Vec<String>)>>();
+	if promoted.is_empty() {
+		return input.to_string();
+	}
+
+	let mut text = input.to_string();
+	let mut replacements = vec![];
+	for (open, close) in types.iter() {
+		if let Some((name, _)) = promoted.get(&input[open + 1..*close]) {
+			replacements.push((*open, *close + 1, name.clone()));
+		}
+	}
+	for (open, _) in input.match_indices('(') {
+		if is_call(&input[..open])
+			|| types.iter().any(|(start, _)| *start == open) {
+			continue;
+		}
+		let close = match utils::matching_bracket(input, open) {
+			Some(close) => close,
+			None => continue,
+		};
+		let names = utils::split_top_level(&input[open + 1..close], ',').iter()
+			.map(|element| LITERAL_FIELD.captures(element.trim()).map(|cap| cap["name"].to_string()))
+			.collect::<Option<Vec<String>>>();
+		if let Some(names) = names {
+			if let Some((name, _)) = promoted.values().find(|(_, fields)| *fields == names) {
+				replacements.push((open, open, format!("new {}", name)));
+			}
+		}
+	}
+	replacements.sort_by_key(|(start, _, _)| *start);
+	for (start, end, replacement) in replacements.into_iter().rev() {
+		text.replace_range(start..end, &replacement);
+	}
+
+	let mut declarations = promoted.iter()
+		.map(|(shape, (name, _))| format!("public record struct {}({});", name, shape))
+		.collect::<Vec<String>>();
+	declarations.sort();
+	format!("{}\n\n{}\n", text.trim_end(), declarations.join("\n"))
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn record_types_and_fields() {
+		assert_eq!(
+			transpile_records(r"
+(int, String) f((int, {String name}) a, List<(int, int)> b) {
+  ({int x, int y}) p = (x: 1, y: 2);
+  var single = (a.$1,);
+  (int,) one = single;
+  if (a is! (int, String)) return (p.x, a.name) as (int, String);
+}", &Config::default()),
+			r"
+(int, String) f((int, String name) a, List<(int, int)> b) {
+  (int x, int y) p = (x: 1, y: 2);
+  var single = ValueTuple.Create(a.Item1);
+  ValueTuple<int> one = single;
+  if (a is! (int, String)) return (p.x, a.name) as (int, String);
+}"
+		);
+	}
+
+	#[test]
+	fn records_promoted() {
+		let config = Config { record_structs: Some(2), ..Config::default() };
+		assert_eq!(
+			transpile_records(r"
+({int x, int y}) f(({int x, int y}) p, (int, int) q) {
+  return (x: p.x + 1, y: p.y);
+}
+({int x, int y}) origin() => (x: 0, y: 0);", &config),
+			r"
+XYRecord f(XYRecord p, (int, int) q) {
+  return new XYRecord(x: p.x + 1, y: p.y);
+}
+XYRecord origin() => new XYRecord(x: 0, y: 0);
+
+public record struct XYRecord(int x, int y);
+"
+		);
+	}
+}
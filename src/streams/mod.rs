@@ -0,0 +1,197 @@
+use regex::Captures;
+use regex::Regex;
+use std::collections::HashSet;
+use crate::config::Config;
+use crate::diagnostics;
+use crate::diagnostics::DiagnosticKind;
+
+/// Transpile the streams into async enumerables, which must be run after
+/// the types are mapped, `Stream` to `IAsyncEnumerable`, and before `new`
+/// is inserted. C# has no push-based streams, so the stream controllers
+/// and the subscriptions are classes declared at the end of the file by
+/// `append_support_classes`, backed by `System.Threading.Channels`.
+///
+/// - `stream.listen(onData)` becomes `stream.Listen(onData)`, which
+///   starts an `await foreach` in the background
+/// - `StreamSubscription<T>` becomes `StreamSubscription`, and
+///   `subscription.cancel()` becomes `subscription.Cancel()`
+/// - the members of the stream controllers, `add`, `addError`, `close`,
+///   `stream` and `isClosed` are capitalized, and `sink` is removed
+/// - `StreamController.broadcast()` is reported, since a channel
+///   delivers each event to one listener only
+pub fn transpile_streams(input: &str) -> String {
+	lazy_static! {
+		static ref LISTEN : Regex = Regex::new(r"(?P<dot>\??\.\s*)listen\s*\(").unwrap();
+		static ref BROADCAST : Regex = Regex::new(r"\bStreamController(?P<args><[\w\s,<>?.@()]*>)?\s*\.\s*broadcast\s*\(").unwrap();
+		static ref SUBSCRIPTION : Regex = Regex::new(r"(?P<pre>\.\s*)?\bStreamSubscription(?:<[\w\s,<>?.@()]*>)?").unwrap();
+		static ref CONTROLLER : Regex = Regex::new(r"\bStreamController\b(?:<[\w\s,<>?.@()]*>)?\??\s+(?P<name>[\w@]+)|(?P<assigned>[\w@]+)\s*=\s*(?:new\s+)?StreamController\b").unwrap();
+		static ref SUBSCRIBED : Regex = Regex::new(r"\bStreamSubscription\??\s+(?P<name>[\w@]+)|(?P<assigned>[\w@]+)\s*=\s*[^;]*\.Listen\s*\(").unwrap();
+	}
+
+	let result = LISTEN.replace_all(input, "${dot}Listen(");
+	let result = BROADCAST.replace_all(&result, |cap: &Captures| -> String {
+		let args = cap.name("args").map(|args| args.as_str()).unwrap_or("");
+		format!("{}StreamController{}(", diagnostics::marker(DiagnosticKind::BroadcastStream), args)
+	});
+	let result = SUBSCRIPTION.replace_all(&result, |cap: &Captures| -> String {
+		match cap.name("pre") {
+			Some(_) => cap.get(0).unwrap().as_str().to_string(),
+			None => String::from("StreamSubscription"),
+		}
+	});
+	let controllers = declared_names(&CONTROLLER, &result);
+	let result = rename_members(&result, &controllers, r"(?:\s*\.\s*sink)?", |member| match member {
+		"add" => Some("Add"),
+		"addError" => Some("AddError"),
+		"close" => Some("Close"),
+		"stream" => Some("Stream"),
+		"isClosed" => Some("IsClosed"),
+		_ => None,
+	});
+	let subscriptions = declared_names(&SUBSCRIBED, &result);
+	rename_members(&result, &subscriptions, "", |member| match member {
+		"cancel" => Some("Cancel"),
+		_ => None,
+	})
+}
+
+/// Declare the support classes at the end of the file if the streams are
+/// used, which must be run after the other passes, so that they leave the
+/// classes alone
+pub fn append_support_classes(input: &str, config: &Config) -> String {
+	if !input.contains(".Listen(") && !input.contains("StreamController") {
+		return input.to_string();
+	}
+	format!("{}\n\n{}", input.trim_end(), support_classes(if config.null_safety { "?" } else { "" }))
+}
+
+/// The names of the variables declared or assigned by the regex,
+/// with the name in `name` or `assigned`
+fn declared_names(re: &Regex, input: &str) -> HashSet<String> {
+	re.captures_iter(input)
+		.filter_map(|cap| cap.name("name").or_else(|| cap.name("assigned")).map(|name| name.as_str().to_string()))
+		.collect()
+}
+
+/// Rename the members of the variables, skipping the optional
+/// member before them, like `sink` in `controller.sink.add`
+fn rename_members<F>(input: &str, names: &HashSet<String>, skipped: &str, rename: F) -> String
+	where F: Fn(&str) -> Option<&'static str> {
+	if names.is_empty() {
+		return input.to_string();
+	}
+	let names = names.iter().map(|name| regex::escape(name)).collect::<Vec<String>>().join("|");
+	let member = Regex::new(&format!(r"\b(?P<name>{}){}(?P<dot>\s*\??\.\s*)(?P<member>\w+)\b", names, skipped)).unwrap();
+	member.replace_all(input, |cap: &Captures| -> String {
+		match rename(&cap["member"]) {
+			Some(renamed) => format!("{}{}{}", &cap["name"], &cap["dot"], renamed),
+			None => cap.get(0).unwrap().as_str().to_string(),
+		}
+	}).to_string()
+}
+
+fn support_classes(nullable: &str) -> String {
+	format!(r"public class StreamController<T>
+{{
+	readonly System.Threading.Channels.Channel<T> _channel = System.Threading.Channels.Channel.CreateUnbounded<T>();
+
+	public IAsyncEnumerable<T> Stream => _channel.Reader.ReadAllAsync();
+
+	public bool IsClosed {{ get; private set; }}
+
+	public void Add(T value) => _channel.Writer.TryWrite(value);
+
+	/// A channel can only fail when it is completed, so the error ends the stream
+	public void AddError(Exception error)
+	{{
+		IsClosed = true;
+		_channel.Writer.TryComplete(error);
+	}}
+
+	public void Close()
+	{{
+		IsClosed = true;
+		_channel.Writer.TryComplete();
+	}}
+}}
+
+public class StreamSubscription
+{{
+	readonly System.Threading.CancellationTokenSource _cancellation = new System.Threading.CancellationTokenSource();
+
+	public Task Done {{ get; }}
+
+	public StreamSubscription(Func<System.Threading.CancellationToken, Task> listen)
+	{{
+		Done = listen(_cancellation.Token);
+	}}
+
+	public Task Cancel()
+	{{
+		_cancellation.Cancel();
+		return Task.CompletedTask;
+	}}
+}}
+
+public static class DartStreams
+{{
+	public static StreamSubscription Listen<T>(this IAsyncEnumerable<T> stream, Action<T> onData,
+		Action<Exception>{} onError = null, Action{} onDone = null)
+	{{
+		return new StreamSubscription(async cancellation =>
+		{{
+			try
+			{{
+				await foreach (var value in stream.WithCancellation(cancellation))
+				{{
+					onData(value);
+				}}
+				onDone?.Invoke();
+			}}
+			catch (OperationCanceledException)
+			{{
+			}}
+			catch (Exception error) when (onError != null)
+			{{
+				onError(error);
+			}}
+		}});
+	}}
+}}
+", nullable, nullable)
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn streams_transpiled() {
+		let (result, diagnostics) = diagnostics::extract(&transpile_streams(r"
+  readonly StreamController<int> _controller = StreamController<int>.broadcast();
+  StreamSubscription<int>? _subscription;
+  IAsyncEnumerable<int> get values => _controller.stream;
+  void start() {
+    _subscription = values.listen((v) => print(v), onDone: () => _controller.close());
+    _controller.sink.add(1);
+  }
+  void stop() => _subscription?.cancel();"));
+		assert_eq!(
+			result,
+			r"
+  readonly StreamController<int> _controller = StreamController<int>();
+  StreamSubscription? _subscription;
+  IAsyncEnumerable<int> get values => _controller.Stream;
+  void start() {
+    _subscription = values.Listen((v) => print(v), onDone: () => _controller.Close());
+    _controller.Add(1);
+  }
+  void stop() => _subscription?.Cancel();"
+		);
+		let result = append_support_classes(&result, &Config::default());
+		assert!(result.contains("Action<Exception>? onError = null, Action? onDone = null)"));
+		assert_eq!(diagnostics.iter().map(|d| (d.kind, d.line)).collect::<Vec<_>>(),
+			vec![(DiagnosticKind::BroadcastStream, 2)]);
+	}
+}
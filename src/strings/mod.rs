@@ -0,0 +1,283 @@
+use crate::mask::Masked;
+use crate::mask::SpanKind;
+
+/// Stands for an interpolated expression while a string is transpiled,
+/// since the expressions are not masked
+const HOLE : char = '\u{E000}';
+
+/// Transpile the dart string literals into C# string literals.
+///
+/// - Strings with `$name` or `${expr}` become interpolated strings `$"..."`,
+///   with the `{` and `}` of the text doubled
+/// - Single quoted strings, including single characters, become double
+///   quoted strings, with the escapes converted
+/// - Raw strings and multi-line strings become verbatim strings `@"..."`
+/// - Adjacent strings are concatenated by `+`
+///
+/// The string literals are masked, so this works on their spans. The parts
+/// of an interpolated string are transpiled together, and the expressions
+/// between them are left to the other passes.
+pub fn transpile_strings(masked: &mut Masked) {
+	let mut literals : Vec<Vec<usize>> = vec![];
+	let mut open = vec![];
+	for (index, span) in masked.spans.iter().enumerate() {
+		match span.kind {
+			SpanKind::Str => literals.push(vec![index]),
+			SpanKind::StrStart => {
+				open.push(literals.len());
+				literals.push(vec![index]);
+			},
+			SpanKind::StrMiddle => literals[*open.last().unwrap()].push(index),
+			SpanKind::StrEnd => literals[open.pop().unwrap()].push(index),
+			_ => {}
+		}
+	}
+	for parts in literals {
+		let literal = parts.iter()
+			.map(|index| masked.spans[*index].text.as_str())
+			.collect::<Vec<&str>>()
+			.join(&HOLE.to_string());
+		let transpiled = transpile_string(&literal);
+		for (index, part) in parts.iter().zip(transpiled.split(HOLE)) {
+			masked.spans[*index].text = part.to_string();
+		}
+	}
+	masked.text = concatenate_adjacent(masked);
+}
+
+/// Double the parentheses of the interpolated expressions which have a `:`,
+/// which would be taken as the start of the format in C#, so that they are
+/// kept when unmasked. This must be run after the other passes.
+pub fn parenthesize_interpolations(masked: &Masked, text: &str) -> String {
+	let mut result = text.to_string();
+	for (open, close) in masked.interpolation_holes(text).into_iter().rev() {
+		let mut depth = 0;
+		let has_colon = text[open + 1..close].chars().any(|c| {
+			match c {
+				'(' | '[' | '{' => depth += 1,
+				')' | ']' | '}' => depth -= 1,
+				_ => {}
+			}
+			c == ':' && depth == 0
+		});
+		if has_colon {
+			result.insert(close, ')');
+			result.insert(open, '(');
+		}
+	}
+	result
+}
+
+/// Put `+` between the strings separated only by spaces and comments
+fn concatenate_adjacent(masked: &Masked) -> String {
+	let is_kind = |c: char, kind: SpanKind| masked.span(c).map(|span| span.kind == SpanKind::Str || span.kind == kind).unwrap_or(false);
+	let is_comment = |c: char| masked.span(c).map(|span| matches!(span.kind,
+		SpanKind::LineComment | SpanKind::DocComment | SpanKind::BlockComment)).unwrap_or(false);
+	let mut result = String::with_capacity(masked.text.len());
+	let mut pending = String::new();
+	let mut after_string = false;
+	for c in masked.text.chars() {
+		if after_string && (c.is_whitespace() || is_comment(c)) {
+			pending.push(c);
+			continue;
+		}
+		if after_string && is_kind(c, SpanKind::StrStart) {
+			result.push_str(" +");
+			if pending.is_empty() {
+				pending.push(' ');
+			}
+		}
+		result.push_str(&pending);
+		pending.clear();
+		result.push(c);
+		after_string = is_kind(c, SpanKind::StrEnd);
+	}
+	result.push_str(&pending);
+	result
+}
+
+enum Part {
+	Text(String),
+	Expr(String),
+}
+
+/// Transpile a single dart string literal
+fn transpile_string(literal: &str) -> String {
+	let raw = literal.starts_with('r');
+	let body = if raw { &literal[1..] } else { literal };
+	let quote = match body.chars().next() {
+		Some(quote) => quote,
+		None => return literal.to_string(),
+	};
+	let triple = body.starts_with(&quote.to_string().repeat(3));
+	let delimiter = if triple { quote.to_string().repeat(3) } else { quote.to_string() };
+	let content = &body[delimiter.len()..];
+	let content = content.strip_suffix(delimiter.as_str()).unwrap_or(content);
+	let content = if triple { skip_first_blank_line(content) } else { content };
+
+	let parts = parse_parts(content, raw);
+	let interpolated = parts.iter().any(|part| matches!(part, Part::Expr(_)));
+	let verbatim = raw || (triple && content.contains('\n'));
+	let mut result = String::new();
+	if interpolated {
+		result.push('$');
+	}
+	if verbatim {
+		result.push('@');
+	}
+	result.push('"');
+	for part in parts {
+		match part {
+			Part::Text(text) => for c in text.chars() {
+				match c {
+					'{' | '}' if interpolated => {
+						result.push(c);
+						result.push(c);
+					},
+					'"' if verbatim => result.push_str("\"\""),
+					_ if verbatim => result.push(c),
+					_ => escape(c, &mut result),
+				}
+			},
+			Part::Expr(expr) => {
+				result.push('{');
+				result.push_str(&expr);
+				result.push('}');
+			},
+		}
+	}
+	result.push('"');
+	result
+}
+
+/// In a triple quoted string, the first line is dropped
+/// if it has only spaces
+fn skip_first_blank_line(content: &str) -> &str {
+	let blank = content.trim_start_matches([' ', '\t']);
+	if let Some(rest) = blank.strip_prefix("\r\n") {
+		rest
+	} else if let Some(rest) = blank.strip_prefix('\n') {
+		rest
+	} else {
+		content
+	}
+}
+
+fn escape(c: char, result: &mut String) {
+	match c {
+		'\\' => result.push_str("\\\\"),
+		'"' => result.push_str("\\\""),
+		'\n' => result.push_str("\\n"),
+		'\r' => result.push_str("\\r"),
+		'\t' => result.push_str("\\t"),
+		'\u{8}' => result.push_str("\\b"),
+		'\u{c}' => result.push_str("\\f"),
+		'\u{b}' => result.push_str("\\v"),
+		'\0' => result.push_str("\\0"),
+		_ if c.is_control() => result.push_str(&format!("\\u{:04X}", c as u32)),
+		_ => result.push(c),
+	}
+}
+
+/// Split the content of a string into texts, with the escapes decoded,
+/// and interpolated expressions
+fn parse_parts(content: &str, raw: bool) -> Vec<Part> {
+	let mut parts = vec![];
+	let mut text = String::new();
+	let mut i = 0;
+	while i < content.len() {
+		let rest = &content[i..];
+		let c = rest.chars().next().unwrap();
+		if raw {
+			text.push(c);
+			i += c.len_utf8();
+		} else if c == '\\' {
+			let (decoded, length) = decode_escape(&rest[1..]);
+			text.extend(decoded);
+			i += 1 + length;
+		} else if rest.starts_with("${") && rest[2..].starts_with(HOLE) {
+			parts.push(Part::Text(std::mem::take(&mut text)));
+			parts.push(Part::Expr(HOLE.to_string()));
+			i += 2 + HOLE.len_utf8() + rest[2 + HOLE.len_utf8()..].starts_with('}') as usize;
+		} else if rest.starts_with('$') && rest[1..].starts_with(HOLE) {
+			parts.push(Part::Text(std::mem::take(&mut text)));
+			parts.push(Part::Expr(HOLE.to_string()));
+			i += 1 + HOLE.len_utf8();
+		} else {
+			text.push(c);
+			i += c.len_utf8();
+		}
+	}
+	parts.push(Part::Text(text));
+	parts.retain(|part| !matches!(part, Part::Text(text) if text.is_empty()));
+	parts
+}
+
+/// Decode the escape after a `\`, returning the character,
+/// if any, and the length of the escape after the `\`
+fn decode_escape(rest: &str) -> (Option<char>, usize) {
+	let c = match rest.chars().next() {
+		Some(c) => c,
+		None => return (Some('\\'), 0),
+	};
+	let hex = |digits: &str| u32::from_str_radix(digits, 16).ok().and_then(std::char::from_u32);
+	match c {
+		'n' => (Some('\n'), 1),
+		'r' => (Some('\r'), 1),
+		't' => (Some('\t'), 1),
+		'b' => (Some('\u{8}'), 1),
+		'f' => (Some('\u{c}'), 1),
+		'v' => (Some('\u{b}'), 1),
+		'x' if rest.len() >= 3 => (hex(&rest[1..3]), 3),
+		'u' if rest[1..].starts_with('{') => match rest.find('}') {
+			Some(close) => (hex(&rest[2..close]), close + 1),
+			None => (Some('u'), 1),
+		},
+		'u' if rest.len() >= 5 => (hex(&rest[1..5]), 5),
+		_ => (Some(c), c.len_utf8()),
+	}
+}
+
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::mask;
+
+	fn transpile(input: &str) -> String {
+		let mut masked = mask::mask(input);
+		transpile_strings(&mut masked);
+		masked.unmask(&parenthesize_interpolations(&masked, &masked.text))
+	}
+
+	#[test]
+	fn simple_strings() {
+		assert_eq!(transpile(r#"f('c', 'it\'s "x"', "a\tb\x41\u{1F600}");"#),
+			r#"f("c", "it's \"x\"", "a\tbA😀");"#);
+	}
+
+	#[test]
+	fn interpolated_strings() {
+		assert_eq!(transpile(r#"print('Hello $name ${a + b}! {x} \$y');"#),
+			r#"print($"Hello {name} {a + b}! {{x}} $y");"#);
+		assert_eq!(transpile(r#"print('a ${b ? '}' : "${c}"} d');"#),
+			r#"print($"a {(b ? "}" : $"{c}")} d");"#);
+	}
+
+	#[test]
+	fn verbatim_strings() {
+		assert_eq!(transpile(r#"var a = r'\d+"'; var b = '''
+line "1"
+$x {y}''';"#),
+			"var a = @\"\\d+\"\"\"; var b = $@\"line \"\"1\"\"\n{x} {{y}}\";");
+	}
+
+	#[test]
+	fn adjacent_strings() {
+		assert_eq!(transpile("var a = 'x' 'y'\n    \"z\";"),
+			"var a = \"x\" + \"y\" +\n    \"z\";");
+		assert_eq!(transpile("f('x' // c\n  'y' /* d */ 'z', 'w' /* e */);"),
+			"f(\"x\" + // c\n  \"y\" + /* d */ \"z\", \"w\" /* e */);");
+	}
+}
@@ -0,0 +1,414 @@
+use regex::Regex;
+use std::collections::HashMap;
+use crate::mask;
+use crate::patterns;
+use crate::utils;
+
+/// Transpile the switch statements and the switch expressions of dart 3.
+///
+/// In a switch statement:
+/// - a non-empty case without a terminator gets a `break`, since C#
+///   allows no implicit fallthrough, and neither does dart 3
+/// - `continue label;` jumping to a labeled case becomes `goto case`
+/// - the patterns of the cases are transpiled into C# patterns
+///
+/// The switch expression `switch (x) { p => a, _ => b }` becomes
+/// `x switch { p => a, _ => b }`. Nested switches are transpiled first.
+///
+/// Map patterns become a type test and a guard, see `patterns::map_pattern`.
+pub fn transpile_switches(input: &str) -> String {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"\bswitch\s*\(").unwrap();
+		static ref TEMP : Regex = Regex::new(r"\b_(?:map|value)(\d+)\b").unwrap();
+	}
+
+	let mut text = input.to_string();
+	let mut temps = TEMP.captures_iter(input)
+		.filter_map(|cap| cap[1].parse::<usize>().ok())
+		.max()
+		.map(|max| max + 1)
+		.unwrap_or(0);
+	let starts = RE.find_iter(input).map(|mat| mat.start()).collect::<Vec<usize>>();
+	for start in starts.into_iter().rev() {
+		if let Some(result) = transpile_switch(&text, start, &mut temps) {
+			text = result;
+		}
+	}
+	text
+}
+
+/// A `case` or `default` clause of a switch statement
+struct Clause {
+	/// The labels before the clause, for `continue label;`
+	labels : Vec<String>,
+	/// The pattern with the guard, None for `default`
+	pattern : Option<String>,
+	/// Where the labels, or the clause if there are no labels, start
+	start : usize,
+	/// Where the body after `:` starts
+	body_start : usize,
+	/// Where the body ends
+	end : usize,
+}
+
+fn transpile_switch(text: &str, start: usize, temps: &mut usize) -> Option<String> {
+	let open = start + text[start..].find('(')?;
+	let close = utils::matching_bracket(text, open)?;
+	let body_open = utils::skip_spaces(text, close + 1);
+	if !text[body_open..].starts_with('{') {
+		return None;
+	}
+	let body_close = utils::matching_bracket(text, body_open)?;
+	let body = &text[body_open + 1..body_close];
+	let object = patterns::is_object(&text[..start], text[open + 1..close].trim());
+	let map = patterns::map_type_arguments(&text[..start], text[open + 1..close].trim());
+
+	if is_statement_body(body) {
+		let clauses = parse_clauses(body);
+		let body = transpile_clauses(body, &clauses, object, map.as_deref(), temps);
+		Some(format!("{}{}{}", &text[..body_open + 1], body, &text[body_close..]))
+	} else {
+		let subject = text[open + 1..close].trim();
+		let subject = if subject.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '.' || c == '@') {
+			subject.to_string()
+		} else {
+			format!("({})", subject)
+		};
+		let arms = utils::split_top_level(body, ',').iter().map(|arm| {
+			match arrow_position(arm) {
+				Some(arrow) => {
+					let pattern = &arm[..arrow];
+					let space = &pattern[..pattern.len() - pattern.trim_start().len()];
+					format!("{}{} {}", space, case_pattern(pattern.trim(), object, map.as_deref(), temps), arm[arrow..].trim_start())
+				},
+				None => arm.to_string(),
+			}
+		}).collect::<Vec<String>>().join(",");
+		Some(format!("{}{} switch {{{}{}", &text[..start], subject, arms, &text[body_close..]))
+	}
+}
+
+/// Whether the body is that of a switch statement, i.e. empty or starting
+/// with a clause, possibly labeled, after the comments
+fn is_statement_body(body: &str) -> bool {
+	lazy_static! {
+		static ref LABEL : Regex = Regex::new(r"^(?P<label>\w+)\s*:").unwrap();
+	}
+
+	let mut first = body.trim_start_matches(|c: char| c.is_whitespace() || mask::span_index(c).is_some());
+	while let Some(cap) = LABEL.captures(first) {
+		if &cap["label"] == "default" {
+			break;
+		}
+		first = first[cap.get(0).unwrap().end()..]
+			.trim_start_matches(|c: char| c.is_whitespace() || mask::span_index(c).is_some());
+	}
+	let first_word = first.split(|c: char| !c.is_alphanumeric() && c != '_').next().unwrap_or("");
+	first.is_empty() || first_word == "case" || first_word == "default"
+}
+
+/// The position of the first `=>` out of brackets
+fn arrow_position(arm: &str) -> Option<usize> {
+	let mut depth = 0;
+	for (i, c) in arm.char_indices() {
+		match c {
+			'(' | '[' | '{' => depth += 1,
+			')' | ']' | '}' => depth -= 1,
+			'=' if depth == 0 && arm[i..].starts_with("=>") => return Some(i),
+			_ => {}
+		}
+	}
+	None
+}
+
+/// Find the clauses of the body of a switch statement
+fn parse_clauses(body: &str) -> Vec<Clause> {
+	lazy_static! {
+		static ref LABEL : Regex = Regex::new(r"^(?P<label>\w+)\s*:\s*").unwrap();
+		static ref CLAUSE : Regex = Regex::new(r"^(?:case\b|default\s*:)").unwrap();
+	}
+
+	let bytes = body.as_bytes();
+	let is_word = |i: usize| bytes[i] == b'_' || bytes[i].is_ascii_alphanumeric();
+	let mut clauses : Vec<Clause> = vec![];
+	let mut depth = 0;
+	let mut i = 0;
+	while i < bytes.len() {
+		match bytes[i] {
+			b'(' | b'[' | b'{' => depth += 1,
+			b')' | b']' | b'}' => depth -= 1,
+			_ if depth == 0 && body.is_char_boundary(i) && (i == 0 || !is_word(i - 1)) => {
+				let mut labels = vec![];
+				let mut j = i;
+				while let Some(cap) = LABEL.captures(&body[j..]) {
+					let label = cap.name("label").unwrap().as_str();
+					if label == "default" || label == "case" {
+						break;
+					}
+					labels.push(label.to_string());
+					j += cap.get(0).unwrap().end();
+				}
+				if CLAUSE.is_match(&body[j..]) {
+					let (pattern, body_start) = if body[j..].starts_with("case") {
+						let colon = match header_end(&body[j..]) {
+							Some(colon) => j + colon,
+							None => break,
+						};
+						(Some(body[j + 4..colon].trim().to_string()), colon + 1)
+					} else {
+						(None, j + body[j..].find(':').unwrap() + 1)
+					};
+					if let Some(last) = clauses.last_mut() {
+						last.end = i;
+					}
+					clauses.push(Clause { labels, pattern, start: i, body_start, end: body.len() });
+					i = body_start;
+					continue;
+				}
+			},
+			_ => {}
+		}
+		i += 1;
+	}
+	clauses
+}
+
+/// The position of the `:` ending the header of a case
+fn header_end(clause: &str) -> Option<usize> {
+	let mut depth = 0;
+	for (i, c) in clause.char_indices() {
+		match c {
+			'(' | '[' | '{' => depth += 1,
+			')' | ']' | '}' => depth -= 1,
+			':' if depth == 0 => return Some(i),
+			_ => {}
+		}
+	}
+	None
+}
+
+fn transpile_clauses(body: &str, clauses: &[Clause], object: bool, map: Option<&str>, temps: &mut usize) -> String {
+	lazy_static! {
+		static ref TERMINATOR : Regex = Regex::new(r"(?:^|[;{}:])\s*(?:break|continue|return|throw|goto|rethrow)\b[^;{}]*;\s*$").unwrap();
+	}
+
+	let targets = clauses.iter().flat_map(|clause| {
+		clause.labels.iter().map(move |label| (label.clone(), match &clause.pattern {
+			Some(pattern) => format!("goto case {};", pattern),
+			None => String::from("goto default;"),
+		}))
+	}).collect::<HashMap<String, String>>();
+
+	let mut result = String::new();
+	result.push_str(&body[..clauses.first().map(|clause| clause.start).unwrap_or(body.len())]);
+	for clause in clauses {
+		match &clause.pattern {
+			// `case _:` would be a type pattern in C#
+			Some(pattern) if pattern == "_" => result.push_str("case var _:"),
+			Some(pattern) => result.push_str(&format!("case {}:", case_pattern(pattern, object, map, temps))),
+			None => result.push_str("default:"),
+		}
+		let mut statements = body[clause.body_start..clause.end].to_string();
+		for (label, goto) in targets.iter() {
+			let re = Regex::new(&format!(r"\bcontinue\s+{}\s*;", label)).unwrap();
+			statements = re.replace_all(&statements, goto.as_str()).to_string();
+		}
+		let content_end = statements.trim_end().len();
+		let is_empty = statements.chars().all(|c| c.is_whitespace() || mask::span_index(c).is_some());
+		if !is_empty && !TERMINATOR.is_match(&strip_comments(&statements[..content_end])) {
+			if statements[..content_end].contains('\n') {
+				let indent = utils::line_indent(&statements, content_end).to_string();
+				statements.insert_str(content_end, &format!("\n{}break;", indent));
+			} else {
+				statements.insert_str(content_end, " break;");
+			}
+		}
+		result.push_str(&statements);
+	}
+	result
+}
+
+/// Transpile the pattern of a case or an arm, with its guard, for a
+/// subject of type object if `object`, or a map with the type arguments `map`
+fn case_pattern(pattern: &str, object: bool, map: Option<&str>, temps: &mut usize) -> String {
+	let (map_pattern, guard) = match patterns::split_guard(pattern) {
+		Some((map_pattern, guard)) => (map_pattern.trim(), Some(guard)),
+		None => (pattern.trim(), None),
+	};
+	if !map_pattern.starts_with('{') {
+		return if object {
+			patterns::transpile_object_pattern(pattern)
+		} else {
+			patterns::transpile_pattern(pattern)
+		};
+	}
+	let (test, mut conditions) = patterns::map_pattern(map_pattern, map, temps);
+	conditions.extend(guard.map(String::from));
+	if conditions.is_empty() {
+		test
+	} else {
+		format!("{} when {}", test, conditions.join(" && "))
+	}
+}
+
+/// Remove the masked comments at the end, which may follow the terminator
+fn strip_comments(statements: &str) -> String {
+	statements.trim_end_matches(|c: char| c.is_whitespace() || mask::span_index(c).is_some()).to_string()
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn switch_statement() {
+		assert_eq!(
+			transpile_switches(r"
+    switch (unit) {
+      case CssLengthUnit.em:
+        value = 1;
+        break;
+      case CssLengthUnit.px:
+      case CssLengthUnit.pt:
+        value = 2;
+      retry:
+      case CssLengthUnit.auto:
+        if (a) continue retry;
+        return;
+      default:
+        if (b) {
+          continue retry;
+        }
+    }"),
+			r"
+    switch (unit) {
+      case CssLengthUnit.em:
+        value = 1;
+        break;
+      case CssLengthUnit.px:
+      case CssLengthUnit.pt:
+        value = 2;
+        break;
+      case CssLengthUnit.auto:
+        if (a) goto case CssLengthUnit.auto;
+        return;
+      default:
+        if (b) {
+          goto case CssLengthUnit.auto;
+        }
+        break;
+    }"
+		);
+	}
+
+	#[test]
+	fn pattern_cases() {
+		assert_eq!(
+			transpile_switches(r"
+  switch (shape) {
+    case Square(length: var l) when l > 0:
+      return l * l;
+    case Circle(:final radius) || Oval(radius: 1):
+      return radius;
+    case final int x?:
+      return x;
+    case _:
+      throw StateError('');
+  }"),
+			r"
+  switch (shape) {
+    case Square { length: var l } when l > 0:
+      return l * l;
+    case Circle { radius: var radius } or Oval { radius: 1 }:
+      return radius;
+    case not null and int x:
+      return x;
+    case var _:
+      throw StateError('');
+  }"
+		);
+	}
+
+	#[test]
+	fn map_pattern_case() {
+		assert_eq!(
+			transpile_switches(r"
+  switch (json) {
+    case {1: var a} when a > 0:
+      print(a);
+  }
+  Dictionary<string, List<int>> counts = f();
+  switch (counts) {
+    case {'a': [var a, ...]}:
+      print(a);
+  }"),
+			r"
+  switch (json) {
+    case System.Collections.IDictionary _map2 when _map2.Contains(1) && _map2[1] is var a && a > 0:
+      print(a);
+      break;
+  }
+  Dictionary<string, List<int>> counts = f();
+  switch (counts) {
+    case IDictionary<string, List<int>> _map0 when _map0.TryGetValue('a', out var _value1) && _value1 is [var a, ..]:
+      print(a);
+      break;
+  }"
+		);
+	}
+
+	#[test]
+	fn object_list_case() {
+		assert_eq!(
+			transpile_switches(r"
+  object? value = f();
+  switch (value) {
+    case [int a, [_, int b]]:
+      print(a + b);
+  }"),
+			r"
+  object? value = f();
+  switch (value) {
+    case System.Collections.IList and [int a, System.Collections.IList and [_, int b]]:
+      print(a + b);
+      break;
+  }"
+		);
+	}
+
+	#[test]
+	fn switch_expression() {
+		assert_eq!(
+			transpile_switches(r"
+  final area = switch (shape.kind) {
+    Kind.square => switch (x) { 1 => a, _ => b },
+    Kind.circle when r > 0 => 3.14 * r * r,
+    _ => 0,
+  };"),
+			r"
+  final area = shape.kind switch {
+    Kind.square => x switch { 1 => a, _ => b },
+    Kind.circle when r > 0 => 3.14 * r * r,
+    _ => 0,
+  };"
+		);
+	}
+
+	#[test]
+	fn commented_switch_statement() {
+		let masked = mask::mask(r"
+  switch (x) { // first
+    case 1: list.forEach((e) => print(e)); break;
+    default: g();
+  }");
+		assert_eq!(
+			masked.unmask(&transpile_switches(&masked.text)),
+			r"
+  switch (x) { // first
+    case 1: list.forEach((e) => print(e)); break;
+    default: g(); break;
+  }"
+		);
+	}
+}
@@ -3,14 +3,23 @@ use crate::eregex::Element;
 use crate::eregex::TextPart;
 use regex::Regex;
 
+/// An identifier or keyword, with optional leading spaces
 pub struct Word {
 	element: BasicElement
 }
 
 impl Word {
-	fn new() -> Word {
-		let re = Regex::new(r"^\s*\b(\w+)\b").unwrap();
-		Word { element: BasicElement::new(re) }
+	pub fn new() -> Word {
+		lazy_static! {
+			static ref RE : Regex = Regex::new(r"^\s*\b(\w+)\b").unwrap();
+		}
+		Word { element: BasicElement::new(RE.clone()) }
+	}
+}
+
+impl Default for Word {
+	fn default() -> Word {
+		Word::new()
 	}
 }
 
@@ -21,6 +30,44 @@ impl Element for Word {
 	}
 }
 
+/// A fixed piece of punctuation such as `,`, `=` or `=>`,
+/// with optional leading spaces
+pub struct Symbol {
+	element: BasicElement
+}
+
+impl Symbol {
+	pub fn new(symbol: &str) -> Symbol {
+		let re = Regex::new(&format!(r"^\s*({})", regex::escape(symbol))).unwrap();
+		Symbol { element: BasicElement::new(re) }
+	}
+}
+
+impl Element for Symbol {
+	type Detail = TextPart;
+	fn find_at(&self, text: &str, start: usize) -> Option<(TextPart, TextPart)> {
+		self.element.find_at(text, start)
+	}
+}
+
+/// A type name, possibly generic and nullable, e.g. `Map<String, List<int>>?`.
+/// The Detail is the type name without the leading spaces.
+pub struct TypeName;
+
+impl Element for TypeName {
+	type Detail = TextPart;
+	fn find_at(&self, text: &str, start: usize) -> Option<(TextPart, TextPart)> {
+		let element = (
+			Word::new(),
+			(Symbol::new("<"), TypeName.separated_by(Symbol::new(",")), Symbol::new(">")).optional(),
+			Symbol::new("?").optional()
+		);
+		let (part, (name, _, _)) = element.find_at(text, start)?;
+		let end = part.end;
+		Some((part, TextPart::new(text, name.start, end)))
+	}
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -65,4 +112,37 @@ mod tests {
 				text: String::from("world")
 			}))));
 	}
-}
\ No newline at end of file
+
+	#[test]
+	fn find_type_name() {
+		let text = " Map<String, List<int>>? value";
+		let (part, name) = TypeName.find_at(text, 0).unwrap();
+		assert_eq!(part.text, " Map<String, List<int>>?");
+		assert_eq!(name.text, "Map<String, List<int>>?");
+
+		assert_eq!(TypeName.find_at("Iterable<String> styles", 0).unwrap().1.text, "Iterable<String>");
+		assert_eq!(TypeName.find_at("<int>", 0), None);
+	}
+
+	#[test]
+	fn find_parameter() {
+		let parameter = (
+			TypeName,
+			Word::new(),
+			(Symbol::new("="), Word::new()).optional()
+		);
+		let parameters = parameter.separated_by(Symbol::new(",")).allow_trailing();
+
+		let (part, items) = parameters.find_at("int a = b, Iterable<String> c,)", 0).unwrap();
+		assert_eq!(part.text, "int a = b, Iterable<String> c,");
+		let items = items.into_iter().map(|(rtype, name, default)| (
+			rtype.text,
+			name.text,
+			default.map(|(_, value)| value.text)
+		)).collect::<Vec<_>>();
+		assert_eq!(items, vec![
+			(String::from("int"), String::from("a"), Some(String::from("b"))),
+			(String::from("Iterable<String>"), String::from("c"), None),
+		]);
+	}
+}
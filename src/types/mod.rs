@@ -0,0 +1,150 @@
+use regex::Captures;
+use regex::Regex;
+use crate::config::Config;
+use crate::keywords;
+use crate::utils;
+
+/// Map the dart types to .NET types according to the configuration.
+///
+/// A type name is only mapped where it is used as a type:
+/// - before the name of a declaration, e.g. `String name`, `String? name`
+///   or `String f(`, which covers parameters, return types and fields
+/// - as a generic type, e.g. `Map<...>`
+/// - inside the type arguments of a generic type, e.g. `<String, int>`
+/// - inside record types, e.g. `(String, int)`
+/// - after `as`, `is` and `is!`
+///
+/// The raw collection types `List`, `Map` and `Set`, without type
+/// arguments, get the type arguments `dynamic`, which dart infers.
+///
+/// Static members of types, like `int.parse` or `String.fromCharCode`,
+/// are kept as they are.
+pub fn transpile_types(input: &str, config: &Config) -> String {
+	lazy_static! {
+		static ref RE : Regex = Regex::new(r"(?P<dot>\.\s*)?\b(?P<name>\w+)\b").unwrap();
+		static ref BEFORE_NAME : Regex = Regex::new(r"^\??\s+(?P<word>[\w@]+)").unwrap();
+		static ref AFTER_OPERATOR : Regex = Regex::new(r"\b(?:as|is!?)\s+$").unwrap();
+	}
+
+	let mut generics = type_arguments(input);
+	generics.extend(crate::records::record_types(input));
+	RE.replace_all(input, |cap: &Captures| -> String {
+		let whole = cap.get(0).unwrap().as_str();
+		let name = cap.name("name").unwrap();
+		let mapped = match config.types.get(name.as_str()) {
+			Some(mapped) => mapped,
+			None => return whole.to_string(),
+		};
+		if cap.name("dot").is_some() {
+			return whole.to_string();
+		}
+		let after = &input[name.end()..];
+		if after.trim_start().starts_with('.') {
+			return whole.to_string();
+		}
+		let open = name.end() + after.len() - after.trim_start().len();
+		let is_type = generics.iter().any(|(start, _)| *start == open)
+			|| BEFORE_NAME.captures(after).map(|cap| {
+				let word = cap.name("word").unwrap().as_str();
+				!keywords::is_dart_keyword(word) || keywords::is_declaring_keyword(word)
+			}).unwrap_or(false)
+			|| generics.iter().any(|(start, end)| *start < name.start() && name.end() <= *end)
+			|| AFTER_OPERATOR.is_match(&input[..name.start()]);
+		if !is_type {
+			whole.to_string()
+		} else if let Some(args) = raw_type_arguments(name.as_str()).filter(|_| !after.trim_start().starts_with('<')) {
+			format!("{}<{}>", mapped, args)
+		} else {
+			mapped.clone()
+		}
+	}).to_string()
+}
+
+/// The type arguments of the raw collection type, which are `dynamic`
+fn raw_type_arguments(name: &str) -> Option<&'static str> {
+	match name {
+		"List" | "Set" => Some("dynamic"),
+		"Map" => Some("dynamic, dynamic"),
+		_ => None,
+	}
+}
+
+/// Whether the .NET type is a value type, which is
+/// wrapped by `Nullable<T>` if marked by `?`
+pub fn is_value_type(name: &str) -> bool {
+	matches!(name,
+		"bool" | "byte" | "sbyte" | "char" | "decimal" | "double" | "float"
+		| "int" | "uint" | "long" | "ulong" | "short" | "ushort"
+		| "DateTime" | "TimeSpan")
+}
+
+/// Find the ranges of the type arguments `<...>` of generic types,
+/// generic invocations and collection literals like `<int>[]`. Comparison operators are told apart by the
+/// content between `<` and `>`, which can only be type names,
+/// `,`, `?`, `.`, spaces and more brackets.
+fn type_arguments(input: &str) -> Vec<(usize, usize)> {
+	lazy_static! {
+		static ref OPEN : Regex = Regex::new(r"(?P<word>\w\s*)?<").unwrap();
+		static ref LITERAL : Regex = Regex::new(r"^\s*[\[{]").unwrap();
+		static ref CONTENT : Regex = Regex::new(r"^[\w\s,<>?.@()\[\]]*$").unwrap();
+	}
+
+	OPEN.captures_iter(input).filter_map(|cap| {
+		let open = cap.get(0).unwrap().end() - 1;
+		let close = utils::matching_bracket(input, open)?;
+		if cap.name("word").is_none() && !LITERAL.is_match(&input[close + 1..]) {
+			return None;
+		}
+		if CONTENT.is_match(&input[open + 1..close]) {
+			Some((open, close))
+		} else {
+			None
+		}
+	}).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn types_mapped_1() {
+		assert_eq!(
+			transpile_types(r"
+public Iterable<String> f(Map<String, List<num>> a, Object b, String? c) {
+  final Set<int> s = a as Map;
+  List? l = b as List;
+  if (b is String && c is! Object) return <String>[];
+  var m = <String, Object>{};
+  num count = int.parse(c) + String.fromCharCode(1).length;
+  return List<String>.from(s);
+}", &Config::default()),
+			r"
+public IEnumerable<string> f(Dictionary<string, List<double>> a, object b, string? c) {
+  final HashSet<int> s = a as Dictionary<dynamic, dynamic>;
+  List<dynamic>? l = b as List<dynamic>;
+  if (b is string && c is! object) return <string>[];
+  var m = <string, object>{};
+  double count = int.parse(c) + String.fromCharCode(1).length;
+  return List<string>.from(s);
+}"
+		);
+	}
+
+	#[test]
+	fn types_mapped_2() {
+		assert_eq!(
+			transpile_types(r"
+Future<int> f(int num, Action<String, int> g) {
+  if (num < count && int > 0) print(num);
+  for (int i = 0; i < num; i++) {}
+}", &Config::default().with_type("int", "long")),
+			r"
+Task<long> f(long num, Action<string, long> g) {
+  if (num < count && int > 0) print(num);
+  for (long i = 0; i < num; i++) {}
+}"
+		);
+	}
+}
@@ -1,9 +1,262 @@
-pub fn is_keyword(word: &str) -> bool {
-	return word == "if"
-		  || word == "while"
-		  || word == "for"
-		  || word == "final"
-		  || word == "class";
+use regex::Regex;
+
+/// Given the position of an opening bracket `(`, `[`, `{` or `<`,
+/// find the position of the matching closing bracket. Only the same
+/// kind of brackets are counted, which is enough since strings and
+/// comments are masked.
+pub fn matching_bracket(text: &str, open: usize) -> Option<usize> {
+	let bytes = text.as_bytes();
+	let (left, right) = match bytes[open] {
+		b'(' => (b'(', b')'),
+		b'[' => (b'[', b']'),
+		b'{' => (b'{', b'}'),
+		b'<' => (b'<', b'>'),
+		_ => return None,
+	};
+	let mut depth = 0;
+	for (i, c) in bytes.iter().enumerate().skip(open) {
+		if *c == left {
+			depth += 1;
+		} else if *c == right {
+			depth -= 1;
+			if depth == 0 {
+				return Some(i);
+			}
+		}
+	}
+	None
+}
+
+/// Split the text by the separator, ignoring the separators
+/// enclosed in any kind of brackets. `<>` are not considered
+/// brackets here since they may also be operators.
+pub fn split_top_level(text: &str, separator: char) -> Vec<&str> {
+	let mut result = vec![];
+	let mut depth = 0;
+	let mut start = 0;
+	for (i, c) in text.char_indices() {
+		match c {
+			'(' | '[' | '{' => depth += 1,
+			')' | ']' | '}' => depth -= 1,
+			_ if c == separator && depth == 0 => {
+				result.push(&text[start..i]);
+				start = i + c.len_utf8();
+			},
+			_ => {}
+		}
+	}
+	result.push(&text[start..]);
+	result
+}
+
+/// Find the end of the expression starting at `start`, i.e. the first
+/// `;` or `,` out of brackets, or the closing bracket enclosing the
+/// expression, or the end of the text.
+pub fn expression_end(text: &str, start: usize) -> usize {
+	let mut depth = 0;
+	for (i, c) in text[start..].char_indices() {
+		match c {
+			'(' | '[' | '{' => depth += 1,
+			')' | ']' | '}' if depth == 0 => return start + i,
+			')' | ']' | '}' => depth -= 1,
+			';' | ',' if depth == 0 => return start + i,
+			_ => {}
+		}
+	}
+	text.len()
+}
+
+/// Split the type arguments of a generic type, e.g. `K, List<V>`
+/// into `K` and `List<V>`, or the elements of a record type
+pub fn split_type_arguments(args: &str) -> Vec<&str> {
+	let mut result = vec![];
+	let mut depth = 0;
+	let mut start = 0;
+	for (i, c) in args.char_indices() {
+		match c {
+			'<' | '(' | '{' | '[' => depth += 1,
+			'>' | ')' | '}' | ']' => depth -= 1,
+			',' if depth == 0 => {
+				result.push(args[start..i].trim());
+				start = i + 1;
+			},
+			_ => {}
+		}
+	}
+	result.push(args[start..].trim());
+	result
+}
+
+/// The end of the statement starting at `start`, a block, a simple
+/// statement ending with `;`, or a compound statement, whose body is
+/// itself a statement, like `if (x) for (...) { ... }`
+pub fn statement_end(text: &str, start: usize) -> Option<usize> {
+	lazy_static! {
+		static ref HEADER : Regex = Regex::new(r"^(?:(?:await\s+)?(?:for|foreach)|if|while|switch|lock|using|catch|when)\s*\(").unwrap();
+		static ref LABEL : Regex = Regex::new(r"^(?P<label>\w+)\s*:").unwrap();
+		static ref KEYWORD : Regex = Regex::new(r"^(?:do|else|try|catch|finally)\b").unwrap();
+	}
+
+	let start = skip_spaces(text, start);
+	let rest = &text[start..];
+	if rest.starts_with('{') {
+		return matching_bracket(text, start).map(|close| close + 1);
+	}
+	if let Some(cap) = HEADER.captures(rest) {
+		let close = matching_bracket(text, start + cap.get(0).unwrap().end() - 1)?;
+		let keyword = rest.split(|c: char| !c.is_alphanumeric()).next().unwrap();
+		let end = statement_end(text, close + 1)?;
+		return Some(match keyword {
+			"if" => else_end(text, end)?,
+			"catch" | "when" => handler_end(text, end)?,
+			_ => end,
+		});
+	}
+	if let Some(keyword) = KEYWORD.find(rest) {
+		let end = statement_end(text, start + keyword.end())?;
+		return match keyword.as_str() {
+			"do" => {
+				let condition = skip_spaces(text, end);
+				if !text[condition..].starts_with("while") {
+					return None;
+				}
+				let open = condition + text[condition..].find('(')?;
+				let close = matching_bracket(text, open)?;
+				Some(close + 1 + text[close + 1..].find(';')? + 1)
+			},
+			"try" | "catch" => handler_end(text, end),
+			_ => Some(end),
+		};
+	}
+	if let Some(cap) = LABEL.captures(rest) {
+		if !["case", "default"].contains(&&cap["label"]) && !rest[cap.get(0).unwrap().end()..].starts_with(':') {
+			return statement_end(text, start + cap.get(0).unwrap().end());
+		}
+	}
+	let statement = split_top_level(rest, ';')[0];
+	if start + statement.len() < text.len() {
+		Some(start + statement.len() + 1)
+	} else {
+		None
+	}
+}
+
+/// The end of the `if` statement whose body ends at `end`, including
+/// the `else` branch if any
+fn else_end(text: &str, end: usize) -> Option<usize> {
+	lazy_static! {
+		static ref ELSE : Regex = Regex::new(r"^\s*else\b").unwrap();
+	}
+
+	match ELSE.find(&text[end..]) {
+		Some(_) => statement_end(text, end),
+		None => Some(end),
+	}
+}
+
+/// The end of the `try` statement whose block or handler ends at `end`,
+/// including the following `catch` and `finally` handlers
+fn handler_end(text: &str, end: usize) -> Option<usize> {
+	lazy_static! {
+		static ref HANDLER : Regex = Regex::new(r"^\s*(?:catch|finally)\b").unwrap();
+	}
+
+	match HANDLER.find(&text[end..]) {
+		Some(_) => statement_end(text, end),
+		None => Some(end),
+	}
+}
+
+/// The position of the innermost bracket enclosing the position
+pub fn enclosing_open(text: &str, pos: usize) -> Option<usize> {
+	let mut depth = 0;
+	for (i, c) in text[..pos].char_indices().rev() {
+		match c {
+			')' | ']' | '}' => depth += 1,
+			'(' | '[' | '{' if depth == 0 => return Some(i),
+			'(' | '[' | '{' => depth -= 1,
+			_ => {}
+		}
+	}
+	None
+}
+
+/// The spaces at the start of the line containing the position
+pub fn line_indent(text: &str, pos: usize) -> &str {
+	let line_start = text[..pos].rfind('\n').map(|i| i + 1).unwrap_or(0);
+	let line = &text[line_start..];
+	&line[..line.len() - line.trim_start().len()]
+}
+
+/// The position of the first non-space character from `pos`
+pub fn skip_spaces(text: &str, pos: usize) -> usize {
+	pos + text[pos..].len() - text[pos..].trim_start().len()
+}
+
+/// Given the position of a closing bracket `)`, `]` or `}`,
+/// find the position of the matching opening bracket
+pub fn matching_open(text: &str, close: usize) -> Option<usize> {
+	let bytes = text.as_bytes();
+	let (left, right) = match bytes[close] {
+		b')' => (b'(', b')'),
+		b']' => (b'[', b']'),
+		b'}' => (b'{', b'}'),
+		_ => return None,
+	};
+	let mut depth = 0;
+	for i in (0..=close).rev() {
+		if bytes[i] == right {
+			depth += 1;
+		} else if bytes[i] == left {
+			depth -= 1;
+			if depth == 0 {
+				return Some(i);
+			}
+		}
+	}
+	None
+}
+
+/// The header of the block directly containing the position, i.e.
+/// the text between the previous statement and the `{`
+fn block_header(text: &str, pos: usize) -> Option<&str> {
+	let bytes = text.as_bytes();
+	let mut depth = 0;
+	let mut open = None;
+	for i in (0..pos).rev() {
+		match bytes[i] {
+			b'}' => depth += 1,
+			b'{' if depth == 0 => {
+				open = Some(i);
+				break;
+			},
+			b'{' => depth -= 1,
+			_ => {}
+		}
+	}
+	let open = open?;
+	let header_start = text[..open].rfind([';', '{', '}'])
+		.map(|i| i + 1).unwrap_or(0);
+	Some(&text[header_start..open])
+}
+
+/// Whether the position is directly inside the body of a class,
+/// mixin or extension
+pub fn in_class_body(text: &str, pos: usize) -> bool {
+	lazy_static! {
+		static ref CLASS : Regex = Regex::new(r"\b(?:class|mixin|extension)\b").unwrap();
+	}
+
+	block_header(text, pos).map(|header| CLASS.is_match(header)).unwrap_or(false)
+}
+
+/// The name of the class or mixin whose body directly contains the position
+pub fn enclosing_class(text: &str, pos: usize) -> Option<String> {
+	lazy_static! {
+		static ref CLASS : Regex = Regex::new(r"\b(?:class|mixin)\s+(?P<name>[\w@]+)").unwrap();
+	}
+
+	CLASS.captures(block_header(text, pos)?).map(|cap| cap["name"].to_string())
 }
 
 #[cfg(test)]
@@ -11,20 +264,68 @@ mod tests {
 	use super::*;
 
 	#[test]
-	fn is_keyword_test() {
-		assert!(is_keyword("if"));
-		assert!(is_keyword("while"));
-		assert!(is_keyword("for"));
-		assert!(is_keyword("final"));
-		assert!(is_keyword("class"));
+	fn bracket_matched() {
+		assert_eq!(matching_bracket("f(a, (b)) {}", 1), Some(8));
+		assert_eq!(matching_bracket("f(a, (b)) {}", 10), Some(11));
+		assert_eq!(matching_bracket("List<Map<K, V>> x", 4), Some(14));
+		assert_eq!(matching_bracket("f(a", 1), None);
+		assert_eq!(matching_bracket("f(a", 0), None);
+	}
+
+	#[test]
+	fn top_level_splitted() {
+		assert_eq!(split_top_level("a, f(b, c), {d, e}", ','), vec!["a", " f(b, c)", " {d, e}"]);
+		assert_eq!(split_top_level("", ','), vec![""]);
+	}
+
+	#[test]
+	fn expression_ended() {
+		assert_eq!(expression_end("x = f(a, b);", 4), 11);
+		assert_eq!(expression_end("[...a, b]", 4), 5);
+		assert_eq!(expression_end("f(a)", 2), 3);
+		assert_eq!(expression_end("a + b", 0), 5);
 	}
 
 	#[test]
-	fn not_keyword_test() {
-		assert!(!is_keyword("function"));
-		assert!(!is_keyword("def"));
-		assert!(!is_keyword("list"));
-		assert!(!is_keyword("set"));
-		assert!(!is_keyword("i f"));
-	}
-}
\ No newline at end of file
+	fn statement_ended() {
+		let text = "if (a) { f(); } print(1);";
+		assert_eq!(statement_end(text, 0), Some(15));
+		let text = "for (x in xs) for (y in ys) { break l; } print(1);";
+		assert_eq!(statement_end(text, 0), Some(40));
+		let text = "if (a) f(); else if (b) { g(); } else h(); print(1);";
+		assert_eq!(statement_end(text, 0), Some(42));
+		let text = "do x++; while (x < 3); print(1);";
+		assert_eq!(statement_end(text, 0), Some(22));
+		let text = "l: try { f(); } catch (e) {} finally {} print(1);";
+		assert_eq!(statement_end(text, 0), Some(39));
+		assert_eq!(statement_end("f(a)", 0), None);
+	}
+
+	#[test]
+	fn type_arguments_splitted() {
+		assert_eq!(split_type_arguments("string, List<Map<K, V>>"), vec!["string", "List<Map<K, V>>"]);
+		assert_eq!(split_type_arguments(" int "), vec!["int"]);
+		assert_eq!(split_type_arguments("(int, int) a, b"), vec!["(int, int) a", "b"]);
+	}
+
+	#[test]
+	fn class_body_found() {
+		let text = "class A<T> extends B {
+  int a;
+  void f() { int b; }
+}
+int c;";
+		assert!(in_class_body(text, text.find("int a").unwrap()));
+		assert!(!in_class_body(text, text.find("int b").unwrap()));
+		assert!(!in_class_body(text, text.find("int c").unwrap()));
+		assert_eq!(enclosing_class(text, text.find("int a").unwrap()), Some(String::from("A")));
+		assert_eq!(enclosing_class(text, text.find("int b").unwrap()), None);
+	}
+
+	#[test]
+	fn open_bracket_matched() {
+		assert_eq!(matching_open("f(a, (b)) {}", 8), Some(1));
+		assert_eq!(matching_open("f(a, (b)) {}", 7), Some(5));
+		assert_eq!(matching_open("a)", 1), None);
+	}
+}
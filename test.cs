@@ -1,25 +1,27 @@
+#nullable enable
+
 public NodeMetadata lazySet(NodeMetadata meta,
 BuildOp buildOp = null,
 Color color = null,
-bool decoOver = null,
-bool decoStrike = null,
-bool decoUnder = null,
+bool? decoOver = null,
+bool? decoStrike = null,
+bool? decoUnder = null,
 TextDecorationStyle decorationStyle = null,
 CssBorderStyle decorationStyleFromCssBorderStyle = null,
-String fontFamily = null,
-String fontSize = null,
-bool fontStyleItalic = null,
+string fontFamily = null,
+string fontSize = null,
+bool? fontStyleItalic = null,
 FontWeight fontWeight = null,
-bool isBlockElement = null,
-bool isNotRenderable = null,
-Iterable<BuildOp> parentOps = null,
-Iterable<String> styles = null,
-Iterable<String> stylesPrepend = null) {
-  meta ??= NodeMetadata();
+bool? isBlockElement = null,
+bool? isNotRenderable = null,
+IEnumerable<BuildOp> parentOps = null,
+IEnumerable<string> styles = null,
+IEnumerable<string> stylesPrepend = null) {
+  meta ??= new NodeMetadata();
 
   if (buildOp != null) {
-    meta._buildOps ??= [];
-    final ops = meta._buildOps as List<BuildOp>;
+    meta._buildOps ??= new List<BuildOp>();
+    var ops = (List<BuildOp>)meta._buildOps;
     if (ops.indexOf(buildOp) == -1) {
       ops.add(buildOp);
     }
@@ -33,15 +35,15 @@ Iterable<String> stylesPrepend = null) {
 
   if (decorationStyle != null) meta.decorationStyle = decorationStyle;
   if (decorationStyleFromCssBorderStyle != null) {
-    public switch() {
+    switch (decorationStyleFromCssBorderStyle) {
       case CssBorderStyle.dashed:
         meta.decorationStyle = TextDecorationStyle.dashed;
         break;
       case CssBorderStyle.dotted:
         meta.decorationStyle = TextDecorationStyle.dotted;
         break;
-      case CssBorderStyle.double:
-        meta.decorationStyle = TextDecorationStyle.double;
+      case CssBorderStyle.@double:
+        meta.decorationStyle = TextDecorationStyle.@double;
         break;
       case CssBorderStyle.solid:
         meta.decorationStyle = TextDecorationStyle.solid;
@@ -68,7 +70,7 @@ Iterable<String> stylesPrepend = null) {
   if (styles != null) {
     assert(styles.length % 2 == 0);
     assert(!meta._stylesFrozen);
-    meta._styles ??= [];
+    meta._styles ??= new List<string>();
     if (styles == stylesPrepend) {
       meta._styles.insertAll(0, styles);
     } else {
@@ -80,18 +82,18 @@ Iterable<String> stylesPrepend = null) {
 }
 
 class BuildOp {
-  final bool isBlockElement;
+  readonly bool isBlockElement;
 
   // op with lower priority will run first
-  final int priority;
+  readonly int priority;
 
-  final BuildOpDefaultStyles _defaultStyles;
-  final BuildOpOnChild _onChild;
-  final BuildOpOnPieces _onPieces;
-  final BuildOpOnWidgets _onWidgets;
+  readonly BuildOpDefaultStyles _defaultStyles;
+  readonly BuildOpOnChild _onChild;
+  readonly BuildOpOnPieces _onPieces;
+  readonly BuildOpOnWidgets _onWidgets;
 
   public BuildOp(BuildOpDefaultStyles defaultStyles = null,
-bool isBlockElement = null,
+bool? isBlockElement = null,
 BuildOpOnChild onChild = null,
 BuildOpOnPieces onPieces = null,
 BuildOpOnWidgets onWidgets = null,
@@ -103,38 +105,38 @@ this.priority = 10)  : _defaultStyles = defaultStyles,
 
   bool get hasOnChild => _onChild != null;
 
-  List<String> defaultStyles(NodeMetadata meta, dom.Element e) =>
+  public List<string> defaultStyles(NodeMetadata meta,
+Element e) =>
       _defaultStyles != null ? _defaultStyles(meta, e) : null;
 
   public NodeMetadata onChild(NodeMetadata meta,
 Element e) =>
       _onChild != null ? _onChild(meta, e) : meta;
 
-  Iterable<BuiltPiece> onPieces(
-    NodeMetadata meta,
-    Iterable<BuiltPiece> pieces,
-  ) =>
+  public IEnumerable<BuiltPiece> onPieces(NodeMetadata meta,
+IEnumerable<BuiltPiece> pieces) =>
       _onPieces != null ? _onPieces(meta, pieces) : pieces;
 
-  Iterable<Widget> onWidgets(NodeMetadata meta, Iterable<Widget> widgets) =>
+  public IEnumerable<Widget> onWidgets(NodeMetadata meta,
+IEnumerable<Widget> widgets) =>
       (_onWidgets != null ? _onWidgets(meta, widgets) : null) ?? widgets;
 }
 
-typedef Iterable<String> BuildOpDefaultStyles(
+typedef IEnumerable<string> BuildOpDefaultStyles(
   NodeMetadata meta,
   dom.Element e,
 );
 typedef NodeMetadata BuildOpOnChild(NodeMetadata meta, dom.Element e);
-typedef Iterable<BuiltPiece> BuildOpOnPieces(
+typedef IEnumerable<BuiltPiece> BuildOpOnPieces(
   NodeMetadata meta,
-  Iterable<BuiltPiece> pieces,
+  IEnumerable<BuiltPiece> pieces,
 );
-typedef Iterable<Widget> BuildOpOnWidgets(
-    NodeMetadata meta, Iterable<Widget> widgets);
+typedef IEnumerable<Widget> BuildOpOnWidgets(
+    NodeMetadata meta, IEnumerable<Widget> widgets);
 
 class BuilderContext {
-  final BuildContext context;
-  final Widget origin;
+  readonly BuildContext context;
+  readonly Widget origin;
 
   BuilderContext(this.context, this.origin);
 }
@@ -143,12 +145,12 @@ abstract class BuiltPiece {
   bool get hasWidgets;
 
   TextBlock get block;
-  Iterable<Widget> get widgets;
+  IEnumerable<Widget> get widgets;
 }
 
 class BuiltPieceSimple extends BuiltPiece {
-  final TextBlock block;
-  final Iterable<Widget> widgets;
+  readonly TextBlock block;
+  readonly IEnumerable<Widget> widgets;
 
   public BuiltPieceSimple(this.block = null,
 this.widgets = null) : assert((block == null) != (widgets == null));
@@ -162,7 +164,7 @@ class CssBorderSide {
   CssLength width;
 }
 
-enum CssBorderStyle { dashed, dotted, double, solid }
+public enum CssBorderStyle { dashed, dotted, @double, solid }
 
 class CssBorders {
   CssBorderSide bottom;
@@ -187,16 +189,18 @@ class CssMargin {
 CssLength left = null,
 CssLength right = null,
 CssLength top = null) =>
-      CssMargin()
-        ..bottom = bottom ?? this.bottom
-        ..left = left ?? this.left
-        ..right = right ?? this.right
-        ..top = top ?? this.top;
+      new CssMargin()
+      {
+        bottom = bottom ?? this.bottom,
+        left = left ?? this.left,
+        right = right ?? this.right,
+        top = top ?? this.top
+      };
 }
 
 class CssLength {
-  final double number;
-  final CssLengthUnit unit;
+  readonly double number;
+  readonly CssLengthUnit unit;
 
   public CssLength(this.number,
 this.unit = CssLengthUnit.px)  : assert(!number.isNegative),
@@ -208,7 +212,7 @@ this.unit = CssLengthUnit.px)  : assert(!number.isNegative),
 TextStyleBuilders tsb) {
     double value;
 
-    public switch(this.unit) {
+    switch (this.unit) {
       case CssLengthUnit.em:
         value = tsb.build(bc).fontSize * number / 1;
         break;
@@ -225,15 +229,15 @@ TextStyleBuilders tsb) {
   }
 }
 
-enum CssLengthUnit {
+public enum CssLengthUnit {
   em,
   px,
 }
 
 class NodeMetadata {
-  Iterable<BuildOp> _buildOps;
+  IEnumerable<BuildOp> _buildOps;
   dom.Element _domElement;
-  Iterable<BuildOp> _parentOps;
+  IEnumerable<BuildOp> _parentOps;
   TextStyleBuilders _tsb;
 
   Color color;
@@ -241,13 +245,13 @@ class NodeMetadata {
   bool decoStrike;
   bool decoUnder;
   TextDecorationStyle decorationStyle;
-  String fontFamily;
-  String fontSize;
+  string fontFamily;
+  string fontSize;
   bool fontStyleItalic;
   FontWeight fontWeight;
   bool _isBlockElement;
   bool isNotRenderable;
-  List<String> _styles;
+  List<string> _styles;
   bool _stylesFrozen = false;
 
   dom.Element get domElement => _domElement;
@@ -256,9 +260,9 @@ class NodeMetadata {
 
   bool get hasParents => _parentOps != null;
 
-  Iterable<BuildOp> get ops => _buildOps;
+  IEnumerable<BuildOp> get ops => _buildOps;
 
-  Iterable<BuildOp> get parents => _parentOps;
+  IEnumerable<BuildOp> get parents => _parentOps;
 
   TextStyleBuilders get tsb => _tsb;
 
@@ -267,7 +271,7 @@ class NodeMetadata {
     _domElement = e;
 
     if (_buildOps != null) {
-      final ops = _buildOps as List;
+      var ops = (List<dynamic>)_buildOps;
       ops.sort((a, b) => a.priority.compareTo(b.priority));
       _buildOps = List.unmodifiable(ops);
     }
@@ -283,13 +287,13 @@ class NodeMetadata {
     return _buildOps?.where((o) => o.isBlockElement)?.length?.compareTo(0) == 1;
   }
 
-  public void styles(Action<String, String> f) {
+  public void styles(Action<string, string> f) {
     _stylesFrozen = true;
     if (_styles == null) return;
 
-    final iterator = _styles.iterator;
+    var iterator = _styles.iterator;
     while (iterator.moveNext()) {
-      final key = iterator.current;
+      var key = iterator.current;
       if (!iterator.moveNext()) return;
       f(key, iterator.current);
     }
@@ -312,10 +316,10 @@ abstract class TextBit {
 }
 
 class DataBit extends TextBit {
-  final TextBlock block;
-  final String data;
-  final VoidCallback onTap;
-  final TextStyleBuilders tsb;
+  readonly TextBlock block;
+  readonly string data;
+  readonly VoidCallback onTap;
+  readonly TextStyleBuilders tsb;
 
   public DataBit(this.block,
 this.data,
@@ -325,10 +329,10 @@ this.onTap = null)
         assert(data != null),
         assert(tsb != null);
 
-  public DataBit rebuild(String data = null,
+  public DataBit rebuild(string data = null,
 VoidCallback onTap = null,
 TextStyleBuilders tsb = null) =>
-      DataBit(
+      new DataBit(
         block,
         data ?? this.data,
         tsb ?? this.tsb,
@@ -337,11 +341,11 @@ TextStyleBuilders tsb = null) =>
 }
 
 class SpaceBit extends TextBit {
-  final TextBlock block;
-  String _data;
+  readonly TextBlock block;
+  string _data;
 
   public SpaceBit(this.block,
-String data = null)
+string data = null)
       : assert(block != null),
         _data = data;
 
@@ -351,8 +355,8 @@ String data = null)
 }
 
 class WidgetBit extends TextBit {
-  final TextBlock block;
-  final WidgetSpan widgetSpan;
+  readonly TextBlock block;
+  readonly WidgetSpan widgetSpan;
 
   public WidgetBit(this.block,
 this.widgetSpan)
@@ -362,9 +366,9 @@ this.widgetSpan)
   public WidgetBit rebuild(PlaceholderAlignment alignment = null,
 TextBaseline baseline = null,
 Widget child = null) =>
-      WidgetBit(
+      new WidgetBit(
         block,
-        WidgetSpan(
+        new WidgetSpan(
           alignment: alignment ?? this.widgetSpan.alignment,
           baseline: baseline ?? this.widgetSpan.baseline,
           child: child ?? this.widgetSpan.child,
@@ -373,9 +377,9 @@ Widget child = null) =>
 }
 
 class TextBlock extends TextBit {
-  final TextBlock parent;
-  final TextStyleBuilders tsb;
-  final _children = <TextBit>[];
+  readonly TextBlock parent;
+  readonly TextStyleBuilders tsb;
+  readonly List<TextBit> _children = new List<TextBit>();
 
   public TextBlock(this.tsb,
 this.parent = null) : assert(tsb != null);
@@ -385,8 +389,8 @@ this.parent = null) : assert(tsb != null);
 
   @override
   TextBit get first {
-    for (final child in _children) {
-      final first = child.first;
+    foreach (var child in _children) {
+      var first = child.first;
       if (first != null) return first;
     }
     return null;
@@ -397,7 +401,7 @@ this.parent = null) : assert(tsb != null);
 
   @override
   bool get isEmpty {
-    for (final child in _children) {
+    foreach (var child in _children) {
       if (child.isNotEmpty) {
         return false;
       }
@@ -411,26 +415,26 @@ this.parent = null) : assert(tsb != null);
   @override
   TextBit get last {
     if (_lastReturnsNull) return null;
-    final l = _children.length;
+    var l = _children.length;
     for (var i = l - 1; i >= 0; i--) {
-      final last = _children[i].last;
+      var last = _children[i].last;
       if (last != null) return last;
     }
 
     _lastReturnsNull = true;
-    final parentLast = parent?.last;
+    var parentLast = parent?.last;
     _lastReturnsNull = false;
     return parentLast;
   }
 
   TextBit get next {
     if (parent == null) return null;
-    final siblings = parent._children;
-    final indexOf = siblings.indexOf(this);
+    var siblings = parent._children;
+    var indexOf = siblings.indexOf(this);
     assert(indexOf != -1);
 
     for (var i = indexOf + 1; i < siblings.length; i++) {
-      final next = siblings[i].first;
+      var next = siblings[i].first;
       if (next != null) return next;
     }
 
@@ -438,38 +442,38 @@ this.parent = null) : assert(tsb != null);
   }
 
   public void addBit(TextBit bit,
-int index = null) =>
+int? index = null) =>
       _children.insert(index ?? _children.length, bit);
 
   public bool addSpace() {
-    final prev = last;
+    var prev = last;
     if (prev == null) {
       if (data == null) return false;
-    } else if (prev is SpaceBit) {
+    } else if (prev is SpaceBit spaceBit) {
       if (data == null) return false;
-      prev._data = "${prev._data ?? ''}$data";
+      spaceBit._data = $"{spaceBit._data ?? ""}{data}";
       return true;
     }
 
-    addBit(SpaceBit(this, data: data));
+    addBit(new SpaceBit(this, data: data));
     return true;
   }
 
-  public void addText(String data) => addBit(DataBit(this, data, tsb));
+  public void addText(string data) => addBit(new DataBit(this, data, tsb));
 
-  public void addWidget(WidgetSpan ws) => addBit(WidgetBit(this, ws));
+  public void addWidget(WidgetSpan ws) => addBit(new WidgetBit(this, ws));
 
   public bool forEachBit(TextBit bit,
 bool reversed = false) {
-    final l = _children.length;
-    final i0 = reversed ? l - 1 : 0;
-    final i1 = reversed ? -1 : l;
-    final ii = reversed ? -1 : 1;
+    var l = _children.length;
+    var i0 = reversed ? l - 1 : 0;
+    var i1 = reversed ? -1 : l;
+    var ii = reversed ? -1 : 1;
 
     for (var i = i0; i != i1; i += ii) {
-      final child = _children[i];
-      final shouldContinue = child is TextBlock
-          ? child.forEachBit(f, reversed: reversed)
+      var child = _children[i];
+      var shouldContinue = child is TextBlock childAsTextBlock
+          ? childAsTextBlock.forEachBit(f, reversed: reversed)
           : f(child, i);
       if (shouldContinue == false) return false;
     }
@@ -481,9 +485,9 @@ bool reversed = false) {
     var i = 0;
     var l = _children.length;
     while (i < l) {
-      final child = _children[i];
-      if (child is TextBlock) {
-        child.rebuildBits(f);
+      var child = _children[i];
+      if (child is TextBlock textBlock) {
+        textBlock.rebuildBits(f);
       } else {
         _children[i] = f(child);
       }
@@ -495,9 +499,9 @@ bool reversed = false) {
     while (true) {
       if (_children.isEmpty) return null;
 
-      final lastChild = _children.last;
-      if (lastChild is TextBlock) {
-        final removed = lastChild.removeLast();
+      var lastChild = _children.last;
+      if (lastChild is TextBlock lastChildAsTextBlock) {
+        var removed = lastChildAsTextBlock.removeLast();
         if (removed != null) {
           return removed;
         } else {
@@ -510,7 +514,7 @@ bool reversed = false) {
   }
 
   public TextBlock sub(TextStyleBuilders tsb) {
-    final sub = TextBlock(tsb, parent: this);
+    var sub = new TextBlock(tsb, parent: this);
     _children.add(sub);
     return sub;
   }
@@ -521,9 +525,9 @@ bool reversed = false) {
 }
 
 class TextStyleBuilders {
-  final _builders = <Function>[];
-  final _inputs = [];
-  final TextStyleBuilders parent;
+  readonly List<Function> _builders = new List<Function>();
+  readonly List<dynamic> _inputs = new List<dynamic>();
+  readonly TextStyleBuilders parent;
 
   BuilderContext _bc;
   TextStyle _output;
@@ -553,7 +557,7 @@ class TextStyleBuilders {
       _output = parent.build(_bc);
     }
 
-    final l = _builders.length;
+    var l = _builders.length;
     for (int i = 0; i < l; i++) {
       _output = _builders[i](this, _output, _inputs[i]);
     }
@@ -561,7 +565,7 @@ class TextStyleBuilders {
     return _output;
   }
 
-  public TextStyleBuilders sub() => TextStyleBuilders(parent: this);
+  public TextStyleBuilders sub() => new TextStyleBuilders(parent: this);
 
   void _resetContextIfNeeded(BuilderContext bc) {
     if (bc == _bc) return;